@@ -3,7 +3,8 @@ use std::io::{BufReader, Read};
 
 use criterion::{criterion_group, criterion_main, Criterion};
 use flate2::bufread::GzDecoder;
-use lhef::{Reader, Writer};
+use lhef::reader::ReaderBuilder;
+use lhef::{Reader, Writer, HEPEUP};
 
 fn criterion_benchmark(c: &mut Criterion) {
     let file = File::open("test_data/2j.lhe.gz").expect("file not found");
@@ -23,6 +24,28 @@ fn criterion_benchmark(c: &mut Criterion) {
         })
     });
 
+    let large_info = with_large_info(&event_txt, 100);
+    for skip_info in [false, true] {
+        let name = if skip_info {
+            "read large info, skipped"
+        } else {
+            "read large info"
+        };
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                let mut lhef = ReaderBuilder::new()
+                    .skip_info(skip_info)
+                    .build(large_info.as_bytes())
+                    .unwrap();
+                let mut nevents = 0;
+                while let Ok(Some(_)) = lhef.hepeup() {
+                    nevents += 1
+                }
+                assert_eq!(nevents, 1628);
+            })
+        });
+    }
+
     let mut events = Vec::new();
     let reader = BufReader::new(event_txt.as_slice());
     let mut lhef = Reader::new(reader).unwrap();
@@ -44,6 +67,52 @@ fn criterion_benchmark(c: &mut Criterion) {
             writer.finish().unwrap();
         })
     });
+
+    let large_event = large_event(50_000);
+    c.bench_function("write large event", |b| {
+        b.iter(|| {
+            let mut writer = Writer::new(std::io::sink(), "3.0").unwrap();
+            writer.heprup(lhef.heprup()).unwrap();
+            writer.hepeup(&large_event).unwrap();
+            writer.finish().unwrap();
+        })
+    });
+}
+
+// Add an `<mgrwt>` block with the given number of lines to each event
+fn with_large_info(event_txt: &[u8], nlines: usize) -> String {
+    let text = std::str::from_utf8(event_txt).unwrap();
+    let mut block = String::from("<mgrwt>\n");
+    for i in 0..nlines {
+        block +=
+            &format!("<pdfrwt beam=\"{i}\"> 1 21 0.1E+00 0.9E+02 </pdfrwt>\n");
+    }
+    block += "</mgrwt>\n</event>";
+    text.replace("</event>", &block)
+}
+
+// Synthetic event with the given number of particles
+fn large_event(nparticles: usize) -> HEPEUP {
+    let mut event = HEPEUP {
+        NUP: nparticles as i32,
+        IDRUP: 1,
+        XWGTUP: 0.25,
+        SCALUP: 91.188,
+        AQEDUP: 0.0078125,
+        AQCDUP: 0.118,
+        ..HEPEUP::empty()
+    };
+    for i in 0..nparticles {
+        let p = i as f64;
+        event.IDUP.push(21);
+        event.ISTUP.push(1);
+        event.MOTHUP.push([1, 2]);
+        event.ICOLUP.push([501 + i as i32, 502 + i as i32]);
+        event.PUP.push([p, -p, 0.5 * p, 2. * p, 0.]);
+        event.VTIMUP.push(0.);
+        event.SPINUP.push(9.);
+    }
+    event
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,21 +1,63 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
+
+use crate::stats::EventStats;
+use crate::status::OUTGOING;
+
+use itertools::izip;
 
 pub type XmlAttr = HashMap<String, String>;
 
+/// Floating-point type for the numbers in events and run information
+///
+/// This trait is implemented for `f64`, which is the default, and for
+/// `f32`, which halves the memory needed for the momenta and weights.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let file = std::fs::File::open("events.lhe").unwrap();
+/// let file = std::io::BufReader::new(file);
+/// let mut reader = lhef::Reader::new(file).unwrap();
+///
+/// let event: Option<lhef::HEPEUP<f32>> = reader.hepeup_as().unwrap();
+/// ```
+pub trait Float:
+    fast_float::FastFloat
+    + ryu::Float
+    + Copy
+    + Default
+    + PartialEq
+    + Debug
+    + Display
+    + Into<f64>
+{
+}
+
+impl Float for f32 {}
+impl Float for f64 {}
+
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 /// Generator run information
 ///
 /// See <https://arxiv.org/abs/hep-ph/0109068v1> for details on the fields.
+/// When changing the number of subprocesses `NPRUP`, the lengths of
+/// `XSECUP`, `XERRUP`, `XMAXUP`, and `LPRUP` have to be adjusted
+/// accordingly. Otherwise, the run information is rejected by the
+/// [Writer](crate::Writer).
 #[allow(non_snake_case)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(PartialEq, Debug, Clone)]
-pub struct HEPRUP {
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct HEPRUP<F = f64> {
     /// Beam IDs
     pub IDBMUP: [i32; 2],
     /// Beam energies
-    pub EBMUP: [f64; 2],
+    pub EBMUP: [F; 2],
     /// PDF groups
     pub PDFGUP: [i32; 2],
     /// PDF set IDs
@@ -25,38 +67,52 @@ pub struct HEPRUP {
     /// Number of subprocesses
     pub NPRUP: i32,
     /// Subprocess cross sections
-    pub XSECUP: Vec<f64>,
+    pub XSECUP: Vec<F>,
     /// Subprocess cross section errors
-    pub XERRUP: Vec<f64>,
+    pub XERRUP: Vec<F>,
     /// Subprocess maximum weights
-    pub XMAXUP: Vec<f64>,
+    pub XMAXUP: Vec<F>,
     /// Process IDs
     pub LPRUP: Vec<i32>,
     /// Optional run information
+    ///
+    /// See [HEPEUP::info] for how whitespace is treated.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "String::is_empty")
+    )]
     pub info: String,
     /// Attributes in `<init>` tag
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "HashMap::is_empty")
+    )]
     pub attr: XmlAttr,
 }
 
 /// Event information
 ///
 /// See <https://arxiv.org/abs/hep-ph/0109068v1> for details on the fields.
+/// When changing the number of particles `NUP`, the lengths of the
+/// particle vectors `IDUP`, `ISTUP`, etc. have to be adjusted
+/// accordingly. Otherwise, the event is rejected by the
+/// [Writer](crate::Writer).
 #[allow(non_snake_case)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(PartialEq, Debug, Clone)]
-pub struct HEPEUP {
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct HEPEUP<F = f64> {
     /// Number of particles
     pub NUP: i32,
     /// Process ID
     pub IDRUP: i32,
     /// Event weight
-    pub XWGTUP: f64,
+    pub XWGTUP: F,
     /// Scale in GeV
-    pub SCALUP: f64,
+    pub SCALUP: F,
     /// Value of the QED coupling α
-    pub AQEDUP: f64,
+    pub AQEDUP: F,
     /// Value of the QCD coupling α_s
-    pub AQCDUP: f64,
+    pub AQCDUP: F,
     /// Particle IDs
     pub IDUP: Vec<i32>,
     /// Particle status
@@ -66,15 +122,828 @@ pub struct HEPEUP {
     /// Colour flow
     pub ICOLUP: Vec<[i32; 2]>,
     /// Particle momentum in GeV
-    pub PUP: Vec<[f64; 5]>,
+    pub PUP: Vec<[F; 5]>,
     /// Lifetime in mm
-    pub VTIMUP: Vec<f64>,
+    pub VTIMUP: Vec<F>,
     /// Spin angle
-    pub SPINUP: Vec<f64>,
+    pub SPINUP: Vec<F>,
     /// Optional event information
+    ///
+    /// When reading, this contains all lines between the last particle
+    /// and the closing tag verbatim, including all whitespace and line
+    /// terminators. When writing, a line break is added if the
+    /// information does not end with one. Apart from this, the
+    /// information is written unchanged, so that reading and writing
+    /// again produces identical output.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "String::is_empty")
+    )]
     pub info: String,
     /// Attributes in `<event>` tag
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "HashMap::is_empty")
+    )]
     pub attr: XmlAttr,
+    /// Original text of the event block
+    ///
+    /// This is only set by readers with the
+    /// [preserve_raw](crate::reader::ReaderBuilder::preserve_raw)
+    /// option. If present, the writer emits this text unchanged instead
+    /// of formatting the other fields. Since `raw` is compared like
+    /// any other field, two events with the same content are only
+    /// equal if they either both lack or have the same original text.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub raw: Option<String>,
 }
 
+#[cfg(feature = "xml")]
 pub type XmlTree = xmltree::Element;
+
+/// Representation of the XML header
+#[cfg(feature = "xml")]
+pub(crate) type XmlHeader = XmlTree;
+/// Representation of the XML header
+#[cfg(not(feature = "xml"))]
+pub(crate) type XmlHeader = String;
+
+impl<F> HEPEUP<F> {
+    /// Check that the indices of decay mothers are consistent
+    ///
+    /// Each entry of `MOTHUP` has to be either zero or a 1-based index
+    /// of a particle in the event, i.e. at most the length of `MOTHUP`.
+    /// `NUP` is not taken into account.
+    ///
+    /// In addition, every nonzero mother index has to be smaller than
+    /// the index of its daughter. This is the usual convention, but not
+    /// required by the LHEF standard. Events listing a particle before
+    /// its mother are therefore rejected, even if they are otherwise
+    /// consistent.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     if let Err(err) = event.validate_mothers() {
+    ///         println!("Found inconsistent event: {err}");
+    ///     }
+    /// }
+    /// ```
+    pub fn validate_mothers(&self) -> Result<(), ValidationError> {
+        use ValidationError::*;
+        for (idx, mothers) in self.MOTHUP.iter().enumerate() {
+            let particle = idx + 1;
+            for &mother in mothers {
+                if mother < 0 || mother as usize > self.MOTHUP.len() {
+                    return Err(MotherOutOfRange { particle, mother });
+                }
+                if mother as usize >= particle {
+                    return Err(MotherAfterDaughter { particle, mother });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<F: Float> HEPEUP<F> {
+    // Name of the first entry that is not a finite number
+    pub(crate) fn find_nonfinite(&self) -> Option<String> {
+        let scalars = [
+            ("XWGTUP", self.XWGTUP),
+            ("SCALUP", self.SCALUP),
+            ("AQEDUP", self.AQEDUP),
+            ("AQCDUP", self.AQCDUP),
+        ];
+        if let Some((name, _)) = scalars.iter().find(|(_, x)| !is_finite(*x)) {
+            return Some(name.to_string());
+        }
+        for (i, p) in self.PUP.iter().enumerate() {
+            if let Some(j) = p.iter().position(|&x| !is_finite(x)) {
+                return Some(format!("PUP({}, {})", i + 1, j + 1));
+            }
+        }
+        if let Some(i) = self.VTIMUP.iter().position(|&x| !is_finite(x)) {
+            return Some(format!("VTIMUP({})", i + 1));
+        }
+        if let Some(i) = self.SPINUP.iter().position(|&x| !is_finite(x)) {
+            return Some(format!("SPINUP({})", i + 1));
+        }
+        None
+    }
+
+    /// Spin information of the particle with the given (0-based) index
+    ///
+    /// Interprets `SPINUP[idx]` according to the LHEF conventions: -1
+    /// and 1 denote definite left- and right-handed helicity, 9 denotes
+    /// unknown spin. Any other value is the cosine of the angle between
+    /// the spin and the three-momentum of the particle in the lab frame.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use lhef::Helicity;
+    ///
+    /// let mut event = lhef::HEPEUP::empty();
+    /// event.SPINUP = vec![-1., 9., 0.5];
+    /// assert_eq!(event.helicity(0), Helicity::Left);
+    /// assert_eq!(event.helicity(1), Helicity::Unknown);
+    /// assert_eq!(event.helicity(2), Helicity::Value(0.5));
+    /// ```
+    pub fn helicity(&self, idx: usize) -> Helicity {
+        let spin: f64 = self.SPINUP[idx].into();
+        if spin == -1. {
+            Helicity::Left
+        } else if spin == 1. {
+            Helicity::Right
+        } else if spin == 9. {
+            Helicity::Unknown
+        } else {
+            Helicity::Value(spin)
+        }
+    }
+}
+
+impl HEPEUP {
+    /// Create an event without particles
+    ///
+    /// All numbers are set to zero and all vectors are empty.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut event = lhef::HEPEUP::empty();
+    /// event.XWGTUP = 1.;
+    /// event.NUP = 1;
+    /// event.IDUP.push(22);
+    /// event.ISTUP.push(lhef::status::OUTGOING);
+    /// event.MOTHUP.push([0, 0]);
+    /// event.ICOLUP.push([0, 0]);
+    /// event.PUP.push([0., 0., 100., 100., 0.]);
+    /// event.VTIMUP.push(0.);
+    /// event.SPINUP.push(9.);
+    ///
+    /// # let mut output = vec![];
+    /// # let mut writer = lhef::Writer::new(&mut output, "1.0").unwrap();
+    /// # writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// # writer.hepeup(&event).unwrap();
+    /// ```
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Compute a hash of the event content
+    ///
+    /// Two events with identical content have the same hash, irrespective
+    /// of the order of the attributes in `attr`. The original text in the
+    /// `raw` field is ignored.
+    ///
+    /// Floating-point numbers are hashed according to their bit
+    /// pattern. This means that numerically equal values with
+    /// different representations, such as `0.0` and `-0.0`, lead to
+    /// different hashes. Hash values are not guaranteed to be the same
+    /// for different versions of Rust or this library.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let mut seen = std::collections::HashSet::new();
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     if !seen.insert(event.content_hash()) {
+    ///         println!("Found duplicate event");
+    ///     }
+    /// }
+    /// ```
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.NUP.hash(&mut hasher);
+        self.IDRUP.hash(&mut hasher);
+        let scalars = [self.XWGTUP, self.SCALUP, self.AQEDUP, self.AQCDUP];
+        for x in scalars {
+            x.to_bits().hash(&mut hasher);
+        }
+        self.IDUP.hash(&mut hasher);
+        self.ISTUP.hash(&mut hasher);
+        self.MOTHUP.hash(&mut hasher);
+        self.ICOLUP.hash(&mut hasher);
+        for p in &self.PUP {
+            for x in p {
+                x.to_bits().hash(&mut hasher);
+            }
+        }
+        for x in self.VTIMUP.iter().chain(self.SPINUP.iter()) {
+            x.to_bits().hash(&mut hasher);
+        }
+        self.info.hash(&mut hasher);
+        let mut attr = Vec::from_iter(&self.attr);
+        attr.sort_unstable();
+        attr.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Find outgoing particles with inconsistent masses
+    ///
+    /// Returns the (0-based) indices of all final-state particles for
+    /// which the stored mass `PUP[i][4]` deviates from the mass
+    /// reconstructed from the four-momentum by more than `rel_tol` times
+    /// the larger of the stored mass and the particle energy. Negative
+    /// reconstructed squared masses are treated as zero.
+    ///
+    /// For light particles, rounding errors of relative size δ in the
+    /// momentum components lead to a reconstructed mass of order √δ
+    /// times the energy. `rel_tol` should therefore be larger than the
+    /// square root of the precision of the momenta.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event = reader.hepeup().unwrap().unwrap();
+    /// for idx in event.check_masses(1e-6) {
+    ///     println!("Particle {idx} has an inconsistent mass");
+    /// }
+    /// ```
+    pub fn check_masses(&self, rel_tol: f64) -> Vec<usize> {
+        let particles = self.ISTUP.iter().zip(self.PUP.iter()).enumerate();
+        particles
+            .filter(|(_, (&status, _))| status == OUTGOING)
+            .filter(|(_, (_, p))| {
+                let m2 = p[3] * p[3] - p[0] * p[0] - p[1] * p[1] - p[2] * p[2];
+                let m = m2.max(0.).sqrt();
+                (m - p[4]).abs() > rel_tol * p[4].max(p[3])
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
+
+impl<F: Float> HEPRUP<F> {
+    // Name of the first entry that is not a finite number
+    pub(crate) fn find_nonfinite(&self) -> Option<String> {
+        if let Some(i) = self.EBMUP.iter().position(|&x| !is_finite(x)) {
+            return Some(format!("EBMUP({})", i + 1));
+        }
+        let subprocesses = [
+            ("XSECUP", &self.XSECUP),
+            ("XERRUP", &self.XERRUP),
+            ("XMAXUP", &self.XMAXUP),
+        ];
+        for (name, values) in subprocesses {
+            if let Some(i) = values.iter().position(|&x| !is_finite(x)) {
+                return Some(format!("{name}({})", i + 1));
+            }
+        }
+        None
+    }
+}
+
+fn is_finite<F: Float>(x: F) -> bool {
+    Into::<f64>::into(x).is_finite()
+}
+
+impl HEPRUP {
+    /// Create run information without subprocesses
+    ///
+    /// All numbers are set to zero and all vectors are empty.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Combine the run information of two compatible runs
+    ///
+    /// Both runs must have the same beams, PDFs, and weighting
+    /// strategy. The subprocesses of `other` are appended to those of
+    /// `self`. If a process ID appears in both runs, the cross sections
+    /// are added, the errors are added in quadrature, and the larger
+    /// maximum weight is kept. The optional information and attributes
+    /// are taken from `self`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("run1.lhe").unwrap();
+    /// let run1 = lhef::Reader::new(std::io::BufReader::new(file)).unwrap();
+    /// let file = std::fs::File::open("run2.lhe").unwrap();
+    /// let run2 = lhef::Reader::new(std::io::BufReader::new(file)).unwrap();
+    ///
+    /// let combined = run1.heprup().merge(run2.heprup()).unwrap();
+    /// ```
+    pub fn merge(&self, other: &HEPRUP) -> Result<HEPRUP, MergeError> {
+        if self.IDBMUP != other.IDBMUP || self.EBMUP != other.EBMUP {
+            return Err(MergeError::MismatchedBeams);
+        }
+        if self.PDFGUP != other.PDFGUP || self.PDFSUP != other.PDFSUP {
+            return Err(MergeError::MismatchedPdfs);
+        }
+        if self.IDWTUP != other.IDWTUP {
+            return Err(MergeError::MismatchedWeights(
+                self.IDWTUP,
+                other.IDWTUP,
+            ));
+        }
+        let mut merged = self.clone();
+        let subprocesses =
+            izip!(&other.XSECUP, &other.XERRUP, &other.XMAXUP, &other.LPRUP);
+        for (&xs, &xserr, &xsmax, &id) in subprocesses {
+            match merged.LPRUP.iter().position(|&i| i == id) {
+                Some(pos) => {
+                    merged.XSECUP[pos] += xs;
+                    merged.XERRUP[pos] = merged.XERRUP[pos].hypot(xserr);
+                    merged.XMAXUP[pos] = merged.XMAXUP[pos].max(xsmax);
+                }
+                None => {
+                    merged.XSECUP.push(xs);
+                    merged.XERRUP.push(xserr);
+                    merged.XMAXUP.push(xsmax);
+                    merged.LPRUP.push(id);
+                }
+            }
+        }
+        merged.NPRUP = merged.LPRUP.len() as i32;
+        Ok(merged)
+    }
+
+    /// Estimate the integrated luminosity of a sample
+    ///
+    /// The luminosity is computed as N_eff / σ, where N_eff is the
+    /// effective number of events, see [EventStats::effective_events],
+    /// and σ is the total cross section. Its unit is the inverse of the
+    /// unit of the cross section, usually pb^-1. The total cross section
+    /// depends on the weighting strategy `IDWTUP`:
+    ///
+    /// - `±1` and `±4`: σ is the mean event weight, i.e. the sum of
+    ///   weights divided by the number of events. This assumes that the
+    ///   event weights are given in the unit of the cross section.
+    /// - `±2` and `±3`: σ is the sum of the cross sections `XSECUP` of
+    ///   all subprocesses. This assumes that the statistics cover the
+    ///   complete sample.
+    ///
+    /// For other weighting strategies, the result is NaN.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let stats = reader.statistics().unwrap();
+    /// let lumi = reader.heprup().integrated_luminosity(&stats);
+    /// println!("Integrated luminosity: {lumi} / pb");
+    /// ```
+    pub fn integrated_luminosity(&self, stats: &EventStats) -> f64 {
+        let xs = match self.IDWTUP.abs() {
+            1 | 4 => stats.sum_weights / stats.n_events as f64,
+            2 | 3 => self.XSECUP.iter().sum(),
+            _ => f64::NAN,
+        };
+        stats.effective_events() / xs
+    }
+}
+
+/// Spin information of a particle
+///
+/// See [HEPEUP::helicity].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Helicity {
+    /// Left-handed helicity, `SPINUP` is -1
+    Left,
+    /// Right-handed helicity, `SPINUP` is 1
+    Right,
+    /// Unknown or unpolarised, `SPINUP` is 9
+    Unknown,
+    /// Cosine of the angle between spin and three-momentum
+    Value(f64),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    #[error("Mother index {mother} of particle {particle} is out of range")]
+    MotherOutOfRange { particle: usize, mother: i32 },
+    #[error("Mother {mother} of particle {particle} does not precede it")]
+    MotherAfterDaughter { particle: usize, mother: i32 },
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    #[error("Beam IDs or energies differ")]
+    MismatchedBeams,
+    #[error("PDF groups or sets differ")]
+    MismatchedPdfs,
+    #[error("Event weight specifications differ: {0} and {1}")]
+    MismatchedWeights(i32, i32),
+}
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impl {
+    use super::*;
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    // Particle entries in an event, used to determine the number of particles
+    type Particle<F> = (i32, i32, [i32; 2], [i32; 2], [F; 5], F, F);
+
+    // Arbitrary finite number, infinities and NaN are replaced by zero
+    fn finite<'a, F: Float + Arbitrary<'a>>(
+        u: &mut Unstructured<'a>,
+    ) -> Result<F> {
+        let x = F::arbitrary(u)?;
+        Ok(if x.into().is_finite() {
+            x
+        } else {
+            F::default()
+        })
+    }
+
+    fn finite_vec<'a, F: Float + Arbitrary<'a>>(
+        u: &mut Unstructured<'a>,
+        len: usize,
+    ) -> Result<Vec<F>> {
+        (0..len).map(|_| finite(u)).collect()
+    }
+
+    /// Generate arbitrary run information
+    ///
+    /// The numbers of entries in `XSECUP`, `XERRUP`, `XMAXUP`, and
+    /// `LPRUP` agree with `NPRUP` and all floating-point numbers are
+    /// finite. To ensure that the run information survives writing and
+    /// reading, `info` and `attr` are always empty.
+    impl<'a, F: Float + Arbitrary<'a>> Arbitrary<'a> for HEPRUP<F> {
+        #[allow(non_snake_case)]
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            let NPRUP = u.arbitrary_len::<(F, F, F, i32)>()?;
+            Ok(HEPRUP {
+                IDBMUP: u.arbitrary()?,
+                EBMUP: [finite(u)?, finite(u)?],
+                PDFGUP: u.arbitrary()?,
+                PDFSUP: u.arbitrary()?,
+                IDWTUP: u.arbitrary()?,
+                NPRUP: NPRUP as i32,
+                XSECUP: finite_vec(u, NPRUP)?,
+                XERRUP: finite_vec(u, NPRUP)?,
+                XMAXUP: finite_vec(u, NPRUP)?,
+                LPRUP: (0..NPRUP)
+                    .map(|_| u.arbitrary())
+                    .collect::<Result<_>>()?,
+                info: String::new(),
+                attr: XmlAttr::new(),
+            })
+        }
+    }
+
+    /// Generate an arbitrary event
+    ///
+    /// The numbers of entries in the particle properties agree with
+    /// `NUP` and all floating-point numbers are finite. To ensure that
+    /// the event survives writing and reading, `info` and `attr` are
+    /// always empty, and `raw` is `None`.
+    impl<'a, F: Float + Arbitrary<'a>> Arbitrary<'a> for HEPEUP<F> {
+        #[allow(non_snake_case)]
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            let NUP = u.arbitrary_len::<Particle<F>>()?;
+            let mut event = HEPEUP {
+                NUP: NUP as i32,
+                IDRUP: u.arbitrary()?,
+                XWGTUP: finite(u)?,
+                SCALUP: finite(u)?,
+                AQEDUP: finite(u)?,
+                AQCDUP: finite(u)?,
+                IDUP: Vec::with_capacity(NUP),
+                ISTUP: Vec::with_capacity(NUP),
+                MOTHUP: Vec::with_capacity(NUP),
+                ICOLUP: Vec::with_capacity(NUP),
+                PUP: Vec::with_capacity(NUP),
+                VTIMUP: finite_vec(u, NUP)?,
+                SPINUP: finite_vec(u, NUP)?,
+                info: String::new(),
+                attr: XmlAttr::new(),
+                raw: None,
+            };
+            for _ in 0..NUP {
+                event.IDUP.push(u.arbitrary()?);
+                event.ISTUP.push(u.arbitrary()?);
+                event.MOTHUP.push(u.arbitrary()?);
+                event.ICOLUP.push(u.arbitrary()?);
+                event.PUP.push([
+                    finite(u)?,
+                    finite(u)?,
+                    finite(u)?,
+                    finite(u)?,
+                    finite(u)?,
+                ]);
+            }
+            Ok(event)
+        }
+    }
+}
+
+#[cfg(test)]
+mod data_tests {
+    use super::*;
+
+    fn heprup() -> HEPRUP {
+        HEPRUP {
+            IDBMUP: [2212, 2212],
+            EBMUP: [7000.0, 7000.0],
+            PDFGUP: [0, 0],
+            PDFSUP: [230000, 230000],
+            IDWTUP: 2,
+            NPRUP: 2,
+            XSECUP: vec![10., 20.],
+            XERRUP: vec![3., 1.],
+            XMAXUP: vec![1., 2.],
+            LPRUP: vec![1, 2],
+            info: String::new(),
+            attr: XmlAttr::new(),
+        }
+    }
+
+    fn event() -> HEPEUP {
+        HEPEUP {
+            NUP: 4,
+            IDRUP: 1,
+            XWGTUP: 84515.12,
+            SCALUP: 91.188,
+            AQEDUP: 0.007546771,
+            AQCDUP: 0.1190024,
+            IDUP: vec![1, 21, 21, 1],
+            ISTUP: vec![-1, -1, 1, 1],
+            MOTHUP: vec![[0, 0], [0, 0], [1, 2], [1, 2]],
+            ICOLUP: vec![[503, 0], [501, 502], [503, 502], [501, 0]],
+            PUP: vec![
+                [0.0, 0.0, 4.7789443449, 4.7789443449, 0.0],
+                [0.0, 0.0, -1240.3761329, 1240.3761329, 0.0],
+                [37.283715118, 21.98166528, -1132.689358, 1133.5159684, 0.0],
+                [
+                    -37.283715118,
+                    -21.98166528,
+                    -102.90783056,
+                    111.63910879,
+                    0.0,
+                ],
+            ],
+            VTIMUP: vec![0.0, 0.0, 0.0, 0.0],
+            SPINUP: vec![1.0, -1.0, -1.0, 1.0],
+            info: String::new(),
+            attr: XmlAttr::new(),
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let event = HEPEUP::empty();
+        assert_eq!(event.NUP, 0);
+        assert_eq!(event.XWGTUP, 0.);
+        assert!(event.PUP.is_empty());
+        assert!(event.raw.is_none());
+        let heprup = HEPRUP::empty();
+        assert_eq!(heprup.NPRUP, 0);
+        assert!(heprup.XSECUP.is_empty());
+    }
+
+    #[test]
+    fn helicity() {
+        let mut ev = HEPEUP::empty();
+        ev.SPINUP = vec![-1., 1., 9., 0., -0.25];
+        assert_eq!(ev.helicity(0), Helicity::Left);
+        assert_eq!(ev.helicity(1), Helicity::Right);
+        assert_eq!(ev.helicity(2), Helicity::Unknown);
+        assert_eq!(ev.helicity(3), Helicity::Value(0.));
+        assert_eq!(ev.helicity(4), Helicity::Value(-0.25));
+    }
+
+    #[test]
+    fn find_nonfinite() {
+        let mut event = event();
+        assert_eq!(event.find_nonfinite(), None);
+        event.PUP[1][2] = f64::INFINITY;
+        assert_eq!(event.find_nonfinite(), Some("PUP(2, 3)".to_owned()));
+        event.XWGTUP = f64::NAN;
+        assert_eq!(event.find_nonfinite(), Some("XWGTUP".to_owned()));
+
+        let mut heprup = heprup();
+        assert_eq!(heprup.find_nonfinite(), None);
+        heprup.XMAXUP[1] = f64::NEG_INFINITY;
+        assert_eq!(heprup.find_nonfinite(), Some("XMAXUP(2)".to_owned()));
+    }
+
+    #[test]
+    fn content_hash() {
+        let mut ev = event();
+        ev.attr.insert("a".to_owned(), "1".to_owned());
+        ev.attr.insert("b".to_owned(), "2".to_owned());
+        ev.attr.insert("c".to_owned(), "3".to_owned());
+        let mut other = event();
+        other.attr.insert("c".to_owned(), "3".to_owned());
+        other.attr.insert("b".to_owned(), "2".to_owned());
+        other.attr.insert("a".to_owned(), "1".to_owned());
+        other.raw = Some(String::from("<event>"));
+        assert_eq!(ev.content_hash(), other.content_hash());
+        other.PUP[2][0] += 1e-10;
+        assert_ne!(ev.content_hash(), other.content_hash());
+    }
+
+    #[test]
+    fn valid_mothers() {
+        assert_eq!(event().validate_mothers(), Ok(()));
+    }
+
+    #[test]
+    fn mother_out_of_range() {
+        let mut event = event();
+        event.MOTHUP[3] = [1, 5];
+        assert_eq!(
+            event.validate_mothers(),
+            Err(ValidationError::MotherOutOfRange {
+                particle: 4,
+                mother: 5
+            })
+        );
+        event.MOTHUP[3] = [-1, 2];
+        assert!(event.validate_mothers().is_err());
+
+        // the number of particles is determined by MOTHUP, not NUP
+        let mut event = HEPEUP::empty();
+        event.NUP = 2;
+        event.MOTHUP = vec![[0, 0]];
+        assert_eq!(event.validate_mothers(), Ok(()));
+        event.MOTHUP[0] = [0, 2];
+        assert_eq!(
+            event.validate_mothers(),
+            Err(ValidationError::MotherOutOfRange {
+                particle: 1,
+                mother: 2
+            })
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let mut other = heprup();
+        other.NPRUP = 2;
+        other.XSECUP = vec![5., 30.];
+        other.XERRUP = vec![4., 2.];
+        other.XMAXUP = vec![3., 4.];
+        other.LPRUP = vec![1, 3];
+        let merged = heprup().merge(&other).unwrap();
+        assert_eq!(merged.NPRUP, 3);
+        assert_eq!(merged.XSECUP, [15., 20., 30.]);
+        assert_eq!(merged.XERRUP, [5., 1., 2.]);
+        assert_eq!(merged.XMAXUP, [3., 2., 4.]);
+        assert_eq!(merged.LPRUP, [1, 2, 3]);
+    }
+
+    #[test]
+    fn integrated_luminosity() {
+        let mut heprup = HEPRUP {
+            IDWTUP: 3,
+            NPRUP: 2,
+            XSECUP: vec![2., 3.],
+            XERRUP: vec![0., 0.],
+            XMAXUP: vec![1., 1.],
+            LPRUP: vec![1, 2],
+            ..HEPRUP::empty()
+        };
+        let mut stats = EventStats::new();
+        for _ in 0..1000 {
+            stats.add(1.);
+        }
+        assert_eq!(heprup.integrated_luminosity(&stats), 200.);
+        heprup.IDWTUP = -2;
+        assert_eq!(heprup.integrated_luminosity(&stats), 200.);
+
+        heprup.IDWTUP = 4;
+        let mut stats = EventStats::new();
+        for weight in [2., 2., 2., 2.] {
+            stats.add(weight);
+        }
+        assert_eq!(heprup.integrated_luminosity(&stats), 2.);
+        // a negative weight reduces the luminosity
+        heprup.IDWTUP = -1;
+        stats.add(-2.);
+        assert!((heprup.integrated_luminosity(&stats) - 1.5).abs() < 1e-12);
+
+        heprup.IDWTUP = 0;
+        assert!(heprup.integrated_luminosity(&stats).is_nan());
+    }
+
+    #[test]
+    fn merge_mismatched_beams() {
+        let mut other = heprup();
+        other.EBMUP = [6500.0, 6500.0];
+        assert_eq!(heprup().merge(&other), Err(MergeError::MismatchedBeams));
+        let mut other = heprup();
+        other.IDBMUP = [2212, -2212];
+        assert_eq!(heprup().merge(&other), Err(MergeError::MismatchedBeams));
+    }
+
+    #[test]
+    fn consistent_masses() {
+        assert!(event().check_masses(1e-8).is_empty());
+        let mut event = event();
+        event.PUP[3] = boosted_muon(1000.);
+        assert!(event.check_masses(1e-6).is_empty());
+    }
+
+    #[test]
+    fn inconsistent_mass() {
+        let mut event = event();
+        event.PUP[3][4] = 4.7;
+        assert_eq!(event.check_masses(1e-8), [3]);
+        // incoming particles are not checked
+        event.PUP[0][4] = 4.7;
+        assert_eq!(event.check_masses(1e-8), [3]);
+
+        // light particle with a large energy
+        let mut event = self::event();
+        event.PUP[2] = boosted_muon(1000.);
+        event.PUP[2][4] = 1.;
+        assert_eq!(event.check_masses(1e-6), [2]);
+    }
+
+    // Muon with the given energy moving along the z axis
+    fn boosted_muon(e: f64) -> [f64; 5] {
+        const M_MU: f64 = 0.1056583755;
+        [0., 0., (e * e - M_MU * M_MU).sqrt(), e, M_MU]
+    }
+
+    #[test]
+    fn mother_after_daughter() {
+        let mut event = event();
+        event.MOTHUP[2] = [1, 4];
+        assert_eq!(
+            event.validate_mothers(),
+            Err(ValidationError::MotherAfterDaughter {
+                particle: 3,
+                mother: 4
+            })
+        );
+    }
+
+    #[cfg(feature = "arbitrary")]
+    proptest::proptest! {
+        #[test]
+        fn arbitrary_round_trip(
+            bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..2048)
+        ) {
+            use arbitrary::Unstructured;
+
+            let mut u = Unstructured::new(&bytes);
+            let heprup: HEPRUP = u.arbitrary().unwrap();
+            let events: Vec<HEPEUP> = u.arbitrary().unwrap();
+            let mut writer = crate::Writer::new(vec![], "1.0").unwrap();
+            writer.heprup(&heprup).unwrap();
+            writer.write_events_ref(&events).unwrap();
+            writer.finish().unwrap();
+            let output = writer.into_inner();
+
+            let mut reader = crate::Reader::new(output.as_slice()).unwrap();
+            proptest::prop_assert_eq!(reader.heprup(), &heprup);
+            for event in events {
+                let read = reader.hepeup().unwrap();
+                proptest::prop_assert_eq!(read, Some(event));
+            }
+            proptest::prop_assert!(reader.hepeup().unwrap().is_none());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_skip_empty() {
+        let json = serde_json::to_value(HEPEUP::empty()).unwrap();
+        assert!(json.get("info").is_none());
+        assert!(json.get("attr").is_none());
+        assert!(json.get("XWGTUP").is_some());
+        let event: HEPEUP = serde_json::from_value(json).unwrap();
+        assert_eq!(event, HEPEUP::empty());
+
+        let mut event = HEPEUP::empty();
+        event.info = "<scales mups='1'/>".to_owned();
+        event.attr.insert("npLO".to_owned(), "1".to_owned());
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["info"], "<scales mups='1'/>");
+        assert_eq!(json["attr"]["npLO"], "1");
+        assert_eq!(serde_json::from_value::<HEPEUP>(json).unwrap(), event);
+
+        let json = serde_json::to_value(heprup()).unwrap();
+        assert!(json.get("info").is_none());
+        assert!(json.get("attr").is_none());
+        assert_eq!(serde_json::from_value::<HEPRUP>(json).unwrap(), heprup());
+    }
+}
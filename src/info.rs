@@ -0,0 +1,822 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::data::*;
+use crate::reader::extract_xml_attr;
+use crate::stats::EventStats;
+use crate::syntax::*;
+
+impl HEPEUP {
+    /// Extract the named weights from the `<rwgt>` block in `info`
+    ///
+    /// Returns the id and value of each `<wgt id='...'>value</wgt>`
+    /// entry in the order in which they appear. XML entities in the ids
+    /// are replaced by the corresponding characters. Entries without an
+    /// id or with a value that is not a number are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event = reader.hepeup().unwrap().unwrap();
+    /// for (id, weight) in event.named_weights() {
+    ///     println!("Weight {id}: {weight}");
+    /// }
+    /// ```
+    pub fn named_weights(&self) -> Vec<(String, f64)> {
+        find_weights(&self.info)
+            .into_iter()
+            .filter_map(|wgt| {
+                let value = self.info[wgt.value].trim();
+                let value = fast_float::parse(value).ok()?;
+                Some((wgt.id, value))
+            })
+            .collect()
+    }
+
+    /// Check that `XWGTUP` agrees with a named weight
+    ///
+    /// Compares the event weight `XWGTUP` to the named weight with the
+    /// given id, see [HEPEUP::named_weights]. If the relative deviation
+    /// |w - XWGTUP| / |XWGTUP| is larger than `rel_tol`, it is returned
+    /// as an error. If there is no weight with the given id, the error
+    /// is NaN.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut event = lhef::HEPEUP::empty();
+    /// event.XWGTUP = 2.;
+    /// event.add_named_weight("nominal", 2.2);
+    /// assert_eq!(event.check_default_weight("nominal", 0.2), Ok(()));
+    /// assert!(event.check_default_weight("nominal", 0.05).is_err());
+    /// ```
+    pub fn check_default_weight(
+        &self,
+        id: &str,
+        rel_tol: f64,
+    ) -> Result<(), f64> {
+        let Some((_, weight)) = self
+            .named_weights()
+            .into_iter()
+            .find(|(wgt_id, _)| wgt_id == id)
+        else {
+            return Err(f64::NAN);
+        };
+        if weight == self.XWGTUP {
+            return Ok(());
+        }
+        let deviation = (weight - self.XWGTUP).abs() / self.XWGTUP.abs();
+        if deviation <= rel_tol {
+            Ok(())
+        } else {
+            Err(deviation)
+        }
+    }
+
+    /// Set a named weight in the `<rwgt>` block in `info`
+    ///
+    /// If there already is a `<wgt>` entry with the given id, its value
+    /// is replaced. Otherwise, a new entry is added at the end of the
+    /// `<rwgt>` block, which is created if necessary. Special XML
+    /// characters in `id` are escaped. Since the event content changes,
+    /// the original text in `raw` is discarded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut event = lhef::HEPEUP::empty();
+    /// event.add_named_weight("scale_up", 0.5);
+    /// assert_eq!(event.named_weights(), [("scale_up".to_owned(), 0.5)]);
+    /// ```
+    pub fn add_named_weight(&mut self, id: &str, value: f64) {
+        let mut buffer = ryu::Buffer::new();
+        let value = buffer.format(value);
+        self.raw = None;
+        let weights = find_weights(&self.info);
+        if let Some(wgt) = weights.into_iter().find(|wgt| wgt.id == id) {
+            self.info.replace_range(wgt.value, value);
+            return;
+        }
+        let id = escape_attr(id);
+        let entry = format!("{WGT_START} id='{id}'>{value}{WGT_END}\n");
+        match find_block(&self.info, RWGT_START, RWGT_END) {
+            Some(block) => {
+                let mut pos = block.end;
+                if !self.info[..pos].ends_with('\n') {
+                    self.info.insert(pos, '\n');
+                    pos += 1;
+                }
+                self.info.insert_str(pos, &entry);
+            }
+            None => {
+                if !self.info.is_empty() && !self.info.ends_with('\n') {
+                    self.info.push('\n');
+                }
+                self.info += RWGT_START;
+                self.info += ">\n";
+                self.info += &entry;
+                self.info += RWGT_END;
+                self.info += "\n";
+            }
+        }
+    }
+}
+
+impl<F> HEPRUP<F> {
+    /// Extract the weight definitions from the `<initrwgt>` block in `info`
+    ///
+    /// Returns the id and name of each `<weight id='...'>name</weight>`
+    /// entry in the order in which they appear. The name is the text of
+    /// the entry without surrounding whitespace, or the id if the text
+    /// is empty. Entries without an id are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut heprup = lhef::HEPRUP::empty();
+    /// heprup.info = "<initrwgt>
+    /// <weightgroup name='scale'>
+    /// <weight id='1'> muR=0.5 </weight>
+    /// </weightgroup>
+    /// </initrwgt>"
+    ///     .to_owned();
+    /// assert_eq!(
+    ///     heprup.weight_definitions(),
+    ///     [("1".to_owned(), "muR=0.5".to_owned())]
+    /// );
+    /// ```
+    pub fn weight_definitions(&self) -> Vec<(String, String)> {
+        find_weight_definitions(&self.info)
+    }
+
+    /// Extract the `<xsecinfo>` tag from `info`
+    ///
+    /// Returns `None` if there is no `<xsecinfo>` tag, or if the
+    /// required attributes `neve` and `totxsec` are missing or cannot be
+    /// parsed. If the `maxweight` attribute is missing, it is set to the
+    /// default value of 1.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut heprup = lhef::HEPRUP::empty();
+    /// heprup.info = "<xsecinfo neve='1000' totxsec='2.5'/>".to_owned();
+    /// let xsecinfo = heprup.xsecinfo().unwrap();
+    /// assert_eq!(xsecinfo.neve, 1000);
+    /// assert_eq!(xsecinfo.totxsec, 2.5);
+    /// assert_eq!(xsecinfo.maxweight, 1.);
+    /// ```
+    pub fn xsecinfo(&self) -> Option<XsecInfo> {
+        let tag = find_tag(&self.info, XSECINFO_START)?;
+        let attr = extract_xml_attr(&self.info[tag], false).ok()?;
+        let maxweight = match attr.get("maxweight") {
+            Some(maxweight) => fast_float::parse(maxweight.trim()).ok()?,
+            None => 1.,
+        };
+        Some(XsecInfo {
+            neve: attr.get("neve")?.trim().parse().ok()?,
+            totxsec: fast_float::parse(attr.get("totxsec")?.trim()).ok()?,
+            maxweight,
+        })
+    }
+
+    /// Set the `<xsecinfo>` tag in `info`
+    ///
+    /// An existing `<xsecinfo>` tag is replaced. Otherwise, a new tag is
+    /// added at the end of `info`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut heprup = lhef::HEPRUP::empty();
+    /// let xsecinfo = lhef::XsecInfo {
+    ///     neve: 1000,
+    ///     totxsec: 2.5,
+    ///     maxweight: 0.01,
+    /// };
+    /// heprup.set_xsecinfo(&xsecinfo);
+    /// assert_eq!(heprup.xsecinfo(), Some(xsecinfo));
+    /// ```
+    pub fn set_xsecinfo(&mut self, xsecinfo: &XsecInfo) {
+        let tag = format!(
+            "{XSECINFO_START} neve='{}' totxsec='{}' maxweight='{}'/>",
+            xsecinfo.neve,
+            ryu::Buffer::new().format(xsecinfo.totxsec),
+            ryu::Buffer::new().format(xsecinfo.maxweight),
+        );
+        match find_tag(&self.info, XSECINFO_START) {
+            Some(mut range) => {
+                if !self.info[range.clone()].ends_with("/>") {
+                    let rest = &self.info[range.end..];
+                    if let Some(end) = rest.find(XSECINFO_END) {
+                        range.end += end + XSECINFO_END.len();
+                    }
+                }
+                self.info.replace_range(range, &tag);
+            }
+            None => {
+                if !self.info.is_empty() && !self.info.ends_with('\n') {
+                    self.info.push('\n');
+                }
+                self.info += &tag;
+                self.info.push('\n');
+            }
+        }
+    }
+}
+
+/// Cross section information from the `<xsecinfo>` tag of a run
+///
+/// See [HEPRUP::xsecinfo].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct XsecInfo {
+    /// Number of events
+    pub neve: u64,
+    /// Total cross section in pb
+    pub totxsec: f64,
+    /// Largest event weight
+    pub maxweight: f64,
+}
+
+impl From<&EventStats> for XsecInfo {
+    /// Cross section information for a sample with the given statistics
+    ///
+    /// The total cross section is the sum of the event weights, and the
+    /// largest weight is the largest absolute event weight.
+    fn from(stats: &EventStats) -> Self {
+        let maxweight = if stats.n_events == 0 {
+            0.
+        } else {
+            stats.max_weight.abs().max(stats.min_weight.abs())
+        };
+        Self {
+            neve: stats.n_events,
+            totxsec: stats.sum_weights,
+            maxweight,
+        }
+    }
+}
+
+impl<F> HEPEUP<F> {
+    /// Get an integer attribute of the `<event>` tag
+    ///
+    /// Returns `None` if there is no attribute with the given name or if
+    /// its value is not an integer. Surrounding whitespace is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event = reader.hepeup().unwrap().unwrap();
+    /// if let Some(np) = event.attr_i32("npLO") {
+    ///     println!("{np} additional partons at leading order");
+    /// }
+    /// ```
+    pub fn attr_i32(&self, name: &str) -> Option<i32> {
+        self.attr.get(name)?.trim().parse().ok()
+    }
+
+    /// Get a floating-point attribute of the `<event>` tag
+    ///
+    /// Returns `None` if there is no attribute with the given name or if
+    /// its value is not a number. Surrounding whitespace is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut event = lhef::HEPEUP::empty();
+    /// event.attr.insert("ptj".to_owned(), "2.5E+01".to_owned());
+    /// assert_eq!(event.attr_f64("ptj"), Some(25.));
+    /// assert_eq!(event.attr_f64("ptb"), None);
+    /// ```
+    pub fn attr_f64(&self, name: &str) -> Option<f64> {
+        fast_float::parse(self.attr.get(name)?.trim()).ok()
+    }
+
+    /// Extract the scales from the `<scales>` tag in `info`
+    ///
+    /// Returns `None` if there is no `<scales>` tag or its attributes
+    /// cannot be parsed. Attributes with names ending in an underscore
+    /// followed by an integer index, like `pt_clust_1`, are interpreted
+    /// as the scales of individual particles. Attributes with values that are not
+    /// numbers are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut event = lhef::HEPEUP::empty();
+    /// event.info = "<scales muf='91.2' pt_clust_3='20.5'/>".to_owned();
+    /// let scales = event.scales().unwrap();
+    /// assert_eq!(scales.scales["muf"], 91.2);
+    /// assert_eq!(scales.particle_scale("pt_clust", 3), Some(20.5));
+    /// ```
+    pub fn scales(&self) -> Option<Scales> {
+        let tag = find_tag(&self.info, SCALES_START)?;
+        let attr = extract_xml_attr(&self.info[tag], false).ok()?;
+        let mut scales = Scales::default();
+        for (name, value) in attr {
+            let Ok(value) = fast_float::parse(value.trim()) else {
+                continue;
+            };
+            let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+            let is_particle_scale = prefix.len() < name.len()
+                && prefix.len() > 1
+                && prefix.ends_with('_');
+            if is_particle_scale {
+                scales.particle_scales.insert(name, value);
+            } else {
+                scales.scales.insert(name, value);
+            }
+        }
+        Some(scales)
+    }
+}
+
+/// Scales from the `<scales>` tag of an event
+///
+/// See [HEPEUP::scales].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scales {
+    /// Scales that do not refer to a particle, e.g. `muf` or `mups`
+    pub scales: HashMap<String, f64>,
+    /// Scales of individual particles with the attribute names as keys,
+    /// e.g. `pt_clust_1`
+    pub particle_scales: HashMap<String, f64>,
+}
+
+impl Scales {
+    /// Get the scale with the given name for the particle with the given
+    /// index
+    ///
+    /// This looks up the scale called `{name}_{index}`, for example
+    /// `pt_clust_1` for name `pt_clust` and index 1.
+    pub fn particle_scale(&self, name: &str, index: usize) -> Option<f64> {
+        self.particle_scales
+            .get(&format!("{name}_{index}"))
+            .copied()
+    }
+}
+
+impl<F: PartialEq> HEPEUP<F> {
+    /// Compare events, treating `info` as XML
+    ///
+    /// This is like `==`, except that `info` is compared as XML, so
+    /// that for example the order of attributes does not matter. If
+    /// `info` cannot be parsed as XML, or the `xml` feature is
+    /// disabled, it is compared as a string. The original text in `raw`
+    /// is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let mut a = lhef::HEPEUP::empty();
+    /// a.info = "<weight a='1' b='2'/>".to_owned();
+    /// let mut b = a.clone();
+    /// b.info = "<weight b='2' a='1'/>".to_owned();
+    /// assert_ne!(a, b);
+    /// assert!(a.semantic_eq(&b));
+    /// ```
+    pub fn semantic_eq(&self, other: &Self) -> bool {
+        let HEPEUP {
+            NUP,
+            IDRUP,
+            XWGTUP,
+            SCALUP,
+            AQEDUP,
+            AQCDUP,
+            IDUP,
+            ISTUP,
+            MOTHUP,
+            ICOLUP,
+            PUP,
+            VTIMUP,
+            SPINUP,
+            info,
+            attr,
+            raw: _,
+        } = self;
+        *NUP == other.NUP
+            && *IDRUP == other.IDRUP
+            && *XWGTUP == other.XWGTUP
+            && *SCALUP == other.SCALUP
+            && *AQEDUP == other.AQEDUP
+            && *AQCDUP == other.AQCDUP
+            && *IDUP == other.IDUP
+            && *ISTUP == other.ISTUP
+            && *MOTHUP == other.MOTHUP
+            && *ICOLUP == other.ICOLUP
+            && *PUP == other.PUP
+            && *VTIMUP == other.VTIMUP
+            && *SPINUP == other.SPINUP
+            && *attr == other.attr
+            && info_eq(info, &other.info)
+    }
+}
+
+// Compare the content of `info` blocks as XML if possible
+fn info_eq(info: &str, other: &str) -> bool {
+    if info == other {
+        return true;
+    }
+    #[cfg(feature = "xml")]
+    {
+        // `info` can contain several elements, so add a common root
+        let parse = |info: &str| {
+            let text = format!("<info>{info}</info>");
+            XmlTree::parse(text.as_bytes()).ok()
+        };
+        if let (Some(info), Some(other)) = (parse(info), parse(other)) {
+            return info == other;
+        }
+    }
+    false
+}
+
+// Position of a `<wgt>` entry in `info`
+struct WeightEntry {
+    id: String,
+    value: Range<usize>,
+}
+
+// Find the range between the opening tag and the end marker of a block
+fn find_block(text: &str, start: &str, end: &str) -> Option<Range<usize>> {
+    let mut pos = 0;
+    while let Some(idx) = text[pos..].find(start) {
+        let tag_start = pos + idx;
+        pos = tag_start + start.len();
+        let rest = &text[pos..];
+        if rest.starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            let content_start = pos + rest.find('>')? + 1;
+            let content_end =
+                content_start + text[content_start..].find(end)?;
+            return Some(content_start..content_end);
+        }
+    }
+    None
+}
+
+// Find the range of the first opening tag with the given start
+fn find_tag(text: &str, start: &str) -> Option<Range<usize>> {
+    let mut pos = 0;
+    while let Some(idx) = text[pos..].find(start) {
+        let tag_start = pos + idx;
+        pos = tag_start + start.len();
+        let rest = &text[pos..];
+        if rest.starts_with(|c: char| "/>".contains(c) || c.is_whitespace()) {
+            let tag_end = pos + rest.find('>')? + 1;
+            return Some(tag_start..tag_end);
+        }
+    }
+    None
+}
+
+fn find_weights(info: &str) -> Vec<WeightEntry> {
+    let mut weights = Vec::new();
+    let Some(block) = find_block(info, RWGT_START, RWGT_END) else {
+        return weights;
+    };
+    let mut pos = block.start;
+    while let Some(value) =
+        find_block(&info[pos..block.end], WGT_START, WGT_END)
+    {
+        let value = pos + value.start..pos + value.end;
+        let tag_start = pos + info[pos..value.start].rfind(WGT_START).unwrap();
+        let tag = &info[tag_start..value.start];
+        pos = value.end + WGT_END.len();
+        if let Some(id) = extract_xml_attr(tag, false)
+            .ok()
+            .and_then(|mut attr| attr.remove("id"))
+        {
+            let id = unescape_attr(&id);
+            weights.push(WeightEntry { id, value });
+        }
+    }
+    weights
+}
+
+// Find the `<weight>` entries in the `<initrwgt>` block of `text`
+pub(crate) fn find_weight_definitions(text: &str) -> Vec<(String, String)> {
+    match find_block(text, INITRWGT_START, INITRWGT_END) {
+        Some(block) => weight_definitions_in(text, block),
+        None => Vec::new(),
+    }
+}
+
+// Find the `<weight>` entries in the `<weightgroup>` with the given
+// name or type in the `<initrwgt>` block of `text`
+pub(crate) fn find_weight_group(
+    text: &str,
+    group: &str,
+) -> Option<Vec<(String, String)>> {
+    let block = find_block(text, INITRWGT_START, INITRWGT_END)?;
+    let mut pos = block.start;
+    while let Some(content) =
+        find_block(&text[pos..block.end], WEIGHTGROUP_START, WEIGHTGROUP_END)
+    {
+        let content = pos + content.start..pos + content.end;
+        let tag_start =
+            pos + text[pos..content.start].rfind(WEIGHTGROUP_START).unwrap();
+        let tag = &text[tag_start..content.start];
+        pos = content.end + WEIGHTGROUP_END.len();
+        let Ok(attr) = extract_xml_attr(tag, false) else {
+            continue;
+        };
+        let name = attr.get("name").or_else(|| attr.get("type"));
+        if name.is_some_and(|name| name.trim() == group) {
+            return Some(weight_definitions_in(text, content));
+        }
+    }
+    None
+}
+
+// Escape special characters in an attribute value
+fn escape_attr(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&apos;",
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Replace the predefined XML entities in an attribute value
+fn unescape_attr(text: &str) -> String {
+    const ENTITIES: [(&str, char); 5] = [
+        ("&lt;", '<'),
+        ("&gt;", '>'),
+        ("&quot;", '"'),
+        ("&apos;", '\''),
+        ("&amp;", '&'),
+    ];
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        unescaped += &rest[..pos];
+        rest = &rest[pos..];
+        match ENTITIES.iter().find(|(name, _)| rest.starts_with(name)) {
+            Some((name, c)) => {
+                unescaped.push(*c);
+                rest = &rest[name.len()..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped += rest;
+    unescaped
+}
+
+// Find the `<weight>` entries in the given range of `text`
+fn weight_definitions_in(
+    text: &str,
+    range: Range<usize>,
+) -> Vec<(String, String)> {
+    let mut definitions = Vec::new();
+    let mut pos = range.start;
+    while let Some(name) =
+        find_block(&text[pos..range.end], WEIGHT_START, WEIGHT_END)
+    {
+        let name = pos + name.start..pos + name.end;
+        let tag_start =
+            pos + text[pos..name.start].rfind(WEIGHT_START).unwrap();
+        let tag = &text[tag_start..name.start];
+        pos = name.end + WEIGHT_END.len();
+        if let Some(id) = extract_xml_attr(tag, false)
+            .ok()
+            .and_then(|mut attr| attr.remove("id"))
+        {
+            let id = unescape_attr(&id);
+            let name = match text[name].trim() {
+                "" => id.clone(),
+                name => name.to_owned(),
+            };
+            definitions.push((id, name));
+        }
+    }
+    definitions
+}
+
+#[cfg(test)]
+mod info_tests {
+    use super::*;
+
+    #[test]
+    fn add_named_weight() {
+        let mut event = HEPEUP::empty();
+        assert!(event.named_weights().is_empty());
+        event.info = "<mgrwt>\n</mgrwt>".to_owned();
+        event.add_named_weight("1", 0.5);
+        assert_eq!(event.named_weights(), [("1".to_owned(), 0.5)]);
+        assert_eq!(
+            event.info,
+            "<mgrwt>\n</mgrwt>\n<rwgt>\n<wgt id='1'>0.5</wgt>\n</rwgt>\n"
+        );
+        event.add_named_weight("2", -3.);
+        event.add_named_weight("1", 2.);
+        assert_eq!(
+            event.named_weights(),
+            [("1".to_owned(), 2.), ("2".to_owned(), -3.)]
+        );
+        assert!(event.info.starts_with("<mgrwt>\n</mgrwt>\n<rwgt>\n"));
+
+        let mut event = HEPEUP::empty();
+        let id = "a'b\"c&d<e>f&amp;";
+        event.add_named_weight(id, 1.5);
+        assert!(event.info.contains("id='a&apos;b&quot;c&amp;d&lt;e&gt;f"));
+        assert_eq!(event.named_weights(), [(id.to_owned(), 1.5)]);
+        event.add_named_weight(id, 2.5);
+        assert_eq!(event.named_weights(), [(id.to_owned(), 2.5)]);
+    }
+
+    #[test]
+    fn named_weights() {
+        let mut event = HEPEUP::empty();
+        event.info = "<rwgt>
+<wgt id=\"a\"> 1.5E+01 </wgt>
+<wgt>2.</wgt>
+<wgt id='b'>nan?</wgt>
+<wgt id='c'>-2e-3</wgt></rwgt>"
+            .to_owned();
+        assert_eq!(
+            event.named_weights(),
+            [("a".to_owned(), 15.), ("c".to_owned(), -2e-3)]
+        );
+        event.add_named_weight("d", 1.);
+        assert!(event
+            .info
+            .ends_with("</wgt>\n<wgt id='d'>1.0</wgt>\n</rwgt>"));
+        assert_eq!(event.named_weights().len(), 3);
+    }
+
+    #[test]
+    fn typed_attributes() {
+        let text = "<event npLO=' 2 ' npNLO=\"-1\" wgt='1e-3' bad='x'>
+0 1 1 1 1 1
+</event>";
+        let event: HEPEUP = text.try_into().unwrap();
+        assert_eq!(event.attr_i32("npLO"), Some(2));
+        assert_eq!(event.attr_i32("npNLO"), Some(-1));
+        assert_eq!(event.attr_i32("wgt"), None);
+        assert_eq!(event.attr_i32("bad"), None);
+        assert_eq!(event.attr_i32("missing"), None);
+        assert_eq!(event.attr_f64("npLO"), Some(2.));
+        assert_eq!(event.attr_f64("wgt"), Some(1e-3));
+        assert_eq!(event.attr_f64("bad"), None);
+    }
+
+    #[test]
+    fn scales() {
+        let mut event = HEPEUP::empty();
+        assert_eq!(event.scales(), None);
+        event.info = "<rwgt>\n</rwgt>\n<scales_x a='1'/>\n\
+                      <scales muf='91.188' mups=\"-1\" mu2='4'
+                        pt_clust_1='10.5' pt_clust_2=' 2e1 ' mur_3='7'
+                        pt_clust_4='nan?'>\n</scales>\n"
+            .to_owned();
+        let scales = event.scales().unwrap();
+        assert_eq!(scales.scales.len(), 3);
+        assert_eq!(scales.scales["muf"], 91.188);
+        assert_eq!(scales.scales["mups"], -1.);
+        assert_eq!(scales.scales["mu2"], 4.);
+        assert_eq!(scales.particle_scales.len(), 3);
+        assert_eq!(scales.particle_scale("pt_clust", 1), Some(10.5));
+        assert_eq!(scales.particle_scale("pt_clust", 2), Some(20.));
+        assert_eq!(scales.particle_scale("mur", 3), Some(7.));
+        assert_eq!(scales.particle_scale("pt_clust", 3), None);
+        assert_eq!(scales.particle_scale("pt_clust", 4), None);
+
+        event.info = "<scales/>".to_owned();
+        assert_eq!(event.scales(), Some(Scales::default()));
+    }
+
+    #[test]
+    fn xsecinfo() {
+        let mut heprup = HEPRUP::empty();
+        assert_eq!(heprup.xsecinfo(), None);
+        heprup.info = "<generator>test</generator>
+<xsecinfo neve=\"100\" totxsec=\"1.5e3\" maxweight=\" 20.5 \"
+ meanweight=\"15\"></xsecinfo>"
+            .to_owned();
+        assert_eq!(
+            heprup.xsecinfo(),
+            Some(XsecInfo {
+                neve: 100,
+                totxsec: 1500.,
+                maxweight: 20.5
+            })
+        );
+        heprup.info = "<xsecinfo neve='-1' totxsec='1.5'/>".to_owned();
+        assert_eq!(heprup.xsecinfo(), None);
+        heprup.info = "<xsecinfo neve='1'/>".to_owned();
+        assert_eq!(heprup.xsecinfo(), None);
+    }
+
+    #[test]
+    fn set_xsecinfo() {
+        let xsecinfo = XsecInfo {
+            neve: 10,
+            totxsec: 1.5,
+            maxweight: 0.5,
+        };
+        let mut heprup = HEPRUP::empty();
+        heprup.info = "<generator>test</generator>".to_owned();
+        heprup.set_xsecinfo(&xsecinfo);
+        assert_eq!(
+            heprup.info,
+            "<generator>test</generator>
+<xsecinfo neve='10' totxsec='1.5' maxweight='0.5'/>\n"
+        );
+        assert_eq!(heprup.xsecinfo(), Some(xsecinfo));
+
+        heprup.info =
+            "<xsecinfo neve='1' totxsec='1'>\n</xsecinfo>\n<a/>".to_owned();
+        heprup.set_xsecinfo(&xsecinfo);
+        assert_eq!(
+            heprup.info,
+            "<xsecinfo neve='10' totxsec='1.5' maxweight='0.5'/>\n<a/>"
+        );
+    }
+
+    #[test]
+    fn xsecinfo_from_stats() {
+        let mut stats = EventStats::new();
+        assert_eq!(XsecInfo::from(&stats).maxweight, 0.);
+        for weight in [1., -3., 2.] {
+            stats.add(weight);
+        }
+        assert_eq!(
+            XsecInfo::from(&stats),
+            XsecInfo {
+                neve: 3,
+                totxsec: 0.,
+                maxweight: 3.
+            }
+        );
+    }
+
+    #[test]
+    fn check_default_weight() {
+        let mut event = HEPEUP::empty();
+        assert!(event.check_default_weight("0", 1e-6).unwrap_err().is_nan());
+        event.add_named_weight("0", 0.);
+        assert_eq!(event.check_default_weight("0", 0.), Ok(()));
+        event.XWGTUP = 0.5;
+        assert_eq!(event.check_default_weight("0", 1e-6), Err(1.));
+        event.add_named_weight("0", 0.5);
+        assert_eq!(event.check_default_weight("0", 0.), Ok(()));
+        event.add_named_weight("1", 0.500001);
+        assert_eq!(event.check_default_weight("1", 1e-5), Ok(()));
+        let deviation = event.check_default_weight("1", 1e-7).unwrap_err();
+        assert!((deviation - 2e-6).abs() < 1e-12);
+        event.add_named_weight("1", -0.5);
+        assert_eq!(event.check_default_weight("1", 1.), Err(2.));
+        event.XWGTUP = 0.;
+        assert_eq!(event.check_default_weight("1", 1e6), Err(f64::INFINITY));
+    }
+
+    #[test]
+    fn semantic_eq() {
+        let mut a = HEPEUP::empty();
+        a.info = "<rwgt>\n<wgt id='1' a=\"x\">0.5</wgt>\n</rwgt>\n\
+                  <scales pt_clust_1=\"1\" pt_clust_2=\"2\"></scales>\n"
+            .to_owned();
+        let mut b = a.clone();
+        b.info = "<rwgt>\n<wgt a='x' id=\"1\">0.5</wgt>\n</rwgt>\n\
+                  <scales pt_clust_2='2' pt_clust_1='1'/>\n"
+            .to_owned();
+        b.raw = Some(String::new());
+        assert_ne!(a, b);
+        #[cfg(feature = "xml")]
+        assert!(a.semantic_eq(&b));
+        #[cfg(not(feature = "xml"))]
+        assert!(!a.semantic_eq(&b));
+
+        b.info = a.info.replace("0.5", "0.6");
+        assert!(!a.semantic_eq(&b));
+        b.info = a.info.clone();
+        b.XWGTUP = 2.;
+        assert!(!a.semantic_eq(&b));
+
+        // not valid XML
+        a.info = "<unclosed>".to_owned();
+        b = a.clone();
+        assert!(a.semantic_eq(&b));
+        b.info = "<unclosed >".to_owned();
+        assert!(!a.semantic_eq(&b));
+    }
+}
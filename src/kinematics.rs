@@ -0,0 +1,589 @@
+use std::iter::Sum;
+use std::ops::{Add, Mul, Sub};
+
+use crate::data::{HEPEUP, HEPRUP};
+use crate::status::{INCOMING, OUTGOING};
+
+use itertools::izip;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// PDG ids of neutrinos
+const NEUTRINOS: [i32; 3] = [12, 14, 16];
+
+/// Four-momentum in GeV
+///
+/// # Example
+///
+/// ```rust
+/// use lhef::FourVector;
+///
+/// let p1 = FourVector::from([0., 0., 3., 5., 4.]);
+/// let p2 = FourVector::from([0., 0., -3., 5., 4.]);
+/// assert_eq!((p1 + p2).mass(), 10.);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FourVector {
+    /// Momentum in x direction
+    pub px: f64,
+    /// Momentum in y direction
+    pub py: f64,
+    /// Momentum in z direction
+    pub pz: f64,
+    /// Energy
+    pub e: f64,
+}
+
+impl FourVector {
+    /// Create a four-vector from its components
+    pub fn new(px: f64, py: f64, pz: f64, e: f64) -> Self {
+        Self { px, py, pz, e }
+    }
+
+    /// Minkowski product with metric (+, -, -, -)
+    pub fn dot(&self, other: &FourVector) -> f64 {
+        self.e * other.e
+            - self.px * other.px
+            - self.py * other.py
+            - self.pz * other.pz
+    }
+
+    /// Squared invariant mass
+    pub fn mass2(&self) -> f64 {
+        self.dot(self)
+    }
+
+    /// Invariant mass
+    ///
+    /// For space-like vectors with negative `mass2`, the result is zero.
+    pub fn mass(&self) -> f64 {
+        self.mass2().max(0.).sqrt()
+    }
+
+    /// Transverse momentum with respect to the z axis
+    pub fn pt(&self) -> f64 {
+        self.px.hypot(self.py)
+    }
+}
+
+impl Add for FourVector {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            px: self.px + other.px,
+            py: self.py + other.py,
+            pz: self.pz + other.pz,
+            e: self.e + other.e,
+        }
+    }
+}
+
+impl Sub for FourVector {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            px: self.px - other.px,
+            py: self.py - other.py,
+            pz: self.pz - other.pz,
+            e: self.e - other.e,
+        }
+    }
+}
+
+impl Mul<f64> for FourVector {
+    type Output = Self;
+
+    fn mul(self, factor: f64) -> Self {
+        Self {
+            px: factor * self.px,
+            py: factor * self.py,
+            pz: factor * self.pz,
+            e: factor * self.e,
+        }
+    }
+}
+
+impl Sum for FourVector {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |sum, p| sum + p)
+    }
+}
+
+impl From<[f64; 4]> for FourVector {
+    /// Convert from (px, py, pz, E)
+    fn from(p: [f64; 4]) -> Self {
+        Self::new(p[0], p[1], p[2], p[3])
+    }
+}
+
+impl From<[f64; 5]> for FourVector {
+    /// Convert from the `PUP` format (px, py, pz, E, m), ignoring the mass
+    fn from(p: [f64; 5]) -> Self {
+        Self::new(p[0], p[1], p[2], p[3])
+    }
+}
+
+impl From<FourVector> for [f64; 4] {
+    /// Convert to (px, py, pz, E)
+    fn from(p: FourVector) -> Self {
+        [p.px, p.py, p.pz, p.e]
+    }
+}
+
+impl From<FourVector> for [f64; 5] {
+    /// Convert to the `PUP` format (px, py, pz, E, m)
+    fn from(p: FourVector) -> Self {
+        [p.px, p.py, p.pz, p.e, p.mass()]
+    }
+}
+
+impl HEPEUP {
+    /// Transverse momentum of the particle with the given (0-based) index
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event = reader.hepeup().unwrap().unwrap();
+    /// for idx in 0..event.PUP.len() {
+    ///     println!("Particle {idx} has transverse momentum {}", event.pt(idx));
+    /// }
+    /// ```
+    pub fn pt(&self, idx: usize) -> f64 {
+        FourVector::from(self.PUP[idx]).pt()
+    }
+
+    /// Scalar sum of the transverse momenta of all final-state particles
+    pub fn ht(&self) -> f64 {
+        self.ht_of_matching(|_| true)
+    }
+
+    /// Scalar sum of the transverse momenta of final-state particles
+    /// with the given PDG ids
+    ///
+    /// Particles and antiparticles have to be listed separately.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event = reader.hepeup().unwrap().unwrap();
+    /// let partons = [-5, -4, -3, -2, -1, 1, 2, 3, 4, 5, 21];
+    /// println!("Partonic HT: {}", event.ht_of(&partons));
+    /// ```
+    pub fn ht_of(&self, ids: &[i32]) -> f64 {
+        self.ht_of_matching(|id| ids.contains(&id))
+    }
+
+    /// Missing transverse momentum
+    ///
+    /// This is the magnitude of the negative vector sum of the
+    /// transverse momenta of all visible final-state particles. All
+    /// final-state particles except for neutrinos are considered
+    /// visible.
+    pub fn missing_et(&self) -> f64 {
+        let visible: FourVector = izip!(&self.IDUP, &self.ISTUP, &self.PUP)
+            .filter(|&(id, &status, _)| {
+                status == OUTGOING && !NEUTRINOS.contains(&id.abs())
+            })
+            .map(|(_, _, &p)| FourVector::from(p))
+            .sum();
+        visible.pt()
+    }
+
+    /// Invariant mass of all final-state particles
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event = reader.hepeup().unwrap().unwrap();
+    /// println!("Final-state mass: {}", event.final_state_mass());
+    /// ```
+    pub fn final_state_mass(&self) -> f64 {
+        self.mass2_with_status(OUTGOING).max(0.).sqrt()
+    }
+
+    /// Squared partonic centre-of-mass energy ŝ
+    ///
+    /// This is the squared invariant mass of all incoming particles.
+    /// Its square root is the energy available in the hard scattering.
+    pub fn shat(&self) -> f64 {
+        self.mass2_with_status(INCOMING)
+    }
+
+    /// Momentum fractions of the incoming partons
+    ///
+    /// The momentum fractions are computed from the light-cone momenta
+    /// (E ± p_z) of the incoming partons relative to those of the
+    /// beams. The first beam with energy `EBMUP[0]` is assumed to move
+    /// in positive z direction and the second beam in negative z
+    /// direction. Accordingly, an incoming parton with non-negative
+    /// longitudinal momentum `PUP[i][2]` is assigned to the first beam
+    /// and all other incoming partons to the second one. For massless
+    /// partons, this corresponds to x = |p_z| / E_beam.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event = reader.hepeup().unwrap().unwrap();
+    /// let [x1, x2] = event.bjorken_x(reader.heprup());
+    /// println!("Momentum fractions: {x1}, {x2}");
+    /// ```
+    pub fn bjorken_x(&self, heprup: &HEPRUP) -> [f64; 2] {
+        let mut x = [0.; 2];
+        for (&status, p) in self.ISTUP.iter().zip(&self.PUP) {
+            if status != INCOMING {
+                continue;
+            }
+            if p[2] >= 0. {
+                x[0] += (p[3] + p[2]) / (2. * heprup.EBMUP[0]);
+            } else {
+                x[1] += (p[3] - p[2]) / (2. * heprup.EBMUP[1]);
+            }
+        }
+        x
+    }
+
+    /// Boost the event to the centre-of-mass frame of the incoming partons
+    ///
+    /// The four-momenta of all particles are boosted such that the sum
+    /// of the momenta of the incoming particles vanishes. The masses
+    /// `PUP[i][4]` are not changed. Since the event content changes, the
+    /// original text in `raw` is discarded.
+    ///
+    /// If there are no incoming particles, or the sum of their momenta
+    /// is not timelike, there is no centre-of-mass frame and the event
+    /// is left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let mut event = reader.hepeup().unwrap().unwrap();
+    /// event.boost_to_cm();
+    /// ```
+    pub fn boost_to_cm(&mut self) {
+        let p_in = self.momentum_with_status(INCOMING);
+        if p_in.e <= 0. || p_in.mass2() <= 0. {
+            return;
+        }
+        let beta = [-p_in.px / p_in.e, -p_in.py / p_in.e, -p_in.pz / p_in.e];
+        for p in &mut self.PUP {
+            boost(p, beta);
+        }
+        self.raw = None;
+    }
+
+    /// Copy of the event in the centre-of-mass frame of the incoming partons
+    ///
+    /// See [HEPEUP::boost_to_cm] for details.
+    pub fn boosted_to_cm(&self) -> HEPEUP {
+        let mut event = self.clone();
+        event.boost_to_cm();
+        event
+    }
+
+    /// Rotate the event about the beam axis
+    ///
+    /// The transverse momentum components `PUP[i][0]` and `PUP[i][1]`
+    /// of all particles are rotated by the given angle in radians.
+    /// Longitudinal momenta, energies, and masses are not changed. Since
+    /// the event content changes, the original text in `raw` is
+    /// discarded.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let mut event = reader.hepeup().unwrap().unwrap();
+    /// event.rotate_phi(std::f64::consts::FRAC_PI_2);
+    /// ```
+    pub fn rotate_phi(&mut self, angle: f64) {
+        let (sin, cos) = angle.sin_cos();
+        for p in &mut self.PUP {
+            let [px, py] = [p[0], p[1]];
+            p[0] = cos * px - sin * py;
+            p[1] = sin * px + cos * py;
+        }
+        self.raw = None;
+    }
+
+    /// Copy of the event rotated about the beam axis
+    ///
+    /// See [HEPEUP::rotate_phi] for details.
+    pub fn rotated_phi(&self, angle: f64) -> HEPEUP {
+        let mut event = self.clone();
+        event.rotate_phi(angle);
+        event
+    }
+
+    /// Displacement between production and decay of a particle
+    ///
+    /// Returns the spatial vector from the production point to the
+    /// decay point of the particle with the given (0-based) index in mm.
+    /// It is computed from the proper lifetime c τ given in
+    /// `VTIMUP[idx]` in mm as (p / m) c τ, where p is the
+    /// three-momentum and m the mass `PUP[idx][4]` of the particle. For
+    /// a vanishing lifetime, the displacement is zero. Otherwise, the
+    /// displacement of a massless particle is not finite.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event = reader.hepeup().unwrap().unwrap();
+    /// for idx in 0..event.PUP.len() {
+    ///     let [x, y, z] = event.vertex(idx);
+    ///     println!("Particle {idx} decays at ({x}, {y}, {z}) mm");
+    /// }
+    /// ```
+    pub fn vertex(&self, idx: usize) -> [f64; 3] {
+        let ctau = self.VTIMUP[idx];
+        if ctau == 0. {
+            return [0.; 3];
+        }
+        let p = &self.PUP[idx];
+        let factor = ctau / p[4];
+        [factor * p[0], factor * p[1], factor * p[2]]
+    }
+
+    // Squared invariant mass of all particles with the given status
+    fn mass2_with_status(&self, status: i32) -> f64 {
+        self.momentum_with_status(status).mass2()
+    }
+
+    // Total four-momentum of all particles with the given status
+    fn momentum_with_status(&self, status: i32) -> FourVector {
+        self.ISTUP
+            .iter()
+            .zip(&self.PUP)
+            .filter(|(&st, _)| st == status)
+            .map(|(_, &p)| FourVector::from(p))
+            .sum()
+    }
+
+    fn ht_of_matching(&self, mut matches: impl FnMut(i32) -> bool) -> f64 {
+        self.IDUP
+            .iter()
+            .enumerate()
+            .filter(|&(idx, &id)| self.ISTUP[idx] == OUTGOING && matches(id))
+            .map(|(idx, _)| self.pt(idx))
+            .sum()
+    }
+}
+
+// Boost a four-momentum (px, py, pz, E, m) with velocity beta
+fn boost(p: &mut [f64; 5], beta: [f64; 3]) {
+    let beta2 = beta.iter().map(|b| b * b).sum::<f64>();
+    if beta2 == 0. {
+        return;
+    }
+    let gamma = 1. / (1. - beta2).sqrt();
+    let beta_p = beta[0] * p[0] + beta[1] * p[1] + beta[2] * p[2];
+    let factor = (gamma - 1.) * beta_p / beta2 + gamma * p[3];
+    for i in 0..3 {
+        p[i] += factor * beta[i];
+    }
+    p[3] = gamma * (p[3] + beta_p);
+}
+
+#[cfg(test)]
+mod kinematics_tests {
+    use super::*;
+    use crate::status::*;
+
+    // g g -> W- j -> e- nu_e~ j
+    fn event() -> HEPEUP {
+        HEPEUP {
+            NUP: 6,
+            IDUP: vec![21, 21, -24, 11, -12, 21],
+            ISTUP: vec![
+                INCOMING,
+                INCOMING,
+                INTERMEDIATE_RESONANCE,
+                OUTGOING,
+                OUTGOING,
+                OUTGOING,
+            ],
+            MOTHUP: vec![[0, 0], [0, 0], [1, 2], [3, 3], [3, 3], [1, 2]],
+            PUP: vec![
+                [0., 0., 200., 200., 0.],
+                [0., 0., -100., 100., 0.],
+                [-6., 8., 50., 200., 193.4],
+                [30., 40., 10., 90., 71.4],
+                [-36., -32., 40., 110., 94.1],
+                [6., -8., 50., 100., 86.0],
+            ],
+            ..HEPEUP::empty()
+        }
+    }
+
+    #[test]
+    fn four_vector() {
+        let p1 = FourVector::new(1., 2., 3., 10.);
+        let p2 = FourVector::from([-1., 0., 5., 8., 0.]);
+        let sum = p1 + p2;
+        assert_eq!(sum, FourVector::new(0., 2., 8., 18.));
+        assert_eq!(sum - p2, p1);
+        assert_eq!(p1 * 2., p1 + p1);
+        assert_eq!(p1.dot(&p2), 80. + 1. - 15.);
+        assert_eq!(p1.mass2(), 100. - 14.);
+        assert_eq!(sum.mass2(), 324. - 4. - 64.);
+        assert_eq!(sum.mass(), 256f64.sqrt());
+        assert_eq!(sum.mass2(), p1.mass2() + p2.mass2() + 2. * p1.dot(&p2));
+        assert_eq!(FourVector::new(0., 0., 2., 1.).mass(), 0.);
+        let pup: [f64; 5] = FourVector::new(0., 0., 3., 5.).into();
+        assert_eq!(pup, [0., 0., 3., 5., 4.]);
+        let p: [f64; 4] = p1.into();
+        assert_eq!(FourVector::from(p), p1);
+    }
+
+    #[test]
+    fn pt() {
+        let event = event();
+        assert_eq!(event.pt(0), 0.);
+        assert_eq!(event.pt(2), 10.);
+        assert_eq!(event.pt(3), 50.);
+    }
+
+    #[test]
+    fn ht() {
+        let event = event();
+        let pt_nu = 36f64.hypot(32.);
+        assert!((event.ht() - (60. + pt_nu)).abs() < 1e-12);
+        assert_eq!(event.ht_of(&[11, 21]), 60.);
+        assert_eq!(event.ht_of(&[-11, 24, -24]), 0.);
+    }
+
+    #[test]
+    fn missing_et() {
+        let event = event();
+        let expected = 36f64.hypot(32.);
+        assert!((event.missing_et() - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn invariant_masses() {
+        let event = event();
+        assert_eq!(event.shat(), 80000.);
+        let m = event.final_state_mass();
+        assert!((m * m - event.shat()).abs() < 1e-9 * event.shat());
+        assert!((event.final_state_mass() - 80000f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bjorken_x() {
+        let heprup = HEPRUP {
+            EBMUP: [6500., 4000.],
+            ..HEPRUP::empty()
+        };
+        let mut event = event();
+        event.PUP[0] = [0., 0., -400., 400., 0.];
+        event.PUP[1] = [0., 0., 650., 650., 0.];
+        let [x1, x2] = event.bjorken_x(&heprup);
+        assert!((x1 - 0.1).abs() < 1e-12);
+        assert!((x2 - 0.1).abs() < 1e-12);
+        // massive incoming parton
+        event.PUP[1] = [0., 0., 1300., 1300. * 2f64.sqrt(), 1300.];
+        let [x1, _] = event.bjorken_x(&heprup);
+        assert!((x1 - (1. + 2f64.sqrt()) / 10.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn boost_to_cm() {
+        let mut event = event();
+        event.PUP[0] = [10., -5., 200., 200.5, 0.];
+        let shat = event.shat();
+        let masses = Vec::from_iter(
+            event.PUP.iter().map(|&p| FourVector::from(p).mass2()),
+        );
+        let boosted = event.boosted_to_cm();
+        let p_in = boosted.momentum_with_status(INCOMING);
+        for p in [p_in.px, p_in.py, p_in.pz] {
+            assert!(p.abs() < 1e-9);
+        }
+        assert!((p_in.e - shat.sqrt()).abs() < 1e-9);
+        for (&p, m2) in boosted.PUP.iter().zip(masses) {
+            let p = FourVector::from(p);
+            assert!((p.mass2() - m2).abs() < 1e-9 * p.e * p.e);
+        }
+        assert_eq!(boosted.PUP[2][4], event.PUP[2][4]);
+        event.boost_to_cm();
+        assert_eq!(event, boosted);
+
+        // no centre-of-mass frame without incoming particles
+        let mut event = self::event();
+        event.ISTUP.fill(1);
+        event.raw = Some(String::new());
+        assert_eq!(event.boosted_to_cm(), event);
+    }
+
+    #[test]
+    fn rotate_phi() {
+        let mut event = event();
+        let shat = event.shat();
+        let masses = Vec::from_iter(
+            event.PUP.iter().map(|&p| FourVector::from(p).mass2()),
+        );
+        let pts = Vec::from_iter((0..event.PUP.len()).map(|i| event.pt(i)));
+        let rotated = event.rotated_phi(0.7);
+        assert!((rotated.shat() - shat).abs() < 1e-9 * shat);
+        for (i, (&p, m2)) in rotated.PUP.iter().zip(masses).enumerate() {
+            assert_eq!(p[2..], event.PUP[i][2..]);
+            let p = FourVector::from(p);
+            assert!((p.mass2() - m2).abs() < 1e-9 * p.e * p.e);
+            assert!((rotated.pt(i) - pts[i]).abs() < 1e-9);
+        }
+        let p = rotated.PUP[2];
+        let p_orig = event.PUP[2];
+        let phi = p[1].atan2(p[0]) - p_orig[1].atan2(p_orig[0]);
+        assert!((phi.rem_euclid(std::f64::consts::TAU) - 0.7).abs() < 1e-12);
+        event.rotate_phi(0.7);
+        assert_eq!(event, rotated);
+    }
+
+    #[test]
+    fn vertex() {
+        let mut event = event();
+        event.VTIMUP = vec![0.; 6];
+        assert_eq!(event.vertex(2), [0.; 3]);
+        // B meson with c τ = 0.455 mm and β γ = 2 along the z axis
+        event.IDUP[5] = 511;
+        event.PUP[5] = [0., 0., 2. * 5.28, 5f64.sqrt() * 5.28, 5.28];
+        event.VTIMUP[5] = 0.455;
+        let [x, y, z] = event.vertex(5);
+        assert_eq!([x, y], [0., 0.]);
+        assert!((z - 0.91).abs() < 1e-12);
+        event.PUP[5] = [5.28, 0., 0., 2f64.sqrt() * 5.28, 5.28];
+        let [x, y, z] = event.vertex(5);
+        assert!((x - 0.455).abs() < 1e-12);
+        assert_eq!([y, z], [0., 0.]);
+    }
+}
@@ -40,20 +40,45 @@
 //! # }
 //! ```
 mod data;
+mod info;
+mod kinematics;
+/// Particle data
+#[cfg(feature = "pdg")]
+pub mod pdg;
 /// LHEF reader
 pub mod reader;
+/// Summary statistics
+pub mod stats;
 /// Particle status codes
 pub mod status;
+/// Strategies for property-based testing
+#[cfg(feature = "proptest")]
+pub mod strategies;
 mod syntax;
+/// Helpers for testing LHEF input and output
+#[cfg(feature = "testing")]
+pub mod testing;
+/// High-level tools for common tasks
+pub mod tools;
 /// LHEF writer
 pub mod writer;
 
+pub use crate::data::Float;
+pub use crate::data::Helicity;
+pub use crate::data::MergeError;
+pub use crate::data::ValidationError;
 pub use crate::data::XmlAttr;
+#[cfg(feature = "xml")]
 pub use crate::data::XmlTree;
 pub use crate::data::HEPEUP;
 pub use crate::data::HEPRUP;
+pub use crate::info::Scales;
+pub use crate::info::XsecInfo;
+pub use crate::kinematics::FourVector;
 pub use crate::reader::Reader;
 pub use crate::writer::Writer;
+#[cfg(feature = "zstd")]
+pub use crate::writer::ZstdFile;
 
 #[cfg(test)]
 mod tests {
@@ -103,4 +128,151 @@ mod tests {
         }
         assert_eq!(cmp_events, events)
     }
+
+    #[test]
+    fn test_read_write_raw() {
+        use std::io::Read;
+
+        let mut input = String::new();
+        {
+            let file =
+                fs::File::open("test_data/2j.lhe.gz").expect("file not found");
+            let mut reader = GzDecoder::new(io::BufReader::new(file));
+            reader.read_to_string(&mut input).unwrap();
+        }
+        let mut reader = reader::ReaderBuilder::new()
+            .preserve_raw(true)
+            .build(input.as_bytes())
+            .unwrap();
+        let mut output = Vec::new();
+        {
+            let mut writer =
+                Writer::new(io::Cursor::new(&mut output), reader.version())
+                    .unwrap();
+            writer.heprup(reader.heprup()).unwrap();
+            while let Some(event) = reader.hepeup().unwrap() {
+                assert!(event.raw.is_some());
+                writer.hepeup(&event).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        let output = String::from_utf8(output).unwrap();
+        let events_start = input.find("<event").unwrap();
+        let output_events_start = output.find("<event").unwrap();
+        assert_eq!(&output[output_events_start..], &input[events_start..]);
+    }
+
+    #[test]
+    fn test_read_write_header_comments() {
+        let header = "<header>
+<!-- run card -->
+<MGRunCard>
+<!-- comment in a block -->
+  10000 = nevents ! Number of unweighted events requested
+</MGRunCard>
+</header>
+";
+        let input = format!(
+            "<LesHouchesEvents version=\"3.0\">
+{header}<init>
+2212 2212 6500 6500 0 0 0 0 3 0
+</init>
+</LesHouchesEvents>
+"
+        );
+        let reader = Reader::new(input.as_bytes()).unwrap();
+        assert_eq!(reader.xml_header_text(), Some(header));
+        let mut writer = Writer::new(Vec::new(), reader.version()).unwrap();
+        writer
+            .xml_header_text(reader.xml_header_text().unwrap())
+            .unwrap();
+        writer.heprup(reader.heprup()).unwrap();
+        writer.finish().unwrap();
+        let output = writer.into_inner();
+        assert_eq!(String::from_utf8_lossy(&output), input);
+        let cmp_reader = Reader::new(output.as_slice()).unwrap();
+        assert_eq!(cmp_reader.xml_header_text(), Some(header));
+        assert_eq!(cmp_reader.xml_header(), reader.xml_header());
+    }
+
+    fn read_write(input: &[u8]) -> Vec<u8> {
+        let mut reader = Reader::new(input).unwrap();
+        let mut writer = Writer::new(Vec::new(), reader.version()).unwrap();
+        if !reader.header().is_empty() {
+            writer.header(reader.header()).unwrap();
+        }
+        if let Some(header) = reader.xml_header() {
+            writer.xml_header(header).unwrap();
+        }
+        writer.heprup(reader.heprup()).unwrap();
+        while let Some(event) = reader.hepeup().unwrap() {
+            writer.hepeup(&event).unwrap();
+        }
+        writer.finish().unwrap();
+        writer.into_inner()
+    }
+
+    #[test]
+    fn test_pipe() {
+        let file = fs::File::open("test_data/HEJFOG.lhe.gz").unwrap();
+        let mut input = Vec::new();
+        io::Read::read_to_end(
+            &mut GzDecoder::new(io::BufReader::new(file)),
+            &mut input,
+        )
+        .unwrap();
+
+        // same as `Reader::from_stdin` and `Writer::to_stdout`, but
+        // with a byte slice and a byte vector
+        let mut output = Vec::new();
+        let reader = Reader::new(input.as_slice()).unwrap();
+        let writer = Writer::buffered(&mut output, reader.version()).unwrap();
+        tools::transform(reader, writer, Some).unwrap();
+
+        let mut reader = Reader::new(input.as_slice()).unwrap();
+        let mut cmp_reader = Reader::new(output.as_slice()).unwrap();
+        assert_eq!(cmp_reader.heprup(), reader.heprup());
+        while let Some(event) = reader.hepeup().unwrap() {
+            assert_eq!(cmp_reader.hepeup().unwrap(), Some(event));
+        }
+        assert!(cmp_reader.hepeup().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_write_idempotent() {
+        let input = "<LesHouchesEvents version=\"3.0\">\r
+<!--\r
+  header with trailing whitespace  \r
+-->\r
+<init>\r
+ 2212 2212 6500 6500 0 0 0 0 3 1\r
+ 1 0.1 1 1\r
+\t<generator name='x'/>  \r
+\r
+</init>\r
+<event>
+ 1 1 1 1 1 1
+ 21 -1 0 0 501 502 0 0 1 1 0 0 9
+  \t
+<weights> 1 2 </weights>\t
+</event>
+<!-- comment -->
+<event >
+ 0 1 1 1 1 1
+</event>
+<event>
+ 0 1 1 1 1 1
+\r
+  </event>  \r
+</LesHouchesEvents>
+";
+        // the first cycle drops comments and normalises numbers
+        let output = read_write(input.as_bytes());
+        assert_eq!(read_write(&output), output);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("<!--\r\n  header with trailing whitespace"));
+        assert!(output.contains("\t<generator name='x'/>  \r\n\r\n</init>"));
+        assert!(output.contains("\n  \t\n<weights> 1 2 </weights>\t\n</event>"));
+        assert!(output.contains("\n\r\n</event>"));
+    }
 }
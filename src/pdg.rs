@@ -0,0 +1,196 @@
+use crate::data::HEPEUP;
+use crate::status::{INCOMING, OUTGOING};
+
+/// Electric charge of a particle in units of the elementary charge
+///
+/// Returns `None` if the charge of the particle with the given PDG id
+/// is not known. Only quarks, leptons, gauge bosons, the Higgs boson,
+/// and a few common hadrons are supported.
+///
+/// # Example
+///
+/// ```rust
+/// use lhef::pdg::charge;
+///
+/// assert_eq!(charge(11), Some(-1.));
+/// assert_eq!(charge(-11), Some(1.));
+/// assert_eq!(charge(-24), Some(-1.));
+/// ```
+pub fn charge(id: i32) -> Option<f64> {
+    charge_thirds(id).map(|c| c as f64 / 3.)
+}
+
+// Electric charge in units of a third of the elementary charge
+pub(crate) fn charge_thirds(id: i32) -> Option<i32> {
+    let charge = match id.abs() {
+        // d, s, b, b'
+        1 | 3 | 5 | 7 => -1,
+        // u, c, t, t'
+        2 | 4 | 6 | 8 => 2,
+        // e, mu, tau, tau'
+        11 | 13 | 15 | 17 => -3,
+        // neutrinos
+        12 | 14 | 16 | 18 => 0,
+        // g, gamma, Z, h
+        9 | 21 | 22 | 23 | 25 => 0,
+        // W
+        24 => 3,
+        // pi0, eta, K_L, K_S
+        111 | 221 | 130 | 310 => 0,
+        // pi+, K+
+        211 | 321 => 3,
+        // n, p
+        2112 => 0,
+        2212 => 3,
+        _ => return None,
+    };
+    Some(id.signum() * charge)
+}
+
+impl HEPEUP {
+    /// Total electric charge of all final-state particles
+    ///
+    /// The charge is given in units of the elementary charge. Particles
+    /// with unknown charge, see [charge], are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event = reader.hepeup().unwrap().unwrap();
+    /// println!("Final-state charge: {}", event.total_charge());
+    /// ```
+    pub fn total_charge(&self) -> f64 {
+        let charge: i32 = self
+            .IDUP
+            .iter()
+            .zip(&self.ISTUP)
+            .filter(|(_, &status)| status == OUTGOING)
+            .filter_map(|(&id, _)| charge_thirds(id))
+            .sum();
+        charge as f64 / 3.
+    }
+
+    /// Check that the electric charge is conserved
+    ///
+    /// Compares the total charge of the incoming particles to the total
+    /// charge of the final-state particles. Particles with unknown
+    /// charge, see [charge], are skipped. On success, the number of
+    /// skipped particles is returned. Otherwise, the error contains the
+    /// difference between outgoing and incoming charge in units of the
+    /// elementary charge.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     match event.check_charge_conservation() {
+    ///         Ok(0) => {}
+    ///         Ok(n) => println!("Skipped {n} particles with unknown charge"),
+    ///         Err(diff) => println!("Charge is violated by {diff}"),
+    ///     }
+    /// }
+    /// ```
+    pub fn check_charge_conservation(&self) -> Result<usize, f64> {
+        let mut skipped = 0;
+        let mut imbalance = 0;
+        for (&id, &status) in self.IDUP.iter().zip(&self.ISTUP) {
+            let sign = match status {
+                INCOMING => -1,
+                OUTGOING => 1,
+                _ => continue,
+            };
+            match charge_thirds(id) {
+                Some(charge) => imbalance += sign * charge,
+                None => skipped += 1,
+            }
+        }
+        if imbalance == 0 {
+            Ok(skipped)
+        } else {
+            Err(imbalance as f64 / 3.)
+        }
+    }
+}
+
+#[cfg(test)]
+mod pdg_tests {
+    use super::*;
+    use crate::status::INTERMEDIATE_RESONANCE;
+
+    #[test]
+    fn quarks() {
+        assert_eq!(charge(1), Some(-1. / 3.));
+        assert_eq!(charge(-1), Some(1. / 3.));
+        assert_eq!(charge(2), Some(2. / 3.));
+        assert_eq!(charge(-2), Some(-2. / 3.));
+        assert_eq!(charge(5), Some(-1. / 3.));
+        assert_eq!(charge(-6), Some(-2. / 3.));
+    }
+
+    #[test]
+    fn leptons() {
+        assert_eq!(charge(11), Some(-1.));
+        assert_eq!(charge(-13), Some(1.));
+        assert_eq!(charge(16), Some(0.));
+        assert_eq!(charge(-12), Some(0.));
+    }
+
+    #[test]
+    fn bosons() {
+        assert_eq!(charge(24), Some(1.));
+        assert_eq!(charge(-24), Some(-1.));
+        assert_eq!(charge(21), Some(0.));
+        assert_eq!(charge(22), Some(0.));
+        assert_eq!(charge(1_000_021), None);
+    }
+
+    #[test]
+    fn total_charge() {
+        // u d~ -> W+ -> e+ nu_e
+        let event = HEPEUP {
+            IDUP: vec![2, -1, 24, -11, 12],
+            ISTUP: vec![
+                INCOMING,
+                INCOMING,
+                INTERMEDIATE_RESONANCE,
+                OUTGOING,
+                OUTGOING,
+            ],
+            ..HEPEUP::empty()
+        };
+        assert_eq!(event.total_charge(), 1.);
+    }
+
+    #[test]
+    fn charge_conservation() {
+        // u d~ -> W+ -> e+ nu_e
+        let mut event = HEPEUP {
+            IDUP: vec![2, -1, 24, -11, 12],
+            ISTUP: vec![
+                INCOMING,
+                INCOMING,
+                INTERMEDIATE_RESONANCE,
+                OUTGOING,
+                OUTGOING,
+            ],
+            ..HEPEUP::empty()
+        };
+        assert_eq!(event.check_charge_conservation(), Ok(0));
+        event.IDUP[3] = 11;
+        assert_eq!(event.check_charge_conservation(), Err(-2.));
+        event.IDUP[0] = 1;
+        assert_eq!(event.check_charge_conservation(), Err(-1.));
+        // gluino and neutralino
+        event.IDUP = vec![2, -1, 24, 1_000_021, 1_000_022, -11, 12];
+        event.ISTUP = vec![INCOMING, INCOMING, 2, 1, 1, 1, 1];
+        assert_eq!(event.check_charge_conservation(), Ok(2));
+    }
+}
@@ -1,7 +1,16 @@
 use crate::data::*;
+use crate::info::find_weight_definitions;
+use crate::stats::EventStats;
+#[cfg(feature = "metrics")]
+use crate::stats::{Throughput, ThroughputMeter};
 use crate::syntax::*;
 
-use std::io::BufRead;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Debug};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::mem::take;
+use std::path::Path;
 use std::str;
 
 use thiserror::Error;
@@ -9,11 +18,324 @@ use thiserror::Error;
 /// Reader for the LHEF format
 #[derive(Debug, PartialEq)]
 pub struct Reader<T> {
-    stream: T,
-    version: &'static str,
+    stream: CountingReader<T>,
+    version: String,
     header: String,
-    xml_header: Option<XmlTree>,
+    xml_header: Option<XmlHeader>,
+    #[cfg(feature = "xml")]
+    xml_header_text: Option<String>,
     heprup: HEPRUP,
+    init_attr_raw: String,
+    unknown_tags: Vec<String>,
+    options: ReaderBuilder,
+    events_start: u64,
+    // Opening line of an XML header that has not been read yet
+    deferred_header: Option<String>,
+    progress: Option<ProgressCallback>,
+    #[cfg(feature = "metrics")]
+    meter: ThroughputMeter,
+}
+
+// Callback reporting the number of bytes read
+struct ProgressCallback(Box<dyn FnMut(u64) + Send + Sync>);
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+// Callbacks cannot be compared, so they are ignored when comparing readers
+impl PartialEq for ProgressCallback {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// Builder for a LHEF reader with non-default options
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let file = std::fs::File::open("events.lhe").unwrap();
+/// let file = std::io::BufReader::new(file);
+/// let reader = lhef::reader::ReaderBuilder::new()
+///     .strict(true)
+///     .build(file)
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReaderBuilder {
+    strict: bool,
+    max_particles: usize,
+    max_subprocesses: usize,
+    preserve_raw: bool,
+    allow_unknown_versions: bool,
+    case_insensitive_tags: bool,
+    max_header_bytes: usize,
+    max_line_bytes: usize,
+    defer_xml_header: bool,
+    skip_info: bool,
+    on_unknown_tag: UnknownTagPolicy,
+    reject_nonfinite: bool,
+}
+
+/// Treatment of unknown tags between events
+///
+/// See [ReaderBuilder::on_unknown_tag].
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum UnknownTagPolicy {
+    /// Ignore unknown tags and their content
+    Skip,
+    /// Keep unknown tags and their content, see [Reader::unknown_tags]
+    Collect,
+    /// Return an error when reading the next event
+    #[default]
+    Error,
+}
+
+impl Default for ReaderBuilder {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            max_particles: 1_000_000,
+            max_subprocesses: 1_000_000,
+            preserve_raw: false,
+            allow_unknown_versions: false,
+            case_insensitive_tags: false,
+            max_header_bytes: 8 * 1024 * 1024,
+            max_line_bytes: 1024 * 1024,
+            defer_xml_header: false,
+            skip_info: false,
+            on_unknown_tag: UnknownTagPolicy::Error,
+            reject_nonfinite: false,
+        }
+    }
+}
+
+impl ReaderBuilder {
+    /// Create a builder with default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable strict mode
+    ///
+    /// In strict mode, additional consistency checks are performed for
+    /// each event. For example, the indices of decay mothers are
+    /// validated with [HEPEUP::validate_mothers], which requires
+    /// mothers to precede their daughters. Furthermore, a tag
+    /// with duplicate attribute names results in an error instead of
+    /// keeping only the last value, and blank lines and comment lines
+    /// starting with `#` between events are rejected instead of
+    /// skipped. The default is `false`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the maximum number of particles in an event
+    ///
+    /// Reading an event with a larger number of particles `NUP`
+    /// results in an error. The default is 1000000.
+    pub fn max_particles(mut self, max: usize) -> Self {
+        self.max_particles = max;
+        self
+    }
+
+    /// Set the maximum number of subprocesses
+    ///
+    /// Reading run information with a larger number of subprocesses
+    /// `NPRUP` results in an error. The default is 1000000.
+    pub fn max_subprocesses(mut self, max: usize) -> Self {
+        self.max_subprocesses = max;
+        self
+    }
+
+    /// Keep the original text of each event
+    ///
+    /// If enabled, the original text of each event block is stored in
+    /// the [HEPEUP::raw] field. The default is `false`.
+    pub fn preserve_raw(mut self, preserve: bool) -> Self {
+        self.preserve_raw = preserve;
+        self
+    }
+
+    /// Accept files with unknown LHEF versions
+    ///
+    /// By default, reading a file with a version other than "1.0",
+    /// "2.0", or "3.0" results in an error. If enabled, any version
+    /// string is accepted and the file is parsed like for version 1.0.
+    pub fn allow_unknown_versions(mut self, allow: bool) -> Self {
+        self.allow_unknown_versions = allow;
+        self
+    }
+
+    /// Ignore case when recognising tags
+    ///
+    /// If enabled, tags like `<Event>` or `<INIT>` are accepted in place
+    /// of `<event>` and `<init>`. The text of the events and the run
+    /// information is not changed. The default is `false`.
+    pub fn case_insensitive_tags(mut self, ignore_case: bool) -> Self {
+        self.case_insensitive_tags = ignore_case;
+        self
+    }
+
+    /// Set the maximum size of a header in bytes
+    ///
+    /// Reading a comment header or XML header that is larger results in
+    /// an error. This prevents reading the whole input into memory if a
+    /// header is not terminated. The default is 8 MiB.
+    pub fn max_header_bytes(mut self, max: usize) -> Self {
+        self.max_header_bytes = max;
+        self
+    }
+
+    /// Set the maximum length of a single line in bytes
+    ///
+    /// Reading a longer line, including the line break, results in an
+    /// error. This protects against untrusted input that does not
+    /// contain line breaks. The default is 1 MiB.
+    pub fn max_line_bytes(mut self, max: usize) -> Self {
+        self.max_line_bytes = max;
+        self
+    }
+
+    /// Do not read the XML header when creating the reader
+    ///
+    /// If enabled, the reader stops in front of the XML header instead
+    /// of parsing it. The header text can then be copied with
+    /// [Reader::take_header_to] without keeping it in memory, which is
+    /// useful for very large headers. Until then, the run information
+    /// is empty. Reading an event first discards the header. The
+    /// default is `false`.
+    pub fn defer_xml_header(mut self, defer: bool) -> Self {
+        self.defer_xml_header = defer;
+        self
+    }
+
+    /// Do not store optional event information
+    ///
+    /// If enabled, the [HEPEUP::info] field of each event is left
+    /// empty. The optional information is still read to find the end of
+    /// the event, but is discarded immediately. This speeds up reading
+    /// when blocks like `<mgrwt>` or `<rwgt>` are not needed. The [raw
+    /// text](Self::preserve_raw) of an event is not affected. The
+    /// default is `false`.
+    pub fn skip_info(mut self, skip: bool) -> Self {
+        self.skip_info = skip;
+        self
+    }
+
+    /// Choose how to treat unknown tags between events
+    ///
+    /// An unknown tag is any tag other than `<event>` and the closing
+    /// `</LesHouchesEvents>` tag. Unless it is self-closing, it extends
+    /// until the matching closing tag, which has to be either on the
+    /// same line as the opening tag or on a separate line. By default,
+    /// reading an event after an unknown tag results in an error.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use lhef::reader::{ReaderBuilder, UnknownTagPolicy};
+    ///
+    /// let text = "<LesHouchesEvents version=\"3.0\">
+    /// <init>
+    /// 2212 2212 6500 6500 0 0 0 0 3 0
+    /// </init>
+    /// <foo/>
+    /// </LesHouchesEvents>
+    /// ";
+    /// let mut reader = ReaderBuilder::new()
+    ///     .on_unknown_tag(UnknownTagPolicy::Collect)
+    ///     .build(text.as_bytes())
+    ///     .unwrap();
+    /// assert!(reader.hepeup().unwrap().is_none());
+    /// assert_eq!(reader.unknown_tags(), ["<foo/>\n"]);
+    /// ```
+    pub fn on_unknown_tag(mut self, policy: UnknownTagPolicy) -> Self {
+        self.on_unknown_tag = policy;
+        self
+    }
+
+    /// Reject floating-point numbers that are not finite
+    ///
+    /// If enabled, reading run information or an event with an entry
+    /// like `inf` or `nan` results in an error. By default, such
+    /// entries are accepted, so that they are preserved when writing the
+    /// events again, see
+    /// [Writer::set_reject_nonfinite](crate::Writer::set_reject_nonfinite).
+    /// Optional information in `info` is not checked.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use lhef::reader::{ReadError, ReaderBuilder};
+    ///
+    /// let text = "<LesHouchesEvents version=\"1.0\">
+    /// <init>
+    /// 2212 2212 inf 6500 0 0 0 0 3 0
+    /// </init>
+    /// </LesHouchesEvents>
+    /// ";
+    /// let reader = ReaderBuilder::new()
+    ///     .reject_nonfinite(true)
+    ///     .build(text.as_bytes());
+    /// assert!(matches!(reader, Err(ReadError::NonFinite(_))));
+    /// ```
+    pub fn reject_nonfinite(mut self, reject: bool) -> Self {
+        self.reject_nonfinite = reject;
+        self
+    }
+
+    /// Create a new LHEF reader with the chosen options
+    pub fn build<T: BufRead>(&self, stream: T) -> Result<Reader<T>, ReadError> {
+        let mut stream = CountingReader::new(stream);
+        let version = parse_version(&mut stream, self)?;
+        let (headers, next) = parse_header(&mut stream, self)?;
+        let mut deferred_header = None;
+        let mut init_attr_raw = String::new();
+        let heprup = if self.defer_xml_header
+            && starts_with_tag(&next, HEADER_START, self.case_insensitive_tags)
+        {
+            deferred_header = Some(next);
+            HEPRUP::empty()
+        } else {
+            init_attr_raw = raw_attr(&next, INIT_START).to_owned();
+            parse_init(&next, &mut stream, self)?
+        };
+        let events_start = stream.count;
+        Ok(Reader {
+            stream,
+            version,
+            header: headers.header,
+            xml_header: headers.xml_header,
+            #[cfg(feature = "xml")]
+            xml_header_text: headers.xml_header_text,
+            heprup,
+            init_attr_raw,
+            unknown_tags: Vec::new(),
+            options: self.clone(),
+            events_start,
+            deferred_header,
+            progress: None,
+            #[cfg(feature = "metrics")]
+            meter: ThroughputMeter::new(),
+        })
+    }
+
+    /// Build a reader for several concatenated documents
+    ///
+    /// The options apply to each document.
+    pub fn build_multi<T: BufRead>(&self, stream: T) -> MultiReader<T> {
+        MultiReader {
+            stream: Some(stream),
+            options: self.clone(),
+            current: None,
+            document_done: false,
+        }
+    }
 }
 
 /// Data components forming a reader
@@ -22,11 +344,11 @@ pub struct ReaderData<T> {
     /// Stream from which we read
     pub stream: T,
     /// LHEF version
-    pub version: &'static str,
+    pub version: String,
     /// LHEF header
     pub header: String,
     /// LHEF XML header
-    pub xml_header: Option<XmlTree>,
+    pub xml_header: Option<XmlHeader>,
     /// Generator run information
     pub heprup: HEPRUP,
 }
@@ -41,22 +363,13 @@ impl<T: BufRead> Reader<T> {
     /// let file = std::io::BufReader::new(file);
     /// let reader = lhef::Reader::new(file).unwrap();
     /// ```
-    pub fn new(mut stream: T) -> Result<Reader<T>, ReadError> {
-        let version = parse_version(&mut stream)?;
-        let (header, xml_header, init_start) = parse_header(&mut stream)?;
-        let heprup = parse_init(&init_start, &mut stream)?;
-        Ok(Reader {
-            stream,
-            version,
-            header,
-            xml_header,
-            heprup,
-        })
+    pub fn new(stream: T) -> Result<Reader<T>, ReadError> {
+        ReaderBuilder::new().build(stream)
     }
 
     /// Get the LHEF version
     pub fn version(&self) -> &str {
-        self.version
+        &self.version
     }
 
     /// Get the LHEF header
@@ -64,18 +377,294 @@ impl<T: BufRead> Reader<T> {
         &self.header
     }
 
+    /// Get the lines of the LHEF header
+    ///
+    /// The lines containing the comment markers `<!--` and `-->` are
+    /// not included.
+    pub fn header_lines(&self) -> Vec<&str> {
+        let mut lines = Vec::from_iter(self.header.lines());
+        if lines.last().is_some_and(|l| l.trim() == COMMENT_END) {
+            lines.pop();
+        }
+        if lines.first().is_some_and(|l| l.trim() == COMMENT_START) {
+            lines.remove(0);
+        }
+        lines
+    }
+
+    /// Extract key-value pairs from the LHEF header
+    ///
+    /// Each header line of the form `key: value` or `key = value` is
+    /// interpreted as a key-value pair, splitting at the first `:` or
+    /// `=`. Surrounding whitespace and leading `#` characters are
+    /// removed from the key and surrounding whitespace is removed from
+    /// the value. Lines without a separator or with an empty key are
+    /// ignored. If a key appears more than once, the last value is
+    /// used.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// if let Some(n) = reader.header_kv().get("Number of Events") {
+    ///     println!("The file contains {n} events");
+    /// }
+    /// ```
+    pub fn header_kv(&self) -> HashMap<&str, &str> {
+        self.header_lines()
+            .into_iter()
+            .filter_map(|line| {
+                let (key, value) = line.split_once([':', '='])?;
+                let key = key.trim().trim_start_matches('#').trim_start();
+                (!key.is_empty()).then_some((key, value.trim()))
+            })
+            .collect()
+    }
+
     /// Get the LHEF xml header
-    pub fn xml_header(&self) -> &Option<XmlTree> {
+    ///
+    /// If the `xml` feature is disabled, the header is not parsed and
+    /// its original text is returned instead.
+    pub fn xml_header(&self) -> &Option<XmlHeader> {
         &self.xml_header
     }
 
+    /// Get the original text of the LHEF xml header
+    ///
+    /// The text includes the enclosing `<header>` tags. Unlike the
+    /// parsed header, it also contains all comments and formatting. It
+    /// can be written with [Writer::xml_header_text](crate::Writer::xml_header_text).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let mut output = vec![];
+    /// let mut writer = lhef::Writer::new(&mut output, "3.0").unwrap();
+    /// if let Some(header) = reader.xml_header_text() {
+    ///     writer.xml_header_text(header).unwrap();
+    /// }
+    /// ```
+    pub fn xml_header_text(&self) -> Option<&str> {
+        #[cfg(feature = "xml")]
+        {
+            self.xml_header_text.as_deref()
+        }
+        #[cfg(not(feature = "xml"))]
+        {
+            self.xml_header.as_deref()
+        }
+    }
+
+    /// Copy a deferred XML header to `out`
+    ///
+    /// If the reader was created with
+    /// [ReaderBuilder::defer_xml_header], the text of the XML header is
+    /// copied line by line to `out` without keeping it in memory. The
+    /// limit on the header size does not apply. Afterwards, the
+    /// remaining headers and the run information are read. If there is
+    /// no deferred XML header, nothing is written.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::reader::ReaderBuilder::new()
+    ///     .defer_xml_header(true)
+    ///     .build(file)
+    ///     .unwrap();
+    /// let header = std::fs::File::create("header.xml").unwrap();
+    /// reader.take_header_to(std::io::BufWriter::new(header)).unwrap();
+    /// println!("Run information: {:?}", reader.heprup());
+    /// ```
+    pub fn take_header_to<W: Write>(
+        &mut self,
+        mut out: W,
+    ) -> Result<(), ReadError> {
+        use ReadError::HeaderWriteErr;
+        let max_line = self.options.max_line_bytes;
+        let ignore_case = self.options.case_insensitive_tags;
+        while let Some(mut line) = self.deferred_header.take() {
+            loop {
+                out.write_all(line.as_bytes()).map_err(HeaderWriteErr)?;
+                if is_tag(&line, HEADER_END, ignore_case) {
+                    break;
+                }
+                line.clear();
+                if read_line(&mut self.stream, &mut line, max_line)? == 0 {
+                    return Err(ReadError::EndOfFile("header"));
+                }
+            }
+            let (headers, next) =
+                parse_header(&mut self.stream, &self.options)?;
+            if !headers.header.is_empty() {
+                self.header = headers.header;
+            }
+            if headers.xml_header.is_some() {
+                self.xml_header = headers.xml_header;
+                #[cfg(feature = "xml")]
+                {
+                    self.xml_header_text = headers.xml_header_text;
+                }
+            }
+            if starts_with_tag(&next, HEADER_START, ignore_case) {
+                self.deferred_header = Some(next);
+            } else {
+                self.init_attr_raw = raw_attr(&next, INIT_START).to_owned();
+                self.heprup =
+                    parse_init(&next, &mut self.stream, &self.options)?;
+                self.events_start = self.stream.count;
+            }
+        }
+        out.flush().map_err(HeaderWriteErr)?;
+        Ok(())
+    }
+
     /// Get the run information in HEPRUP format
     pub fn heprup(&self) -> &HEPRUP {
         &self.heprup
     }
 
+    /// Get the original attribute text of the `<init>` tag
+    ///
+    /// This is the text between `<init` and the closing `>`, including
+    /// any whitespace and line breaks. The parsed attributes are
+    /// available in [HEPRUP::attr]. For example, for the tag
+    /// `<init a="1"  b='2'>`, the attribute text is ` a="1"  b='2'`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// println!("<init{}>", reader.init_attr_raw());
+    /// ```
+    pub fn init_attr_raw(&self) -> &str {
+        &self.init_attr_raw
+    }
+
+    /// Get the unknown tags encountered so far
+    ///
+    /// Each entry contains the full text of a tag between events,
+    /// including its content and closing tag. Tags are only collected
+    /// with [UnknownTagPolicy::Collect], see
+    /// [ReaderBuilder::on_unknown_tag].
+    pub fn unknown_tags(&self) -> &[String] {
+        &self.unknown_tags
+    }
+
+    /// Get mutable access to the run information in HEPRUP format
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// for xs in &mut reader.heprup_mut().XSECUP {
+    ///     *xs *= 2.;
+    /// }
+    /// ```
+    pub fn heprup_mut(&mut self) -> &mut HEPRUP {
+        &mut self.heprup
+    }
+
+    /// Get the number of bytes read so far
+    ///
+    /// When constructing a reader from [ReaderData], the count starts
+    /// at zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let event_start = reader.position();
+    /// let event = reader.hepeup().unwrap();
+    /// let event_end = reader.position();
+    /// ```
+    pub fn position(&self) -> u64 {
+        self.stream.count
+    }
+
+    /// Set a callback for reporting progress
+    ///
+    /// After each event, the callback is called with the number of
+    /// bytes read so far, see [Reader::position].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let size = file.metadata().unwrap().len();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// reader.set_progress(move |pos| {
+    ///     println!("{:.1}% done", 100. * pos as f64 / size as f64)
+    /// });
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn set_progress<F>(&mut self, callback: F)
+    where
+        F: FnMut(u64) + Send + Sync + 'static,
+    {
+        self.progress = Some(ProgressCallback(Box::new(callback)));
+    }
+
+    /// Remove the callback set with [Reader::set_progress]
+    pub fn clear_progress(&mut self) {
+        self.progress = None;
+    }
+
+    /// Get the number of events and bytes read since the reader was created
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     // ...
+    /// }
+    /// let throughput = reader.throughput();
+    /// println!("Read {} bytes per second", throughput.bytes_per_sec());
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn throughput(&self) -> Throughput {
+        self.meter.throughput(self.stream.count)
+    }
+
+    // Update metrics and report progress after reading an event
+    fn finish_event(&mut self) {
+        #[cfg(feature = "metrics")]
+        self.meter.add_event();
+        if let Some(ProgressCallback(callback)) = &mut self.progress {
+            callback(self.stream.count)
+        }
+    }
+
     /// Get the next event in HEPEUP format
     ///
+    /// Comment blocks between events and before the final
+    /// `</LesHouchesEvents>` line are skipped.
+    ///
     /// # Example
     ///
     /// ```rust,no_run
@@ -90,104 +679,1251 @@ impl<T: BufRead> Reader<T> {
     /// }
     /// ```
     pub fn hepeup(&mut self) -> Result<Option<HEPEUP>, ReadError> {
+        self.hepeup_as()
+    }
+
+    /// Get the next event with the given floating-point type
+    ///
+    /// This is like [Reader::hepeup], but the momenta, weights, and
+    /// other floating-point numbers are stored as `F`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// while let Some(event) = reader.hepeup_as::<f32>().unwrap() {
+    ///     println!("Event weight: {}", event.XWGTUP);
+    /// }
+    /// ```
+    pub fn hepeup_as<F: Float>(
+        &mut self,
+    ) -> Result<Option<HEPEUP<F>>, ReadError> {
         let mut line = String::new();
-        self.stream.read_line(&mut line)?;
-        if line.starts_with(EVENT_START) {
-            Ok(Some(parse_event(&line, &mut self.stream)?))
-        } else if line.trim() == LHEF_LAST_LINE {
+        self.read_line(&mut line)?;
+        self.hepeup_starting_with(line)
+    }
+
+    fn hepeup_starting_with<F: Float>(
+        &mut self,
+        mut line: String,
+    ) -> Result<Option<HEPEUP<F>>, ReadError> {
+        let ignore_case = self.options.case_insensitive_tags;
+        self.skip_comments(&mut line)?;
+        if line.is_empty() {
+            Err(ReadError::EndOfFile("LesHouchesEvents"))
+        } else if starts_with_tag(&line, EVENT_START, ignore_case) {
+            let options = &self.options;
+            let max_line = options.max_line_bytes;
+            read_open_tag(&mut self.stream, &mut line, "event", max_line)?;
+            let event = if options.preserve_raw {
+                let mut raw = line;
+                let event_open_len = raw.len();
+                read_lines_until(
+                    &mut self.stream,
+                    &mut raw,
+                    EVENT_END,
+                    "event",
+                    usize::MAX,
+                    options,
+                )?;
+                let (event_open, rest) = raw.split_at(event_open_len);
+                let mut event =
+                    parse_event(event_open, &mut rest.as_bytes(), options)?;
+                event.raw = Some(raw);
+                event
+            } else {
+                parse_event(&line, &mut self.stream, options)?
+            };
+            if self.options.strict {
+                event.validate_mothers()?;
+            }
+            self.finish_event();
+            Ok(Some(event))
+        } else if is_tag(&line, LHEF_LAST_LINE, ignore_case) {
             Ok(None)
         } else {
             Err(ReadError::BadEventStart(line))
         }
     }
 
-    /// Extract all components
-    #[deprecated(note = "Use `ReaderData::from(self)` instead")]
-    pub fn into_parts(self) -> ReaderData<T> {
-        self.into()
-    }
-
-    /// Build from raw components
-    #[deprecated(note = "Use `Reader::from(data)` instead")]
-    pub fn from_parts(data: ReaderData<T>) -> Self {
-        Self::from(data)
+    // Skip comment blocks and unknown tags, starting with `line`, and
+    // store the first other line in `line`. Outside strict mode, blank
+    // lines and comment lines starting with `#` are skipped as well.
+    fn skip_comments(&mut self, line: &mut String) -> Result<(), ReadError> {
+        loop {
+            let is_stray = !self.options.strict
+                && !line.is_empty()
+                && (line.trim().is_empty()
+                    || line.trim_start().starts_with('#'));
+            if line.trim_start().starts_with(COMMENT_START) {
+                if !line.trim_end().ends_with(COMMENT_END) {
+                    read_lines_until(
+                        &mut self.stream,
+                        line,
+                        COMMENT_END,
+                        "comment",
+                        self.options.max_header_bytes,
+                        &self.options,
+                    )?;
+                }
+            } else if !is_stray && !self.read_unknown_tag(line)? {
+                return Ok(());
+            }
+            line.clear();
+            self.read_line(line)?;
+        }
     }
-}
 
-impl<T> From<Reader<T>> for ReaderData<T> {
-    fn from(source: Reader<T>) -> Self {
-        Self {
-            stream: source.stream,
-            version: source.version,
-            header: source.header,
-            xml_header: source.xml_header,
-            heprup: source.heprup,
+    // If `line` starts an unknown tag that should not result in an
+    // error, read the rest of it and return `true`
+    fn read_unknown_tag(
+        &mut self,
+        line: &mut String,
+    ) -> Result<bool, ReadError> {
+        let options = &self.options;
+        if options.on_unknown_tag == UnknownTagPolicy::Error {
+            return Ok(false);
+        }
+        let Some(tag) = line.trim_start().strip_prefix('<') else {
+            return Ok(false);
+        };
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(tag.len());
+        let name = &tag[..name_end];
+        let ignore_case = options.case_insensitive_tags;
+        if name.is_empty() || starts_with_tag(line, EVENT_START, ignore_case) {
+            return Ok(false);
         }
+        let end = format!("</{name}>");
+        let max_line = options.max_line_bytes;
+        read_open_tag(&mut self.stream, line, "unknown tag", max_line)?;
+        let open_end = line.find('>').unwrap();
+        let complete = line[..open_end].ends_with('/')
+            || if ignore_case {
+                line.to_ascii_lowercase()
+                    .contains(&end.to_ascii_lowercase())
+            } else {
+                line.contains(&end)
+            };
+        if !complete {
+            read_lines_until(
+                &mut self.stream,
+                line,
+                &end,
+                "unknown tag",
+                options.max_header_bytes,
+                options,
+            )?;
+        }
+        if options.on_unknown_tag == UnknownTagPolicy::Collect {
+            self.unknown_tags.push(take(line));
+        }
+        Ok(true)
     }
-}
 
-impl<T> From<ReaderData<T>> for Reader<T> {
-    fn from(source: ReaderData<T>) -> Self {
-        Self {
-            stream: source.stream,
-            version: source.version,
-            header: source.header,
-            xml_header: source.xml_header,
-            heprup: source.heprup,
+    // Read a line, respecting the maximum line length
+    fn read_line(&mut self, line: &mut String) -> Result<usize, ReadError> {
+        if self.deferred_header.is_some() {
+            self.take_header_to(std::io::sink())?;
         }
+        read_line(&mut self.stream, line, self.options.max_line_bytes)
     }
-}
 
-fn parse_version<T: BufRead>(
+    /// Get the text of the next event without parsing it
+    ///
+    /// The text includes the opening `<event>` tag and the closing
+    /// `</event>` tag.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// while let Some(event) = reader.next_raw_event().unwrap() {
+    ///     print!("{event}");
+    /// }
+    /// ```
+    pub fn next_raw_event(&mut self) -> Result<Option<String>, ReadError> {
+        let ignore_case = self.options.case_insensitive_tags;
+        let mut text = String::new();
+        self.read_line(&mut text)?;
+        self.skip_comments(&mut text)?;
+        if text.is_empty() {
+            Err(ReadError::EndOfFile("LesHouchesEvents"))
+        } else if starts_with_tag(&text, EVENT_START, ignore_case) {
+            read_lines_until(
+                &mut self.stream,
+                &mut text,
+                EVENT_END,
+                "event",
+                usize::MAX,
+                &self.options,
+            )?;
+            self.finish_event();
+            Ok(Some(text))
+        } else if is_tag(&text, LHEF_LAST_LINE, ignore_case) {
+            Ok(None)
+        } else {
+            Err(ReadError::BadEventStart(text))
+        }
+    }
+
+    /// Compute summary statistics for the event weights
+    ///
+    /// All remaining events are read. For efficiency, only the weights
+    /// are parsed.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let stats = reader.statistics().unwrap();
+    /// println!("Sum of weights: {}", stats.sum_weights);
+    /// ```
+    pub fn statistics(&mut self) -> Result<EventStats, ReadError> {
+        let mut stats = EventStats::new();
+        while let Some((_, weight)) = self.next_event_weight()? {
+            stats.add(weight);
+        }
+        Ok(stats)
+    }
+
+    /// Find the largest absolute event weight
+    ///
+    /// All remaining events are read. For efficiency, only the weights
+    /// are parsed. If there are no events, the result is zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let max_weight = reader.max_weight().unwrap();
+    /// println!("Largest weight: {max_weight}");
+    /// ```
+    pub fn max_weight(&mut self) -> Result<f64, ReadError> {
+        let mut max = 0.;
+        while let Some((_, weight)) = self.next_event_weight()? {
+            max = f64::max(max, weight.abs());
+        }
+        Ok(max)
+    }
+
+    /// Find the largest absolute event weight for each process id
+    ///
+    /// All remaining events are read. The result maps each process id
+    /// `IDRUP` to the largest absolute weight of the events for this
+    /// process.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// for (idrup, max_weight) in reader.max_weight_per_process().unwrap() {
+    ///     println!("Largest weight for process {idrup}: {max_weight}");
+    /// }
+    /// ```
+    pub fn max_weight_per_process(
+        &mut self,
+    ) -> Result<BTreeMap<i32, f64>, ReadError> {
+        let mut max = BTreeMap::new();
+        while let Some((idrup, weight)) = self.next_event_weight()? {
+            let entry = max.entry(idrup).or_insert(0.);
+            *entry = f64::max(*entry, weight.abs());
+        }
+        Ok(max)
+    }
+
+    // Skip to after the next event, only parsing its process id and weight
+    fn next_event_weight(&mut self) -> Result<Option<(i32, f64)>, ReadError> {
+        let ignore_case = self.options.case_insensitive_tags;
+        let mut line = String::new();
+        self.read_line(&mut line)?;
+        self.skip_comments(&mut line)?;
+        if line.is_empty() {
+            return Err(ReadError::EndOfFile("LesHouchesEvents"));
+        } else if is_tag(&line, LHEF_LAST_LINE, ignore_case) {
+            return Ok(None);
+        } else if !starts_with_tag(&line, EVENT_START, ignore_case) {
+            return Err(ReadError::BadEventStart(line));
+        }
+        let max_line = self.options.max_line_bytes;
+        read_open_tag(&mut self.stream, &mut line, "event", max_line)?;
+        line.clear();
+        self.read_line(&mut line)?;
+        let mut entries = line.split_whitespace();
+        let _: i32 = parse(|| "NUP", entries.next())?;
+        let idrup = parse(|| "IDRUP", entries.next())?;
+        let xwgtup = parse_float(|| "XWGTUP", entries.next())?;
+        loop {
+            line.clear();
+            if self.read_line(&mut line)? == 0 {
+                return Err(ReadError::EndOfFile("event"));
+            }
+            if is_tag(&line, EVENT_END, ignore_case) {
+                self.finish_event();
+                return Ok(Some((idrup, xwgtup)));
+            }
+        }
+    }
+
+    /// Iterate over all events satisfying a predicate
+    ///
+    /// The iterator stops after the first error.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// for event in reader.filter_events(|event| event.IDRUP == 1) {
+    ///     let event = event.unwrap();
+    ///     assert_eq!(event.IDRUP, 1);
+    /// }
+    /// ```
+    pub fn filter_events<F>(self, pred: F) -> FilteredEvents<T, F>
+    where
+        F: FnMut(&HEPEUP) -> bool,
+    {
+        FilteredEvents {
+            reader: self,
+            pred,
+            done: false,
+        }
+    }
+
+    /// Iterate over all events, skipping malformed ones
+    ///
+    /// After an error, the iterator tries to continue with the next
+    /// line starting an event. The iterator ends after the last line
+    /// of the input, the end of the input, or an I/O error. If the
+    /// input ends before the last line, the final item is an error and
+    /// [LossyEvents::truncated] returns `true`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// for event in reader.events_lossy() {
+    ///     match event {
+    ///         Ok(event) => println!("Found an event: {event:?}"),
+    ///         Err(err) => println!("Skipping malformed event: {err}"),
+    ///     }
+    /// }
+    /// ```
+    pub fn events_lossy(&mut self) -> LossyEvents<'_, T> {
+        LossyEvents {
+            reader: self,
+            next_line: None,
+            done: false,
+            truncated: false,
+        }
+    }
+
+    /// Iterate over all events together with their named weights
+    ///
+    /// The named weights of each event, see [HEPEUP::named_weights],
+    /// are joined to the weight definitions in the run information, see
+    /// [HEPRUP::weight_definitions]. If the run information does not
+    /// define any weights, the definitions are taken from the XML
+    /// header instead. Each weight is stored under the name of its
+    /// definition. Weights without a definition are omitted, as are
+    /// definitions without a corresponding weight in the event. The
+    /// iterator stops after the first error.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// for event in reader.weighted_events() {
+    ///     let (event, weights) = event.unwrap();
+    ///     for (name, weight) in weights {
+    ///         println!("Weight {name}: {weight} (central: {})", event.XWGTUP);
+    ///     }
+    /// }
+    /// ```
+    pub fn weighted_events(
+        &mut self,
+    ) -> impl Iterator<Item = Result<(HEPEUP, HashMap<String, f64>), ReadError>> + '_
+    {
+        let mut definitions = self.heprup.weight_definitions();
+        if definitions.is_empty() {
+            if let Some(header) = self.xml_header_text() {
+                definitions = find_weight_definitions(header);
+            }
+        }
+        let names: HashMap<_, _> = definitions.into_iter().collect();
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let event = match self.hepeup() {
+                Ok(Some(event)) => event,
+                Ok(None) => {
+                    done = true;
+                    return None;
+                }
+                Err(err) => {
+                    done = true;
+                    return Some(Err(err));
+                }
+            };
+            let weights = event
+                .named_weights()
+                .into_iter()
+                .filter_map(|(id, wgt)| Some((names.get(&id)?.clone(), wgt)))
+                .collect();
+            Some(Ok((event, weights)))
+        })
+    }
+
+    /// Iterate over the events with indices from `start` to `end`
+    ///
+    /// Indices start from zero at the current position of the reader.
+    /// The first `start` events are skipped without parsing them. After
+    /// that, up to `end - start` events are read. The iterator stops
+    /// after the first error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is larger than `end`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// for event in reader.events_range(1000, 2000) {
+    ///     let event = event.unwrap();
+    ///     // ...
+    /// }
+    /// ```
+    pub fn events_range(
+        &mut self,
+        start: usize,
+        end: usize,
+    ) -> impl Iterator<Item = Result<HEPEUP, ReadError>> + '_ {
+        assert!(start <= end, "range start {start} is after end {end}");
+        let mut to_skip = start;
+        let mut remaining = end - start;
+        std::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+            while to_skip > 0 {
+                to_skip -= 1;
+                match self.next_raw_event() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        remaining = 0;
+                        return None;
+                    }
+                    Err(err) => {
+                        remaining = 0;
+                        return Some(Err(err));
+                    }
+                }
+            }
+            let next = self.hepeup().transpose();
+            remaining = match next {
+                Some(Ok(_)) => remaining - 1,
+                _ => 0,
+            };
+            next
+        })
+    }
+
+    /// Check that the number of events agrees with the declared number
+    ///
+    /// All remaining events are counted without parsing them and
+    /// compared to the number `neve` declared in the `<xsecinfo>` tag
+    /// of the run information, see [HEPRUP::xsecinfo]. If they differ,
+    /// an [EventCountMismatch](ReadError::EventCountMismatch) error with
+    /// the declared and the actual number is returned. If no number of
+    /// events is declared, the events are still read, but no comparison
+    /// is made.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// if let Err(err) = reader.verify_event_count() {
+    ///     println!("Incomplete file: {err}");
+    /// }
+    /// ```
+    pub fn verify_event_count(&mut self) -> Result<(), ReadError> {
+        let mut nevents = 0;
+        while self.next_raw_event()?.is_some() {
+            nevents += 1;
+        }
+        match self.heprup.xsecinfo() {
+            Some(xsecinfo) if xsecinfo.neve != nevents => {
+                Err(ReadError::EventCountMismatch(xsecinfo.neve, nevents))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Read the run information and all remaining events
+    ///
+    /// The events are collected into a vector that grows as needed. For
+    /// seekable streams, [Reader::read_all_reserved] avoids reallocating
+    /// the vector.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let (heprup, events) = reader.read_all().unwrap();
+    /// println!("Read {} events", events.len());
+    /// ```
+    pub fn read_all(self) -> Result<(HEPRUP, Vec<HEPEUP>), ReadError> {
+        self.read_all_into(Vec::new())
+    }
+
+    fn read_all_into(
+        mut self,
+        mut events: Vec<HEPEUP>,
+    ) -> Result<(HEPRUP, Vec<HEPEUP>), ReadError> {
+        while let Some(event) = self.hepeup()? {
+            events.push(event);
+        }
+        Ok((self.heprup, events))
+    }
+
+    /// Extract all components
+    #[deprecated(note = "Use `ReaderData::from(self)` instead")]
+    pub fn into_parts(self) -> ReaderData<T> {
+        self.into()
+    }
+
+    /// Build from raw components
+    #[deprecated(note = "Use `Reader::from(data)` instead")]
+    pub fn from_parts(data: ReaderData<T>) -> Self {
+        Self::from(data)
+    }
+}
+
+impl<T> From<Reader<T>> for ReaderData<T> {
+    fn from(source: Reader<T>) -> Self {
+        Self {
+            stream: source.stream.inner,
+            version: source.version,
+            header: source.header,
+            xml_header: source.xml_header,
+            heprup: source.heprup,
+        }
+    }
+}
+
+impl<T> From<ReaderData<T>> for Reader<T> {
+    fn from(source: ReaderData<T>) -> Self {
+        Self {
+            stream: CountingReader::new(source.stream),
+            version: source.version,
+            header: source.header,
+            xml_header: source.xml_header,
+            #[cfg(feature = "xml")]
+            xml_header_text: None,
+            heprup: source.heprup,
+            init_attr_raw: String::new(),
+            unknown_tags: Vec::new(),
+            options: ReaderBuilder::default(),
+            events_start: 0,
+            deferred_header: None,
+            progress: None,
+            #[cfg(feature = "metrics")]
+            meter: ThroughputMeter::new(),
+        }
+    }
+}
+
+impl<R: Read> Reader<BufReader<R>> {
+    /// Create a new LHEF reader from an unbuffered stream
+    ///
+    /// The stream is wrapped in a [BufReader]. Use [Reader::new] for
+    /// streams that are already buffered.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use std::fs::File;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut reader = lhef::Reader::from_read(File::open("events.lhe")?)?;
+    /// while let Some(event) = reader.hepeup()? {
+    ///     println!("Event weight: {}", event.XWGTUP);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_read(stream: R) -> Result<Self, ReadError> {
+        Self::new(BufReader::new(stream))
+    }
+}
+
+impl Reader<std::io::StdinLock<'static>> {
+    /// Create a new LHEF reader for the standard input
+    ///
+    /// The standard input is locked for the lifetime of the reader.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// // cat events.lhe | mytool
+    /// let mut reader = lhef::Reader::from_stdin().unwrap();
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     println!("Event weight: {}", event.XWGTUP);
+    /// }
+    /// ```
+    pub fn from_stdin() -> Result<Self, ReadError> {
+        Self::new(std::io::stdin().lock())
+    }
+}
+
+impl Reader<Box<dyn BufRead + Send>> {
+    /// Open a LHEF file
+    ///
+    /// Compressed files are recognised by their extension and
+    /// decompressed while reading:
+    ///
+    /// | Extension | Compression | Feature |
+    /// |-----------|-------------|---------|
+    /// | `.gz`     | gzip        | `gzip`  |
+    /// | `.bz2`    | bzip2       | `bzip2` |
+    /// | `.xz`     | xz          | `xz`    |
+    /// | `.zst`    | zstd        | `zstd`  |
+    ///
+    /// Opening a compressed file without the corresponding feature
+    /// results in an error. Files with any other extension are read
+    /// as they are.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let mut reader = lhef::Reader::from_path("events.lhe.gz").unwrap();
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     println!("Event weight: {}", event.XWGTUP);
+    /// }
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ReadError> {
+        Self::new(open_path(path.as_ref())?)
+    }
+}
+
+// Open a file, decompressing it according to its extension
+fn open_path(path: &Path) -> Result<Box<dyn BufRead + Send>, ReadError> {
+    let file = BufReader::new(File::open(path)?);
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    let stream: Box<dyn BufRead + Send> = match extension {
+        Some("gz") => {
+            #[cfg(feature = "gzip")]
+            {
+                let decoder = flate2::bufread::MultiGzDecoder::new(file);
+                Box::new(BufReader::new(decoder))
+            }
+            #[cfg(not(feature = "gzip"))]
+            return Err(ReadError::CompressionDisabled("gz", "gzip"));
+        }
+        Some("bz2") => {
+            #[cfg(feature = "bzip2")]
+            {
+                let decoder = bzip2::bufread::MultiBzDecoder::new(file);
+                Box::new(BufReader::new(decoder))
+            }
+            #[cfg(not(feature = "bzip2"))]
+            return Err(ReadError::CompressionDisabled("bz2", "bzip2"));
+        }
+        Some("xz") => {
+            #[cfg(feature = "xz")]
+            {
+                let decoder = xz2::bufread::XzDecoder::new_multi_decoder(file);
+                Box::new(BufReader::new(decoder))
+            }
+            #[cfg(not(feature = "xz"))]
+            return Err(ReadError::CompressionDisabled("xz", "xz"));
+        }
+        Some("zst") => {
+            #[cfg(feature = "zstd")]
+            {
+                let decoder = zstd::Decoder::with_buffer(file)?;
+                Box::new(BufReader::new(decoder))
+            }
+            #[cfg(not(feature = "zstd"))]
+            return Err(ReadError::CompressionDisabled("zst", "zstd"));
+        }
+        _ => Box::new(file),
+    };
+    Ok(stream)
+}
+
+impl<T: BufRead + Seek> Reader<T> {
+    /// Go back to the first event
+    ///
+    /// When constructing a reader from [ReaderData], this goes back to
+    /// the position of the stream at construction time.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let mut max_weight = 0f64;
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     max_weight = max_weight.max(event.XWGTUP.abs());
+    /// }
+    /// reader.rewind_events().unwrap();
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     println!("Relative weight: {}", event.XWGTUP / max_weight);
+    /// }
+    /// ```
+    pub fn rewind_events(&mut self) -> Result<(), ReadError> {
+        self.seek_to_position(self.events_start)?;
+        Ok(())
+    }
+
+    /// Read the run information and all remaining events
+    ///
+    /// Like [Reader::read_all], but the remaining events are counted
+    /// first to reserve the required memory in advance. Counting only
+    /// looks for opening `<event>` tags and is much faster than parsing
+    /// the events.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let (heprup, events) = reader.read_all_reserved().unwrap();
+    /// println!("Read {} events", events.len());
+    /// ```
+    pub fn read_all_reserved(
+        mut self,
+    ) -> Result<(HEPRUP, Vec<HEPEUP>), ReadError> {
+        let start = self.position();
+        let ignore_case = self.options.case_insensitive_tags;
+        let mut nevents = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.read_line(&mut line)? == 0 {
+                break;
+            }
+            if starts_with_tag(&line, EVENT_START, ignore_case) {
+                nevents += 1;
+            } else if is_tag(&line, LHEF_LAST_LINE, ignore_case) {
+                break;
+            }
+        }
+        self.seek_to_position(start)?;
+        self.read_all_into(Vec::with_capacity(nevents))
+    }
+
+    // Seek to a position as returned by `Reader::position`
+    fn seek_to_position(&mut self, position: u64) -> std::io::Result<()> {
+        let offset = position as i64 - self.stream.count as i64;
+        self.stream.inner.seek(SeekFrom::Current(offset))?;
+        self.stream.count = position;
+        Ok(())
+    }
+}
+
+/// LHEF reader with random access to events
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let file = std::fs::File::open("events.lhe").unwrap();
+/// let file = std::io::BufReader::new(file);
+/// let mut reader = lhef::reader::IndexedReader::new(file).unwrap();
+///
+/// let offsets = reader.build_index().unwrap();
+/// println!("Found {} events", offsets.len());
+/// let event = reader.read_event_at(1000).unwrap();
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct IndexedReader<T> {
+    reader: Reader<T>,
+    index: Vec<u64>,
+}
+
+impl<T: BufRead + Seek> IndexedReader<T> {
+    /// Create a new LHEF reader with random access to events
+    pub fn new(stream: T) -> Result<Self, ReadError> {
+        Ok(Self::from(Reader::new(stream)?))
+    }
+
+    /// Access the underlying reader
+    pub fn reader(&self) -> &Reader<T> {
+        &self.reader
+    }
+
+    /// Record the position of each event
+    ///
+    /// Returns the position of each event in the format of
+    /// [Reader::position]. This method has to be called before
+    /// accessing events with [IndexedReader::read_event_at].
+    pub fn build_index(&mut self) -> Result<Vec<u64>, ReadError> {
+        self.index.clear();
+        self.reader.rewind_events()?;
+        let ignore_case = self.reader.options.case_insensitive_tags;
+        let mut line = String::new();
+        loop {
+            let position = self.reader.position();
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            if starts_with_tag(&line, EVENT_START, ignore_case) {
+                self.index.push(position);
+            } else if is_tag(&line, LHEF_LAST_LINE, ignore_case) {
+                break;
+            }
+        }
+        Ok(self.index.clone())
+    }
+
+    /// Read the event with the given index, starting from zero
+    pub fn read_event_at(&mut self, i: usize) -> Result<HEPEUP, ReadError> {
+        let Some(&position) = self.index.get(i) else {
+            return Err(ReadError::NoSuchEvent(i));
+        };
+        self.reader.seek_to_position(position)?;
+        self.reader.hepeup()?.ok_or(ReadError::NoSuchEvent(i))
+    }
+}
+
+impl<T> From<Reader<T>> for IndexedReader<T> {
+    fn from(reader: Reader<T>) -> Self {
+        Self {
+            reader,
+            index: Vec::new(),
+        }
+    }
+}
+
+/// LHEF reader for several concatenated documents
+///
+/// Each document starts with its own `<LesHouchesEvents>` line and has
+/// its own headers and run information. Whitespace between documents is
+/// ignored.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let file = std::fs::File::open("events.lhe").unwrap();
+/// let file = std::io::BufReader::new(file);
+/// let mut reader = lhef::reader::MultiReader::new(file);
+///
+/// while let Some((heprup, events)) = reader.next_document().unwrap() {
+///     println!("Beam energies: {:?}", heprup.EBMUP);
+///     for event in events {
+///         let event = event.unwrap();
+///         // ...
+///     }
+/// }
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct MultiReader<T> {
+    stream: Option<T>,
+    options: ReaderBuilder,
+    current: Option<Reader<T>>,
+    // Whether the last line of the current document has been read
+    document_done: bool,
+}
+
+impl<T: BufRead> MultiReader<T> {
+    /// Create a new LHEF reader for concatenated documents
+    pub fn new(stream: T) -> Self {
+        ReaderBuilder::new().build_multi(stream)
+    }
+
+    /// Go to the next document
+    ///
+    /// Returns the run information and an iterator over the events of
+    /// the next document, or `None` if there are no more documents. Any
+    /// events of the previous document that have not been read are
+    /// skipped. The iterator stops after the first error. After an
+    /// error in the headers, the run information, or an event, further
+    /// documents can in general not be read.
+    pub fn next_document(
+        &mut self,
+    ) -> Result<Option<(HEPRUP, DocumentEvents<'_, T>)>, ReadError> {
+        if let Some(mut reader) = self.current.take() {
+            if !self.document_done {
+                while reader.next_raw_event()?.is_some() {}
+            }
+            self.stream = Some(ReaderData::from(reader).stream);
+        }
+        let Some(mut stream) = self.stream.take() else {
+            return Ok(None);
+        };
+        loop {
+            let buf = stream.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            let whitespace =
+                buf.iter().take_while(|c| c.is_ascii_whitespace()).count();
+            if whitespace == 0 {
+                break;
+            }
+            stream.consume(whitespace);
+        }
+        let reader = self.current.insert(self.options.build(stream)?);
+        self.document_done = false;
+        let events = DocumentEvents {
+            reader,
+            done: &mut self.document_done,
+        };
+        Ok(Some((events.reader.heprup().clone(), events)))
+    }
+}
+
+/// Iterator over the events of a single document
+///
+/// See [MultiReader::next_document].
+#[derive(Debug)]
+pub struct DocumentEvents<'a, T> {
+    reader: &'a mut Reader<T>,
+    done: &'a mut bool,
+}
+
+impl<T> DocumentEvents<'_, T> {
+    /// Access the reader for the current document
+    pub fn reader(&self) -> &Reader<T> {
+        self.reader
+    }
+}
+
+impl<T: BufRead> Iterator for DocumentEvents<'_, T> {
+    type Item = Result<HEPEUP, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if *self.done {
+            return None;
+        }
+        match self.reader.hepeup() {
+            Ok(Some(event)) => Some(Ok(event)),
+            Ok(None) => {
+                *self.done = true;
+                None
+            }
+            Err(err) => {
+                *self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// Wrapper counting the number of bytes read
+#[derive(Debug, PartialEq)]
+struct CountingReader<T> {
+    inner: T,
+    count: u64,
+}
+
+impl<T> CountingReader<T> {
+    fn new(inner: T) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<T: Read> Read for CountingReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let nread = self.inner.read(buf)?;
+        self.count += nread as u64;
+        Ok(nread)
+    }
+}
+
+impl<T: BufRead> BufRead for CountingReader<T> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.count += amt as u64;
+        self.inner.consume(amt)
+    }
+}
+
+/// Iterator over the events satisfying a predicate
+///
+/// See [Reader::filter_events].
+pub struct FilteredEvents<T, F> {
+    reader: Reader<T>,
+    pred: F,
+    done: bool,
+}
+
+impl<T, F> Iterator for FilteredEvents<T, F>
+where
+    T: BufRead,
+    F: FnMut(&HEPEUP) -> bool,
+{
+    type Item = Result<HEPEUP, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.reader.hepeup() {
+                Ok(Some(event)) => {
+                    if (self.pred)(&event) {
+                        return Some(Ok(event));
+                    }
+                }
+                Ok(None) => self.done = true,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over all events, skipping malformed ones
+///
+/// See [Reader::events_lossy].
+#[derive(Debug)]
+pub struct LossyEvents<'a, T> {
+    reader: &'a mut Reader<T>,
+    next_line: Option<String>,
+    done: bool,
+    truncated: bool,
+}
+
+impl<T: BufRead> LossyEvents<'_, T> {
+    /// Check whether the input ended before the last line
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let mut events = reader.events_lossy();
+    /// let nevents = events.by_ref().filter(|event| event.is_ok()).count();
+    /// if events.truncated() {
+    ///     println!("Input was truncated after {nevents} events");
+    /// }
+    /// ```
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn skip_to_next_event(&mut self) -> Result<(), ReadError> {
+        let ignore_case = self.reader.options.case_insensitive_tags;
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                self.done = true;
+                self.truncated = true;
+                return Ok(());
+            }
+            if starts_with_tag(&line, EVENT_START, ignore_case)
+                || is_tag(&line, LHEF_LAST_LINE, ignore_case)
+            {
+                self.next_line = Some(line);
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<T: BufRead> Iterator for LossyEvents<'_, T> {
+    type Item = Result<HEPEUP, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let line = match self.next_line.take() {
+            Some(line) => line,
+            None => {
+                let mut line = String::new();
+                if let Err(err) = self.reader.read_line(&mut line) {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+                line
+            }
+        };
+        match self.reader.hepeup_starting_with(line) {
+            Ok(Some(event)) => Some(Ok(event)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(ReadError::ReadErr(err)) => {
+                self.done = true;
+                Some(Err(err.into()))
+            }
+            Err(err @ ReadError::LineTooLong(_)) => {
+                self.done = true;
+                Some(Err(err))
+            }
+            Err(err @ ReadError::EndOfFile(_)) => {
+                self.done = true;
+                self.truncated = true;
+                Some(Err(err))
+            }
+            Err(err) => {
+                if let Err(err) = self.skip_to_next_event() {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+fn parse_version<T: BufRead>(
     stream: &mut T,
-) -> Result<&'static str, ReadError> {
+    options: &ReaderBuilder,
+) -> Result<String, ReadError> {
     use self::ReadError::*;
+    let max_line = options.max_line_bytes;
     let mut first_line = String::new();
-    stream.read_line(&mut first_line)?;
-    let line_cp = first_line.clone();
-    let mut line_entries = first_line.trim().split('"');
-    if line_entries.next() != Some(LHEF_TAG_OPEN) {
-        return Err(ReadError::BadFirstLine(line_cp));
-    };
-    let version = match line_entries.next() {
-        Some("1.0") => "1.0",
-        Some("2.0") => "2.0",
-        Some("3.0") => "3.0",
-        Some(version) => return Err(UnsupportedVersion(version.to_string())),
-        None => return Err(MissingVersion),
-    };
-    if line_entries.next() != Some(">") {
-        return Err(ReadError::BadFirstLine(line_cp));
+    read_line(stream, &mut first_line, max_line)?;
+    let mut tag = first_line.trim_start_matches(BOM).trim();
+    if tag.starts_with(XML_DECL_START) {
+        tag = match tag.find(XML_DECL_END) {
+            Some(idx) => tag[idx + XML_DECL_END.len()..].trim_start(),
+            None => return Err(BadFirstLine(first_line)),
+        };
+        if tag.is_empty() {
+            first_line.clear();
+            read_line(stream, &mut first_line, max_line)?;
+            tag = first_line.trim();
+        }
+    }
+    let is_root_tag = tag
+        .strip_prefix(LHEF_TAG_START)
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|c| c.is_whitespace() || c == '>');
+    if !is_root_tag {
+        return Err(BadFirstLine(first_line));
+    }
+    let attr = match extract_xml_attr(tag, options.strict) {
+        Ok(attr) => attr,
+        Err(_) => return Err(BadFirstLine(first_line)),
     };
-    Ok(version)
+    match attr.get("version").map(|v| v.as_str()) {
+        Some(version @ ("1.0" | "2.0" | "3.0")) => Ok(version.to_owned()),
+        Some(version) if options.allow_unknown_versions => {
+            Ok(version.to_owned())
+        }
+        Some(version) => Err(UnsupportedVersion(version.to_owned())),
+        None => Err(MissingVersion),
+    }
+}
+
+// Headers preceding the run information
+#[derive(Default)]
+struct Headers {
+    header: String,
+    xml_header: Option<XmlHeader>,
+    #[cfg(feature = "xml")]
+    xml_header_text: Option<String>,
 }
 
 fn parse_header<T: BufRead>(
     mut stream: &mut T,
-) -> Result<(String, Option<XmlTree>, String), ReadError> {
+    options: &ReaderBuilder,
+) -> Result<(Headers, String), ReadError> {
     use ReadError::BadHeaderStart;
-    let mut header = String::new();
-    let mut xml_header = None;
+    let ignore_case = options.case_insensitive_tags;
+    let max_len = options.max_header_bytes;
+    let mut headers = Headers::default();
     loop {
         let mut header_text = String::new();
-        stream.read_line(&mut header_text)?;
+        read_line(stream, &mut header_text, options.max_line_bytes)?;
         if header_text.trim_start().starts_with(COMMENT_START) {
             if header_text.trim() != COMMENT_START {
                 return Err(BadHeaderStart(header_text));
             }
-            read_lines_until(&mut stream, &mut header_text, COMMENT_END)?;
-            header = header_text;
-        } else if header_text.trim_start().starts_with(HEADER_START) {
-            read_lines_until(&mut stream, &mut header_text, HEADER_END)?;
-            xml_header = Some(XmlTree::parse(header_text.as_bytes())?);
-        } else if header_text.trim_start().starts_with(INIT_START) {
-            return Ok((header, xml_header, header_text));
+            read_lines_until(
+                &mut stream,
+                &mut header_text,
+                COMMENT_END,
+                "header",
+                max_len,
+                options,
+            )?;
+            headers.header = header_text;
+        } else if starts_with_tag(&header_text, HEADER_START, ignore_case) {
+            if options.defer_xml_header {
+                return Ok((headers, header_text));
+            }
+            read_lines_until(
+                &mut stream,
+                &mut header_text,
+                HEADER_END,
+                "header",
+                max_len,
+                options,
+            )?;
+            #[cfg(feature = "xml")]
+            {
+                let xml_header = XmlTree::parse(header_text.as_bytes())?;
+                headers.xml_header = Some(xml_header);
+                headers.xml_header_text = Some(header_text);
+            }
+            #[cfg(not(feature = "xml"))]
+            {
+                headers.xml_header = Some(header_text);
+            }
+        } else if starts_with_tag(&header_text, INIT_START, ignore_case) {
+            let max_line = options.max_line_bytes;
+            read_open_tag(&mut stream, &mut header_text, "init", max_line)?;
+            return Ok((headers, header_text));
         } else {
             return Err(ReadError::BadHeaderStart(header_text));
         }
     }
 }
 
+// Remove the last line, keeping the line terminator of the previous line
 fn pop_line(s: &mut String) {
     s.pop();
     while !s.is_empty() && !s.ends_with('\n') {
@@ -197,19 +1933,105 @@ fn pop_line(s: &mut String) {
 
 fn read_lines_until<T: BufRead>(
     stream: &mut T,
-    header: &mut String,
-    header_end: &str,
+    text: &mut String,
+    end: &str,
+    block: &'static str,
+    max_len: usize,
+    options: &ReaderBuilder,
 ) -> Result<(), ReadError> {
+    let max_line = options.max_line_bytes;
+    let ignore_case = options.case_insensitive_tags;
     loop {
-        if stream.read_line(header)? == 0 {
-            return Err(ReadError::EndOfFile("header"));
+        let remaining = max_len.saturating_sub(text.len());
+        let max_read = remaining.min(max_line);
+        let nread = read_line_truncated(stream, text, max_read)?;
+        if nread == 0 {
+            return Err(ReadError::EndOfFile(block));
+        }
+        if nread > remaining {
+            return Err(ReadError::TooLarge(block, max_len));
+        }
+        if nread > max_line {
+            return Err(ReadError::LineTooLong(max_line));
         }
-        if header.lines().last().unwrap().trim() == header_end {
+        if is_tag(text.lines().last().unwrap(), end, ignore_case) {
             return Ok(());
         }
     }
 }
 
+// Read a line of at most `max_len` bytes
+fn read_line<T: BufRead>(
+    stream: &mut T,
+    text: &mut String,
+    max_len: usize,
+) -> Result<usize, ReadError> {
+    let nread = read_line_truncated(stream, text, max_len)?;
+    if nread > max_len {
+        return Err(ReadError::LineTooLong(max_len));
+    }
+    Ok(nread)
+}
+
+// Read a line, stopping after `max_len + 1` bytes for longer lines
+//
+// Longer lines are not appended to `text`, since they may end in the
+// middle of a UTF-8 character.
+fn read_line_truncated<T: BufRead>(
+    stream: &mut T,
+    text: &mut String,
+    max_len: usize,
+) -> std::io::Result<usize> {
+    // read at most one byte more than allowed to detect long lines
+    let limit = max_len.saturating_add(1);
+    let mut line = Vec::new();
+    let nread = stream.take(limit as u64).read_until(b'\n', &mut line)?;
+    if nread > max_len {
+        return Ok(nread);
+    }
+    let line = std::str::from_utf8(&line).map_err(|err| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    })?;
+    text.push_str(line);
+    Ok(nread)
+}
+
+// Read until the end of an opening tag that may span several lines
+fn read_open_tag<T: BufRead>(
+    stream: &mut T,
+    text: &mut String,
+    block: &'static str,
+    max_line: usize,
+) -> Result<(), ReadError> {
+    while !text.contains('>') {
+        if read_line(stream, text, max_line)? == 0 {
+            return Err(ReadError::EndOfFile(block));
+        }
+    }
+    Ok(())
+}
+
+// Check whether a line starts with the given tag, ignoring leading
+// whitespace
+fn starts_with_tag(line: &str, tag: &str, ignore_case: bool) -> bool {
+    match line.trim_start().get(..tag.len()) {
+        Some(start) if ignore_case => start.eq_ignore_ascii_case(tag),
+        Some(start) => start == tag,
+        None => false,
+    }
+}
+
+// Check whether a line consists of the given tag, ignoring surrounding
+// whitespace
+fn is_tag(line: &str, tag: &str, ignore_case: bool) -> bool {
+    let line = line.trim();
+    if ignore_case {
+        line.eq_ignore_ascii_case(tag)
+    } else {
+        line == tag
+    }
+}
+
 fn parse<F, T, S>(name: F, text: Option<&str>) -> Result<T, ReadError>
 where
     T: str::FromStr,
@@ -224,10 +2046,11 @@ where
     }
 }
 
-fn parse_f64<F, S>(name: F, text: Option<&str>) -> Result<f64, ReadError>
+fn parse_float<F, S, X>(name: F, text: Option<&str>) -> Result<X, ReadError>
 where
     F: FnOnce() -> S,
     S: Into<String>,
+    X: Float,
 {
     use self::ReadError::*;
     let text: &str = text.ok_or_else(|| MissingEntry(name().into()))?;
@@ -258,7 +2081,7 @@ struct Attr<'a> {
     value: &'a str,
 }
 
-fn next_attr(attr_str: &str) -> Result<(Option<Attr>, &str), ReadError> {
+fn next_attr(attr_str: &str) -> Result<(Option<Attr<'_>>, &str), ReadError> {
     use self::ReadError::BadXmlTag;
     let mut rem = attr_str;
     let name_end = rem.find(|c: char| c.is_whitespace() || c == '=');
@@ -287,7 +2110,15 @@ fn next_attr(attr_str: &str) -> Result<(Option<Attr>, &str), ReadError> {
     Ok((Some(attr), rem))
 }
 
-fn extract_xml_attr(xml_tag: &str) -> Result<XmlAttr, ReadError> {
+// Extract the attributes of a tag
+//
+// If `strict` is set, duplicate attribute names are an error. Otherwise,
+// the last value is kept. Values can span several lines and are kept
+// verbatim, including line breaks.
+pub(crate) fn extract_xml_attr(
+    xml_tag: &str,
+    strict: bool,
+) -> Result<XmlAttr, ReadError> {
     let mut attr_str = extract_xml_attr_str(xml_tag)?;
     let mut attr = XmlAttr::new();
     loop {
@@ -297,28 +2128,50 @@ fn extract_xml_attr(xml_tag: &str) -> Result<XmlAttr, ReadError> {
             Some(next_attr) => {
                 let name = next_attr.name.to_string();
                 let value = next_attr.value.to_string();
-                attr.insert(name, value);
+                if attr.insert(name, value).is_some() && strict {
+                    return Err(ReadError::BadXmlTag(xml_tag.to_owned()));
+                }
             }
         };
         attr_str = rem;
     }
 }
 
+// Upper limit for reserving memory before the corresponding entries
+// have actually been read
+const MAX_PREALLOC: usize = 1024;
+
+fn checked_capacity(
+    name: &'static str,
+    len: i32,
+    max: usize,
+) -> Result<usize, ReadError> {
+    if len < 0 {
+        return Err(ReadError::NegativeCount(name, len));
+    }
+    if len as usize > max {
+        return Err(ReadError::TooManyEntries(name, len, max));
+    }
+    Ok((len as usize).min(MAX_PREALLOC))
+}
+
 #[allow(non_snake_case)]
-fn parse_init<T: BufRead>(
+fn parse_init<T: BufRead, F: Float>(
     init_open: &str,
     stream: &mut T,
-) -> Result<HEPRUP, ReadError> {
+    options: &ReaderBuilder,
+) -> Result<HEPRUP<F>, ReadError> {
+    let max_line = options.max_line_bytes;
     let mut line = String::new();
-    stream.read_line(&mut line)?;
+    read_line(stream, &mut line, max_line)?;
     let mut entries = line.split_whitespace();
     let IDBMUP = [
         parse(|| "IDBMUP(1)", entries.next())?,
         parse(|| "IDBMUP(2)", entries.next())?,
     ];
     let EBMUP = [
-        parse_f64(|| "EBMUP(1)", entries.next())?,
-        parse_f64(|| "EBMUP(2)", entries.next())?,
+        parse_float(|| "EBMUP(1)", entries.next())?,
+        parse_float(|| "EBMUP(2)", entries.next())?,
     ];
     let PDFGUP = [
         parse(|| "PDFGUP(1)", entries.next())?,
@@ -330,34 +2183,42 @@ fn parse_init<T: BufRead>(
     ];
     let IDWTUP = parse(|| "IDWTUP", entries.next())?;
     let NPRUP = parse(|| "NPRUP", entries.next())?;
-    let mut XSECUP = Vec::with_capacity(NPRUP as usize);
-    let mut XERRUP = Vec::with_capacity(NPRUP as usize);
-    let mut XMAXUP = Vec::with_capacity(NPRUP as usize);
-    let mut LPRUP = Vec::with_capacity(NPRUP as usize);
+    let capacity = checked_capacity("NPRUP", NPRUP, options.max_subprocesses)?;
+    let mut XSECUP = Vec::with_capacity(capacity);
+    let mut XERRUP = Vec::with_capacity(capacity);
+    let mut XMAXUP = Vec::with_capacity(capacity);
+    let mut LPRUP = Vec::with_capacity(capacity);
     for i in 0..NPRUP {
         let mut line = String::new();
-        stream.read_line(&mut line)?;
+        read_line(stream, &mut line, max_line)?;
         let mut entries = line.split_whitespace();
-        XSECUP
-            .push(parse_f64(|| format!("XSECUP({})", i + 1), entries.next())?);
-        XERRUP
-            .push(parse_f64(|| format!("XERRUP({})", i + 1), entries.next())?);
-        XMAXUP
-            .push(parse_f64(|| format!("XMAXUP({})", i + 1), entries.next())?);
+        XSECUP.push(parse_float(
+            || format!("XSECUP({})", i + 1),
+            entries.next(),
+        )?);
+        XERRUP.push(parse_float(
+            || format!("XERRUP({})", i + 1),
+            entries.next(),
+        )?);
+        XMAXUP.push(parse_float(
+            || format!("XMAXUP({})", i + 1),
+            entries.next(),
+        )?);
         LPRUP.push(parse(|| format!("LPRUP({})", i + 1), entries.next())?);
     }
+    let ignore_case = options.case_insensitive_tags;
     let mut info = String::new();
     loop {
-        if stream.read_line(&mut info)? == 0 {
+        if read_line(stream, &mut info, max_line)? == 0 {
             return Err(ReadError::EndOfFile("init"));
         }
-        if info.lines().last().unwrap() == INIT_END {
+        if is_tag(info.lines().last().unwrap(), INIT_END, ignore_case) {
             pop_line(&mut info);
             break;
         }
     }
-    let attr = extract_xml_attr(init_open)?;
-    Ok(HEPRUP {
+    let attr = extract_xml_attr(init_open, options.strict)?;
+    let heprup = HEPRUP {
         IDBMUP,
         EBMUP,
         PDFGUP,
@@ -370,33 +2231,42 @@ fn parse_init<T: BufRead>(
         LPRUP,
         info,
         attr,
-    })
+    };
+    if options.reject_nonfinite {
+        if let Some(name) = heprup.find_nonfinite() {
+            return Err(ReadError::NonFinite(name));
+        }
+    }
+    Ok(heprup)
 }
 
 #[allow(non_snake_case)]
-fn parse_event<T: BufRead>(
+fn parse_event<T: BufRead, F: Float>(
     event_open: &str,
     stream: &mut T,
-) -> Result<HEPEUP, ReadError> {
+    options: &ReaderBuilder,
+) -> Result<HEPEUP<F>, ReadError> {
+    let max_line = options.max_line_bytes;
     let mut line = String::new();
-    stream.read_line(&mut line)?;
+    read_line(stream, &mut line, max_line)?;
     let mut entries = line.split_whitespace();
     let NUP = parse(|| "NUP", entries.next())?;
     let IDRUP = parse(|| "IDRUP", entries.next())?;
-    let XWGTUP = parse_f64(|| "XWGTUP", entries.next())?;
-    let SCALUP = parse_f64(|| "SCALUP", entries.next())?;
-    let AQEDUP = parse_f64(|| "AQEDUP", entries.next())?;
-    let AQCDUP = parse_f64(|| "AQCDUP", entries.next())?;
-    let mut IDUP = Vec::with_capacity(NUP as usize);
-    let mut ISTUP = Vec::with_capacity(NUP as usize);
-    let mut MOTHUP = Vec::with_capacity(NUP as usize);
-    let mut ICOLUP = Vec::with_capacity(NUP as usize);
-    let mut PUP = Vec::with_capacity(NUP as usize);
-    let mut VTIMUP = Vec::with_capacity(NUP as usize);
-    let mut SPINUP = Vec::with_capacity(NUP as usize);
+    let XWGTUP = parse_float(|| "XWGTUP", entries.next())?;
+    let SCALUP = parse_float(|| "SCALUP", entries.next())?;
+    let AQEDUP = parse_float(|| "AQEDUP", entries.next())?;
+    let AQCDUP = parse_float(|| "AQCDUP", entries.next())?;
+    let capacity = checked_capacity("NUP", NUP, options.max_particles)?;
+    let mut IDUP = Vec::with_capacity(capacity);
+    let mut ISTUP = Vec::with_capacity(capacity);
+    let mut MOTHUP = Vec::with_capacity(capacity);
+    let mut ICOLUP = Vec::with_capacity(capacity);
+    let mut PUP = Vec::with_capacity(capacity);
+    let mut VTIMUP = Vec::with_capacity(capacity);
+    let mut SPINUP = Vec::with_capacity(capacity);
     for i in 0..NUP {
         let mut line = String::new();
-        stream.read_line(&mut line)?;
+        read_line(stream, &mut line, max_line)?;
         let mut entries = line.split_whitespace();
         IDUP.push(parse(|| format!("IDUP({})", i + 1), entries.next())?);
         ISTUP.push(parse(|| format!("ISTUP({})", i + 1), entries.next())?);
@@ -409,94 +2279,718 @@ fn parse_event<T: BufRead>(
             parse(|| format!("ICOLUP({}, 2)", i + 1), entries.next())?,
         ]);
         PUP.push([
-            parse_f64(|| format!("PUP({}, 1)", i + 1), entries.next())?,
-            parse_f64(|| format!("PUP({}, 2)", i + 1), entries.next())?,
-            parse_f64(|| format!("PUP({}, 3)", i + 1), entries.next())?,
-            parse_f64(|| format!("PUP({}, 4)", i + 1), entries.next())?,
-            parse_f64(|| format!("PUP({}, 5)", i + 1), entries.next())?,
+            parse_float(|| format!("PUP({}, 1)", i + 1), entries.next())?,
+            parse_float(|| format!("PUP({}, 2)", i + 1), entries.next())?,
+            parse_float(|| format!("PUP({}, 3)", i + 1), entries.next())?,
+            parse_float(|| format!("PUP({}, 4)", i + 1), entries.next())?,
+            parse_float(|| format!("PUP({}, 5)", i + 1), entries.next())?,
         ]);
-        VTIMUP
-            .push(parse_f64(|| format!("VTIMUP({})", i + 1), entries.next())?);
-        SPINUP
-            .push(parse_f64(|| format!("SPINUP({})", i + 1), entries.next())?);
+        VTIMUP.push(parse_float(
+            || format!("VTIMUP({})", i + 1),
+            entries.next(),
+        )?);
+        SPINUP.push(parse_float(
+            || format!("SPINUP({})", i + 1),
+            entries.next(),
+        )?);
+    }
+    let ignore_case = options.case_insensitive_tags;
+    let mut info = String::new();
+    loop {
+        if options.skip_info {
+            info.clear();
+        }
+        if read_line(stream, &mut info, max_line)? == 0 {
+            return Err(ReadError::EndOfFile("event"));
+        }
+        if is_tag(info.lines().last().unwrap(), EVENT_END, ignore_case) {
+            pop_line(&mut info);
+            break;
+        }
+    }
+    if options.skip_info {
+        info.clear();
+    }
+    let attr = extract_xml_attr(event_open, options.strict)?;
+    let event = HEPEUP {
+        NUP,
+        IDRUP,
+        XWGTUP,
+        SCALUP,
+        AQEDUP,
+        AQCDUP,
+        IDUP,
+        ISTUP,
+        MOTHUP,
+        ICOLUP,
+        PUP,
+        VTIMUP,
+        SPINUP,
+        info,
+        attr,
+        raw: None,
+    };
+    if options.reject_nonfinite {
+        if let Some(name) = event.find_nonfinite() {
+            return Err(ReadError::NonFinite(name));
+        }
+    }
+    Ok(event)
+}
+
+impl<F: Float> TryFrom<&str> for HEPEUP<F> {
+    type Error = ReadError;
+
+    /// Parse a single `<event>` block
+    ///
+    /// Leading and trailing whitespace is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let text = "
+    /// <event>
+    /// 1 1 0.5 91.188 0.0078 0.118
+    /// 22 1 0 0 0 0 0.0 0.0 45.0 45.0 0.0 0.0 -1.0
+    /// </event>
+    /// ";
+    /// let event: lhef::HEPEUP = text.try_into().unwrap();
+    /// assert_eq!(event.XWGTUP, 0.5);
+    /// assert_eq!(event.IDUP, [22]);
+    /// ```
+    fn try_from(text: &str) -> Result<Self, Self::Error> {
+        let text = text.trim();
+        if !starts_with_tag(text, EVENT_START, false) {
+            return Err(ReadError::BadEventStart(text.to_owned()));
+        }
+        let (event_open, mut rest) = split_open_tag(text, "event")?;
+        parse_event(event_open, &mut rest, &ReaderBuilder::default())
+    }
+}
+
+impl<F: Float> TryFrom<&str> for HEPRUP<F> {
+    type Error = ReadError;
+
+    /// Parse a single `<init>` block
+    ///
+    /// Leading and trailing whitespace is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let text = "
+    /// <init>
+    /// 2212 2212 7000 7000 0 0 230000 230000 2 1
+    /// 1.5 0.1 2.0 1
+    /// </init>
+    /// ";
+    /// let heprup: lhef::HEPRUP = text.try_into().unwrap();
+    /// assert_eq!(heprup.XSECUP, [1.5]);
+    /// ```
+    fn try_from(text: &str) -> Result<Self, Self::Error> {
+        let text = text.trim();
+        if !starts_with_tag(text, INIT_START, false) {
+            return Err(ReadError::BadInitStart(text.to_owned()));
+        }
+        let (init_open, mut rest) = split_open_tag(text, "init")?;
+        parse_init(init_open, &mut rest, &ReaderBuilder::default())
+    }
+}
+
+// Text between the name and the closing `>` of an opening tag
+fn raw_attr<'a>(open_tag: &'a str, tag_start: &str) -> &'a str {
+    let rest = open_tag.trim_start().get(tag_start.len()..).unwrap_or("");
+    match rest.find('>') {
+        Some(end) => &rest[..end],
+        None => rest,
+    }
+}
+
+// Split text into the first line(s) up to the end of the opening tag
+// and the remainder
+fn split_open_tag<'a>(
+    text: &'a str,
+    block: &'static str,
+) -> Result<(&'a str, &'a [u8]), ReadError> {
+    let tag_end = text.find('>').ok_or(ReadError::EndOfFile(block))?;
+    let line_end = match text[tag_end..].find('\n') {
+        Some(pos) => tag_end + pos + 1,
+        None => return Err(ReadError::EndOfFile(block)),
+    };
+    let (open, rest) = text.split_at(line_end);
+    Ok((open, rest.as_bytes()))
+}
+
+#[derive(Error, Debug)]
+pub enum ReadError {
+    #[error(
+        "First line '{0}' in input does not start with '{}'",
+        LHEF_TAG_START
+    )]
+    BadFirstLine(String),
+    #[error(
+        "Encountered unrecognized line '{0}', \
+         expected a header starting with '{}', '{}', \
+         or the init block starting with '{}'",
+        COMMENT_START,
+        HEADER_START,
+        INIT_START
+    )]
+    BadHeaderStart(String),
+    #[error("Encountered malformed xml tag: '{0}'")]
+    BadXmlTag(String),
+    #[error(
+        "Encountered unrecognized line '{0}', \
+         expected an event starting with '{}'",
+        EVENT_START
+    )]
+    BadEventStart(String),
+    #[error(
+        "Encountered unrecognized line '{0}', \
+         expected the init block starting with '{}'",
+        INIT_START
+    )]
+    BadInitStart(String),
+    #[error("Encountered line longer than {0} bytes")]
+    LineTooLong(usize),
+    #[error("Missing entry '{0}'")]
+    MissingEntry(String),
+    #[error("Failed to convert to number: '{0}'")]
+    ConversionError(String),
+    #[error("Entry '{0}' is not a finite number")]
+    NonFinite(String),
+    #[error(
+        "Unsupported version '{0}': only '1.0', '2.0', '3.0' are supported"
+    )]
+    UnsupportedVersion(String),
+    #[error("Version information missing")]
+    MissingVersion,
+    #[error("No event with index {0}")]
+    NoSuchEvent(usize),
+    #[error("Declared {0} events, but found {1}")]
+    EventCountMismatch(u64, u64),
+    #[error("{0} = {1} is negative")]
+    NegativeCount(&'static str, i32),
+    #[error("{0} = {1} exceeds the maximum of {2}")]
+    TooManyEntries(&'static str, i32, usize),
+    #[error("Size of '{0}' block exceeds the maximum of {1} bytes")]
+    TooLarge(&'static str, usize),
+    #[error("Encountered '{0}' block without closing tag")]
+    EndOfFile(&'static str),
+    #[error("Read error: {0}")]
+    ReadErr(#[from] std::io::Error),
+    #[error("Reading '.{0}' files requires the '{1}' feature")]
+    CompressionDisabled(&'static str, &'static str),
+    #[error("Failed to write header: {0}")]
+    HeaderWriteErr(std::io::Error),
+    #[cfg(feature = "xml")]
+    #[error("xml parse error: {0}")]
+    XmlErr(#[from] xmltree::ParseError),
+    #[error("Invalid event: {0}")]
+    Invalid(#[from] ValidationError),
+}
+
+#[cfg(test)]
+mod reader_tests {
+    extern crate flate2;
+    use super::*;
+
+    use reader_tests::flate2::bufread::GzDecoder;
+    use std::fs::File;
+    use std::io::{BufReader, Cursor, Read};
+
+    #[test]
+    fn read_version_tag() {
+        let rest = "<init>\n2212 2212 7000 7000 0 0 0 0 3 0\n\
+                    </init>\n</LesHouchesEvents>\n";
+        for first_line in [
+            "<LesHouchesEvents version=\"1.0\">",
+            "<LesHouchesEvents version = \"2.0\" >",
+            "  <LesHouchesEvents\tversion='3.0'>",
+            "<LesHouchesEvents xmlns:xsi=\"x\" version=\"3.0\" other='y'>",
+        ] {
+            let text = format!("{first_line}\n{rest}");
+            let reader = Reader::new(text.as_bytes());
+            assert!(reader.is_ok(), "failed to read '{first_line}'");
+        }
+
+        let text = format!("<LesHouchesEvents other=\"1.0\">\n{rest}");
+        let err = Reader::new(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, ReadError::MissingVersion));
+        let text = format!("<LesHouchesEvents version=\"4.0\">\n{rest}");
+        let err = Reader::new(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, ReadError::UnsupportedVersion(v) if v == "4.0"));
+        let text = format!("<LesHouchesEventsX version=\"1.0\">\n{rest}");
+        let err = Reader::new(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, ReadError::BadFirstLine(_)));
+    }
+
+    #[test]
+    fn read_unknown_version() {
+        let text = "<LesHouchesEvents version=\"3.1\">
+<init>
+2212 2212 7000 7000 0 0 0 0 3 0
+</init>
+<event>
+0 1 1 1 1 1
+</event>
+</LesHouchesEvents>
+";
+        let err = Reader::new(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, ReadError::UnsupportedVersion(v) if v == "3.1"));
+        let mut lhef = ReaderBuilder::new()
+            .allow_unknown_versions(true)
+            .build(text.as_bytes())
+            .unwrap();
+        assert_eq!(lhef.version(), "3.1");
+        assert!(lhef.hepeup().unwrap().is_some());
+        assert!(lhef.hepeup().unwrap().is_none());
+    }
+
+    #[test]
+    fn read_header_kv() {
+        let text = "<LesHouchesEvents version=\"1.0\">
+<!--
+Generated by some generator
+# Number of Events : 10
+seed = 42
+  url: https://example.com
+: no key
+-->
+<init>
+2212 2212 7000 7000 0 0 0 0 3 0
+</init>
+</LesHouchesEvents>
+";
+        let lhef = Reader::new(text.as_bytes()).unwrap();
+        assert_eq!(lhef.header_lines().len(), 5);
+        assert_eq!(lhef.header_lines()[0], "Generated by some generator");
+        assert_eq!(
+            lhef.header_kv(),
+            HashMap::from([
+                ("Number of Events", "10"),
+                ("seed", "42"),
+                ("url", "https://example.com"),
+            ])
+        );
+        assert!(lhef.header().starts_with("<!--"));
+    }
+
+    #[test]
+    fn read_prefix() {
+        let rest = "<LesHouchesEvents version=\"1.0\">
+<init>
+2212 2212 7000 7000 0 0 0 0 3 0
+</init>
+</LesHouchesEvents>
+";
+        for prefix in [
+            "\u{feff}",
+            "<?xml version=\"1.0\"?>\n",
+            "\u{feff}<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<?xml version=\"1.0\"?>",
+        ] {
+            let text = format!("{prefix}{rest}");
+            let lhef = Reader::new(text.as_bytes());
+            assert!(lhef.is_ok(), "failed to read with prefix '{prefix}'");
+            assert_eq!(lhef.unwrap().version(), "1.0");
+        }
+        let text = format!("<?xml version=\"1.0\"\n{rest}");
+        let err = Reader::new(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, ReadError::BadFirstLine(_)));
+    }
+
+    #[test]
+    fn read_case_insensitive() {
+        let text = "<LesHouchesEvents version=\"1.0\">
+<INIT>
+2212 2212 7000 7000 0 0 0 0 3 0
+</INIT>
+<EVENT attr=\"1\">
+0 1 1 1 1 1
+</EVENT>
+<Event>
+0 2 1 1 1 1
+</Event>
+</LESHOUCHESEVENTS>
+";
+        assert!(Reader::new(text.as_bytes()).is_err());
+        let mut lhef = ReaderBuilder::new()
+            .case_insensitive_tags(true)
+            .build(text.as_bytes())
+            .unwrap();
+        let event = lhef.hepeup().unwrap().unwrap();
+        assert_eq!(event.IDRUP, 1);
+        assert_eq!(event.attr["attr"], "1");
+        assert_eq!(
+            lhef.next_raw_event().unwrap().unwrap(),
+            "<Event>
+0 2 1 1 1 1
+</Event>
+"
+        );
+        assert!(lhef.hepeup().unwrap().is_none());
+    }
+
+    #[test]
+    fn read_multiline_tags() {
+        let text = "<LesHouchesEvents version=\"1.0\">
+<init a=\"1\"
+  b=\"2\">
+2212 2212 7000 7000 0 0 0 0 3 0
+</init>
+<event attr0=\"x\"
+  attr1='y'
+  attr2=\"z\"
+>
+0 1 1 1 1 1
+</event>
+<event a='1'
+  b='2'>
+0 2 3 1 1 1
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert_eq!(lhef.heprup().attr.len(), 2);
+        assert_eq!(lhef.heprup().IDBMUP, [2212, 2212]);
+        let event = lhef.hepeup().unwrap().unwrap();
+        assert_eq!(event.IDRUP, 1);
+        assert_eq!(event.attr.len(), 3);
+        assert_eq!(event.attr["attr1"], "y");
+        assert_eq!(lhef.max_weight().unwrap(), 3.);
+
+        let mut lhef = ReaderBuilder::new()
+            .preserve_raw(true)
+            .build(text.as_bytes())
+            .unwrap();
+        let event = lhef.hepeup().unwrap().unwrap();
+        assert_eq!(event.attr["attr2"], "z");
+        assert!(event.raw.unwrap().starts_with("<event attr0=\"x\"\n"));
+    }
+
+    #[test]
+    fn init_attr_raw() {
+        let attr = " a=\"1\"  b='2'\n  c=\"3\"\t";
+        let text = format!(
+            "<LesHouchesEvents version=\"1.0\">
+<init{attr}>
+2212 2212 7000 7000 0 0 0 0 3 0
+</init>
+</LesHouchesEvents>
+"
+        );
+        let start = text.find(INIT_START).unwrap() + INIT_START.len();
+        let end = start + text[start..].find('>').unwrap();
+        let lhef = Reader::new(text.as_bytes()).unwrap();
+        assert_eq!(lhef.init_attr_raw(), &text[start..end]);
+        assert_eq!(lhef.init_attr_raw(), attr);
+        assert_eq!(lhef.heprup().attr.len(), 3);
+
+        let text = text.replace("<init", "<INIT");
+        let lhef = ReaderBuilder::new()
+            .case_insensitive_tags(true)
+            .build(text.as_bytes())
+            .unwrap();
+        assert_eq!(lhef.init_attr_raw(), attr);
+    }
+
+    #[test]
+    fn read_multiline_attr_value() {
+        let text = "<LesHouchesEvents version=\"1.0\">
+<init comment=\"first
+second\">
+2212 2212 7000 7000 0 0 0 0 3 0
+</init>
+<event a='1' comment=\"  first
+  second
+\" b='2'>
+0 1 1 1 1 1
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert_eq!(lhef.heprup().attr["comment"], "first\nsecond");
+        let event = lhef.hepeup().unwrap().unwrap();
+        let comment = "  first\n  second\n";
+        assert_eq!(event.attr["comment"], comment);
+        assert_eq!(event.attr["b"], "2");
+
+        let start = text.find(EVENT_START).unwrap();
+        let parsed = HEPEUP::<f64>::try_from(&text[start..]).unwrap();
+        assert_eq!(parsed, event);
+
+        let mut writer = crate::Writer::new(vec![], "1.0").unwrap();
+        writer.heprup(lhef.heprup()).unwrap();
+        writer.hepeup(&event).unwrap();
+        writer.finish().unwrap();
+        let output = writer.into_inner();
+        let mut lhef = Reader::new(output.as_slice()).unwrap();
+        assert_eq!(lhef.heprup().attr["comment"], "first\nsecond");
+        assert_eq!(lhef.hepeup().unwrap().unwrap(), event);
+    }
+
+    #[test]
+    fn read_f32() {
+        let file =
+            File::open("test_data/HEJFOG.lhe.gz").expect("file not found");
+        let mut text = Vec::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_end(&mut text)
+            .unwrap();
+        let mut lhef = Reader::new(text.as_slice()).unwrap();
+        let mut lhef32 = Reader::new(text.as_slice()).unwrap();
+        let mut nevents = 0;
+        while let Some(event) = lhef.hepeup().unwrap() {
+            let event32: HEPEUP<f32> = lhef32.hepeup_as().unwrap().unwrap();
+            let close =
+                |x: f64, y: f32| (x - y as f64).abs() <= 1e-6 * x.abs().max(1.);
+            assert_eq!(event32.IDUP, event.IDUP);
+            assert!(close(event.XWGTUP, event32.XWGTUP));
+            assert!(close(event.AQCDUP, event32.AQCDUP));
+            for (p, p32) in event.PUP.iter().zip(&event32.PUP) {
+                for (&x, &y) in p.iter().zip(p32) {
+                    assert!(close(x, y), "{x} != {y}");
+                }
+            }
+            nevents += 1;
+        }
+        assert!(lhef32.hepeup_as::<f32>().unwrap().is_none());
+        assert_eq!(nevents, 10);
+    }
+
+    #[test]
+    fn read_unterminated_header() {
+        let mut text =
+            String::from("<LesHouchesEvents version=\"1.0\">\n<!--\n");
+        for _ in 0..1000 {
+            text += "some header line\n";
+        }
+        text += "<init>\n2212 2212 7000 7000 0 0 0 0 3 0\n</init>\n";
+        let mut input = Cursor::new(text.as_bytes());
+        let err = ReaderBuilder::new()
+            .max_header_bytes(100)
+            .build(&mut input)
+            .err()
+            .unwrap();
+        assert!(matches!(err, ReadError::TooLarge("header", 100)));
+        assert!(input.position() < 200);
+
+        let text = text.replace("<init>", "-->\n<init>");
+        assert!(ReaderBuilder::new()
+            .max_header_bytes(20_000)
+            .build(text.as_bytes())
+            .is_ok());
+        assert!(ReaderBuilder::new()
+            .max_header_bytes(10_000)
+            .build(text.as_bytes())
+            .is_err());
+    }
+
+    #[test]
+    fn take_large_header() {
+        let mut header = String::from("<header>\n<slha>\n");
+        for i in 0..10_000 {
+            header += &format!("  {i} 1.000000e+02 # parameter {i}\n");
+        }
+        header += "</slha>\n</header>\n";
+        let text = format!(
+            "<LesHouchesEvents version=\"1.0\">
+{header}<!--
+comment header
+-->
+<init>
+2212 2212 7000 7000 0 0 0 0 3 0
+</init>
+<event>
+0 1 1 91.188 0.0078125 0.118
+</event>
+</LesHouchesEvents>
+"
+        );
+        let builder = ReaderBuilder::new()
+            .max_header_bytes(1000)
+            .defer_xml_header(true);
+        let mut reader = builder.build(text.as_bytes()).unwrap();
+        assert_eq!(reader.heprup(), &HEPRUP::empty());
+        let mut output = Vec::new();
+        reader.take_header_to(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), header);
+        assert_eq!(reader.xml_header(), &None);
+        assert_eq!(reader.header(), "<!--\ncomment header\n-->\n");
+        assert_eq!(reader.heprup().IDBMUP, [2212, 2212]);
+        let mut output = Vec::new();
+        reader.take_header_to(&mut output).unwrap();
+        assert!(output.is_empty());
+        assert!(reader.hepeup().unwrap().is_some());
+        assert!(reader.hepeup().unwrap().is_none());
+
+        // reading an event discards the header
+        let mut reader = builder.build(text.as_bytes()).unwrap();
+        assert!(reader.hepeup().unwrap().is_some());
+        assert_eq!(reader.heprup().IDBMUP, [2212, 2212]);
+
+        // without deferring, the header is too large
+        let builder = builder.defer_xml_header(false);
+        assert!(matches!(
+            builder.build(text.as_bytes()),
+            Err(ReadError::TooLarge("header", 1000))
+        ));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn from_path_gzip() {
+        let mut reader = Reader::from_path("test_data/HEJFOG.lhe.gz").unwrap();
+        let mut nevents = 0;
+        while reader.hepeup().unwrap().is_some() {
+            nevents += 1;
+        }
+        assert_eq!(nevents, 10);
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn from_path_gzip() {
+        assert!(matches!(
+            Reader::from_path("test_data/HEJFOG.lhe.gz"),
+            Err(ReadError::CompressionDisabled("gz", "gzip"))
+        ));
+    }
+
+    #[test]
+    fn from_path_plain() {
+        let file = File::open("test_data/HEJFOG.lhe.gz").unwrap();
+        let mut input = GzDecoder::new(BufReader::new(file));
+        let mut text = Vec::new();
+        input.read_to_end(&mut text).unwrap();
+        let mut path =
+            tempfile::Builder::new().suffix(".lhe").tempfile().unwrap();
+        path.write_all(&text).unwrap();
+
+        let mut reader = Reader::from_path(path.path()).unwrap();
+        assert_eq!(reader.version(), "3.0");
+        let mut nevents = 0;
+        while reader.hepeup().unwrap().is_some() {
+            nevents += 1;
+        }
+        assert_eq!(nevents, 10);
+        assert!(matches!(
+            Reader::from_path("test_data/does_not_exist.lhe"),
+            Err(ReadError::ReadErr(_))
+        ));
     }
-    let mut info = String::new();
-    loop {
-        if stream.read_line(&mut info)? == 0 {
-            return Err(ReadError::EndOfFile("event"));
+
+    #[test]
+    fn from_read() {
+        let file = File::open("test_data/HEJFOG.lhe.gz").unwrap();
+        let input = GzDecoder::new(BufReader::new(file));
+        let mut reader = Reader::from_read(input).unwrap();
+        assert_eq!(reader.version(), "3.0");
+        let mut nevents = 0;
+        while reader.hepeup().unwrap().is_some() {
+            nevents += 1;
         }
-        if info.lines().last().unwrap().trim() == EVENT_END {
-            pop_line(&mut info);
-            break;
+        assert_eq!(nevents, 10);
+    }
+
+    #[test]
+    fn read_long_line() {
+        let mut text = String::from(
+            "<LesHouchesEvents version=\"1.0\">
+<init>
+2212 2212 7000 7000 0 0 0 0 3 0
+</init>
+<event>
+0 1 1 91.188 0.0078125 0.118
+",
+        );
+        let info_start = text.len();
+        text += &"x".repeat(10_000);
+        text += "\n</event>\n</LesHouchesEvents>\n";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert!(lhef.hepeup().unwrap().is_some());
+
+        for preserve_raw in [false, true] {
+            let mut input = Cursor::new(text.as_bytes());
+            let mut lhef = ReaderBuilder::new()
+                .max_line_bytes(1000)
+                .preserve_raw(preserve_raw)
+                .build(&mut input)
+                .unwrap();
+            let err = lhef.hepeup().unwrap_err();
+            assert!(matches!(err, ReadError::LineTooLong(1000)));
+            // the rest of the line has not been read
+            assert!(input.position() <= info_start as u64 + 1001);
         }
+
+        let mut lhef = ReaderBuilder::new()
+            .max_line_bytes(1000)
+            .build(text.as_bytes())
+            .unwrap();
+        assert!(lhef.next_raw_event().is_err());
+
+        // the limit may fall inside a multi-byte character
+        let utf8_text =
+            text.replacen(&"x".repeat(10_000), &"ä".repeat(10_000), 1);
+        let mut lhef = ReaderBuilder::new()
+            .max_line_bytes(1001)
+            .build(utf8_text.as_bytes())
+            .unwrap();
+        let err = lhef.hepeup().unwrap_err();
+        assert!(matches!(err, ReadError::LineTooLong(1001)));
+
+        let text = text.replacen("<init>", &"x".repeat(2000), 1);
+        let err = ReaderBuilder::new()
+            .max_line_bytes(1000)
+            .build(text.as_bytes())
+            .err()
+            .unwrap();
+        assert!(matches!(err, ReadError::LineTooLong(1000)));
     }
-    let attr = extract_xml_attr(event_open)?;
-    Ok(HEPEUP {
-        NUP,
-        IDRUP,
-        XWGTUP,
-        SCALUP,
-        AQEDUP,
-        AQCDUP,
-        IDUP,
-        ISTUP,
-        MOTHUP,
-        ICOLUP,
-        PUP,
-        VTIMUP,
-        SPINUP,
-        info,
-        attr,
-    })
-}
 
-#[derive(Error, Debug)]
-pub enum ReadError {
-    #[error("First line '{0}' in input does start with '{}'", LHEF_TAG_OPEN)]
-    BadFirstLine(String),
-    #[error(
-        "Encountered unrecognized line '{0}', \
-         expected a header starting with '{}', '{}', \
-         or the init block starting with '{}'",
-        COMMENT_START,
-        HEADER_START,
-        INIT_START
-    )]
-    BadHeaderStart(String),
-    #[error("Encountered malformed xml tag: '{0}'")]
-    BadXmlTag(String),
-    #[error(
-        "Encountered unrecognized line '{0}', \
-         expected an event starting with '{}'",
-        EVENT_START
-    )]
-    BadEventStart(String),
-    #[error("Missing entry '{0}'")]
-    MissingEntry(String),
-    #[error("Failed to convert to number: '{0}'")]
-    ConversionError(String),
-    #[error(
-        "Unsupported version '{0}': only '1.0', '2.0', '3.0' are supported"
-    )]
-    UnsupportedVersion(String),
-    #[error("Version information missing")]
-    MissingVersion,
-    #[error("Encountered '{0}' block without closing tag")]
-    EndOfFile(&'static str),
-    #[error("Read error: {0}")]
-    ReadErr(#[from] std::io::Error),
-    #[error("xml parse error: {0}")]
-    XmlErr(#[from] xmltree::ParseError),
-}
+    #[test]
+    fn read_progress() {
+        use std::sync::{Arc, Mutex};
 
-#[cfg(test)]
-mod reader_tests {
-    extern crate flate2;
-    use super::*;
+        let file = File::open("test_data/HEJFOG.lhe.gz").unwrap();
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let mut lhef = Reader::new(reader).unwrap();
+        let positions = Arc::new(Mutex::new(Vec::new()));
+        let cb_positions = positions.clone();
+        lhef.set_progress(move |pos| cb_positions.lock().unwrap().push(pos));
+        let start = lhef.position();
+        while lhef.hepeup().unwrap().is_some() {
+            assert_eq!(
+                *positions.lock().unwrap().last().unwrap(),
+                lhef.position()
+            );
+        }
+        let positions = positions.lock().unwrap();
+        assert_eq!(positions.len(), 10);
+        assert!(positions[0] > start);
+        assert!(positions.windows(2).all(|p| p[0] < p[1]));
+    }
 
-    use reader_tests::flate2::bufread::GzDecoder;
-    use std::fs::File;
-    use std::io::BufReader;
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn read_throughput() {
+        let file = File::open("test_data/HEJFOG.lhe.gz").unwrap();
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let mut lhef = Reader::new(reader).unwrap();
+        let start = lhef.throughput();
+        assert_eq!(start.events, 0);
+        assert_eq!(start.bytes, lhef.position());
+        lhef.hepeup().unwrap();
+        lhef.next_raw_event().unwrap();
+        let throughput = lhef.throughput();
+        assert_eq!(throughput.events, 2);
+        assert_eq!(throughput.bytes, lhef.position());
+        assert!(throughput.bytes > start.bytes);
+        assert!(throughput.elapsed >= start.elapsed);
+        lhef.statistics().unwrap();
+        assert_eq!(lhef.throughput().events, 10);
+    }
 
     #[test]
     fn read_correct() {
@@ -504,12 +2998,20 @@ mod reader_tests {
         let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
         let mut lhef = Reader::new(reader).unwrap();
         assert_eq!(lhef.version(), "3.0");
+        #[cfg(feature = "xml")]
         {
             let header = lhef.xml_header().as_ref().unwrap();
             let mg_version_entry = &header.children[0];
             assert_eq!(mg_version_entry.name, "MGVersion");
             assert_eq!(mg_version_entry.text.as_ref().unwrap(), "\n#5.2.3.3\n");
         }
+        #[cfg(not(feature = "xml"))]
+        {
+            let header = lhef.xml_header().as_ref().unwrap();
+            assert!(header.starts_with("<header>\n"));
+            assert!(header.contains("<MGVersion>"));
+            assert!(header.ends_with("</header>\n"));
+        }
         assert!(lhef.heprup().attr.is_empty());
         let mut nevents = 0;
         while let Ok(Some(_)) = lhef.hepeup() {
@@ -542,4 +3044,813 @@ mod reader_tests {
         }
         assert_eq!(nevents, 10);
     }
+
+    #[test]
+    fn filter_events() {
+        let file =
+            File::open("test_data/HEJFOG.lhe.gz").expect("file not found");
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let lhef = Reader::new(reader).unwrap();
+        let events: Vec<_> = lhef
+            .filter_events(|event| event.SCALUP > 100.)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| event.SCALUP > 100.));
+
+        let text = "<LesHouchesEvents version=\"1.0\">
+<init>
+2212 2212 7000 7000 0 0 230000 230000 2 1
+1 0.1 1 1
+</init>
+<event>
+0 1 1 91.188 0.0078125 0.118
+</event>
+<event>
+0 2 1 91.188 0.0078125 0.118
+</event>
+<event>
+0 1 1 91.188 0.0078125
+</event>
+</LesHouchesEvents>
+";
+        let lhef = Reader::new(text.as_bytes()).unwrap();
+        let mut events = lhef.filter_events(|event| event.IDRUP == 1);
+        assert!(events.next().unwrap().is_ok());
+        assert!(events.next().unwrap().is_err());
+        assert!(events.next().is_none());
+    }
+
+    #[test]
+    fn read_lossy() {
+        let text = "<LesHouchesEvents version=\"1.0\">
+<init>
+2212 2212 7000 7000 0 0 230000 230000 2 1
+1 0.1 1 1
+</init>
+<event>
+1 1 1 91.188 0.0078125 0.118
+21 1 0 0 501 502 0 0 10 10 0 0 9
+</event>
+<event>
+2 2 1 91.188 0.0078125 0.118
+21 1 0 0 501 502 0 0 10 10 0 0 9
+21 1 0 0 501 502 0 0 -10 zero 0 0 9
+</event>
+garbage
+<event>
+1 3 1 91.188 0.0078125 0.118
+21 1 0 0 501 502 0 0 10 10 0 0 9
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        let events: Vec<_> = lhef.events_lossy().collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].as_ref().unwrap().IDRUP, 1);
+        assert!(events[1].is_err());
+        assert_eq!(events[2].as_ref().unwrap().IDRUP, 3);
+    }
+
+    #[test]
+    fn read_truncated() {
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let mut text = String::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_string(&mut text)
+            .unwrap();
+
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        let mut events = lhef.events_lossy();
+        assert_eq!(events.by_ref().filter(|ev| ev.is_ok()).count(), 1628);
+        assert!(!events.truncated());
+
+        let (event_1001, _) =
+            text.match_indices(EVENT_START).nth(1000).unwrap();
+        for end in [event_1001, event_1001 + 100] {
+            let mut lhef = Reader::new(&text.as_bytes()[..end]).unwrap();
+            let mut events = lhef.events_lossy();
+            let mut nevents = 0;
+            let mut last = None;
+            for event in events.by_ref() {
+                if event.is_ok() {
+                    nevents += 1;
+                }
+                last = Some(event);
+            }
+            assert_eq!(nevents, 1000);
+            assert!(events.truncated());
+            assert!(matches!(last, Some(Err(_))));
+        }
+    }
+
+    #[test]
+    fn read_too_many_entries() {
+        let text = "<LesHouchesEvents version=\"1.0\">
+<init>
+2212 2212 7000 7000 0 0 230000 230000 2 1
+1 0.1 1 1
+</init>
+<event>
+2000000000 1 1 91.188 0.0078125 0.118
+21 -1 0 0 501 502 0 0 10 10 0 0 9
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert!(matches!(
+            lhef.hepeup(),
+            Err(ReadError::TooManyEntries("NUP", 2000000000, 1000000))
+        ));
+
+        let res = ReaderBuilder::new()
+            .max_subprocesses(0)
+            .build(text.as_bytes());
+        assert!(matches!(res, Err(ReadError::TooManyEntries("NPRUP", 1, 0))));
+    }
+
+    #[test]
+    fn read_negative_count() {
+        let text = "<LesHouchesEvents version=\"1.0\">
+<init>
+2212 2212 7000 7000 0 0 230000 230000 2 1
+1 0.1 1 1
+</init>
+<event>
+-1 1 1 91.188 0.0078125 0.118
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert!(matches!(
+            lhef.hepeup(),
+            Err(ReadError::NegativeCount("NUP", -1))
+        ));
+
+        let text = text.replace(" 2 1\n", " 2 -1\n");
+        assert!(matches!(
+            Reader::new(text.as_bytes()),
+            Err(ReadError::NegativeCount("NPRUP", -1))
+        ));
+    }
+
+    #[test]
+    fn read_raw() {
+        let file =
+            File::open("test_data/HEJFOG.lhe.gz").expect("file not found");
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let mut lhef = Reader::new(reader).unwrap();
+        let first = lhef.next_raw_event().unwrap().unwrap();
+        assert!(first.starts_with("<event attr0=\"t0\" attr1=\"\" >\n"));
+        assert!(first.ends_with("<scales mups=\"-1\"/>\n</event>\n"));
+        assert_eq!(first.lines().count(), 12);
+        let mut nevents = 1;
+        while let Some(event) = lhef.next_raw_event().unwrap() {
+            assert!(event.starts_with(EVENT_START));
+            assert!(event.ends_with("</event>\n"));
+            nevents += 1;
+        }
+        assert_eq!(nevents, 10);
+    }
+
+    #[test]
+    fn read_all() {
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let lhef = Reader::new(reader).unwrap();
+        let expected_heprup = lhef.heprup().clone();
+        let (heprup, events) = lhef.read_all().unwrap();
+        assert_eq!(heprup, expected_heprup);
+        assert_eq!(events.len(), 1628);
+
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let mut text = Vec::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_end(&mut text)
+            .unwrap();
+        let mut lhef = Reader::new(Cursor::new(text)).unwrap();
+        let first = lhef.hepeup().unwrap().unwrap();
+        assert_eq!(first, events[0]);
+        let (heprup, reserved) = lhef.read_all_reserved().unwrap();
+        assert_eq!(heprup, expected_heprup);
+        assert_eq!(reserved.capacity(), 1627);
+        assert_eq!(reserved, events[1..]);
+    }
+
+    #[test]
+    fn read_position() {
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let mut text = Vec::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_end(&mut text)
+            .unwrap();
+        let mut lhef = Reader::new(text.as_slice()).unwrap();
+        let mut position = lhef.position();
+        assert!(position > 0);
+        assert!(text[position as usize..].starts_with(b"<event>"));
+        while lhef.hepeup().unwrap().is_some() {
+            assert!(lhef.position() > position);
+            position = lhef.position();
+        }
+        assert_eq!(lhef.position(), text.len() as u64);
+    }
+
+    #[test]
+    fn read_indexed() {
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let mut text = Vec::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_end(&mut text)
+            .unwrap();
+        let mut lhef = Reader::new(text.as_slice()).unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = lhef.hepeup().unwrap() {
+            events.push(event);
+        }
+
+        let stream = BufReader::with_capacity(100, Cursor::new(&text));
+        let mut lhef = IndexedReader::new(stream).unwrap();
+        assert!(matches!(
+            lhef.read_event_at(0),
+            Err(ReadError::NoSuchEvent(0))
+        ));
+        let index = lhef.build_index().unwrap();
+        assert_eq!(index.len(), 1628);
+        assert!(text[index[1000] as usize..].starts_with(b"<event>"));
+        assert_eq!(lhef.read_event_at(1000).unwrap(), events[1000]);
+        assert_eq!(lhef.read_event_at(3).unwrap(), events[3]);
+        assert_eq!(lhef.read_event_at(1627).unwrap(), events[1627]);
+        assert!(matches!(
+            lhef.read_event_at(1628),
+            Err(ReadError::NoSuchEvent(1628))
+        ));
+    }
+
+    #[test]
+    fn events_range() {
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let mut text = Vec::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_end(&mut text)
+            .unwrap();
+        let (_, events) =
+            Reader::new(text.as_slice()).unwrap().read_all().unwrap();
+
+        let mut lhef = Reader::new(text.as_slice()).unwrap();
+        let range: Vec<_> =
+            lhef.events_range(100, 110).map(Result::unwrap).collect();
+        assert_eq!(range.len(), 10);
+        assert_eq!(range[0].IDUP, events[100].IDUP);
+        assert_eq!(range[0].PUP, events[100].PUP);
+        assert_eq!(range[0].XWGTUP, events[100].XWGTUP);
+        assert_eq!(range, events[100..110]);
+        // indices are relative to the current position
+        let next = lhef.events_range(0, 1).next().unwrap().unwrap();
+        assert_eq!(next, events[110]);
+        assert_eq!(lhef.events_range(1, 1).count(), 0);
+        let tail: Vec<_> =
+            lhef.events_range(1500, 2000).map(Result::unwrap).collect();
+        assert_eq!(tail, events[1611..]);
+    }
+
+    #[test]
+    fn read_multi() {
+        let mut text = vec![];
+        let runs = [(6500., 2), (4000., 1)];
+        for (energy, nevents) in runs {
+            let heprup = HEPRUP {
+                EBMUP: [energy, energy],
+                ..HEPRUP::empty()
+            };
+            let events = vec![HEPEUP::empty(); nevents];
+            crate::writer::write_minimal(&mut text, "1.0", &heprup, events)
+                .unwrap();
+            text.push(b'\n');
+        }
+
+        let mut reader = MultiReader::new(text.as_slice());
+        let mut found = vec![];
+        while let Some((heprup, events)) = reader.next_document().unwrap() {
+            let nevents = events.map(Result::unwrap).count();
+            found.push((heprup.EBMUP[0], nevents));
+        }
+        assert_eq!(found, runs);
+        assert!(reader.next_document().unwrap().is_none());
+
+        // unread events are skipped
+        let mut reader = MultiReader::new(text.as_slice());
+        let (_, mut events) = reader.next_document().unwrap().unwrap();
+        assert!(events.next().unwrap().is_ok());
+        let (heprup, events) = reader.next_document().unwrap().unwrap();
+        assert_eq!(heprup.EBMUP[0], 4000.);
+        assert_eq!(events.reader().version(), "1.0");
+        assert!(reader.next_document().unwrap().is_none());
+
+        // the iterator ends after an error in a truncated document
+        let truncated = &text[..text.len() - 30];
+        let mut reader = MultiReader::new(truncated);
+        let (_, events) = reader.next_document().unwrap().unwrap();
+        assert_eq!(events.count(), 2);
+        let (_, events) = reader.next_document().unwrap().unwrap();
+        let events: Vec<_> = events.collect();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], Err(ReadError::EndOfFile(_))));
+        assert!(reader.next_document().unwrap().is_none());
+    }
+
+    #[test]
+    fn read_twice() {
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let mut text = Vec::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_end(&mut text)
+            .unwrap();
+        let stream = BufReader::with_capacity(100, Cursor::new(&text));
+        let mut lhef = Reader::new(stream).unwrap();
+        let events_start = lhef.position();
+        for _ in 0..2 {
+            let mut nevents = 0;
+            while lhef.hepeup().unwrap().is_some() {
+                nevents += 1
+            }
+            assert_eq!(nevents, 1628);
+            lhef.rewind_events().unwrap();
+            assert_eq!(lhef.position(), events_start);
+        }
+    }
+
+    #[test]
+    fn edit_heprup() {
+        let file =
+            File::open("test_data/HEJFOG.lhe.gz").expect("file not found");
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let mut lhef = Reader::new(reader).unwrap();
+        lhef.heprup_mut().XSECUP[0] = 1.5;
+        let mut output = Vec::new();
+        {
+            let mut writer =
+                crate::Writer::new(Cursor::new(&mut output), "3.0").unwrap();
+            writer.heprup(lhef.heprup()).unwrap();
+            writer.finish().unwrap();
+        }
+        let lhef = Reader::new(output.as_slice()).unwrap();
+        assert_eq!(lhef.heprup().XSECUP, [1.5]);
+    }
+
+    #[test]
+    fn read_statistics() {
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let mut text = Vec::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_end(&mut text)
+            .unwrap();
+        let mut lhef = Reader::new(text.as_slice()).unwrap();
+        let mut expected = EventStats::new();
+        while let Some(event) = lhef.hepeup().unwrap() {
+            expected.add(event.XWGTUP);
+        }
+        let mut lhef = Reader::new(text.as_slice()).unwrap();
+        let stats = lhef.statistics().unwrap();
+        assert_eq!(stats, expected);
+        assert_eq!(stats.n_events, 1628);
+        assert!(stats.min_weight > 0.);
+        assert_eq!(stats.n_negative_weights, 0);
+    }
+
+    #[test]
+    fn read_max_weight() {
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let mut text = Vec::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_end(&mut text)
+            .unwrap();
+        let mut lhef = Reader::new(text.as_slice()).unwrap();
+        let mut expected = 0.;
+        while let Some(event) = lhef.hepeup().unwrap() {
+            expected = f64::max(expected, event.XWGTUP.abs());
+        }
+        let mut lhef = Reader::new(text.as_slice()).unwrap();
+        assert_eq!(lhef.max_weight().unwrap(), expected);
+        let mut lhef = Reader::new(text.as_slice()).unwrap();
+        let max = lhef.max_weight_per_process().unwrap();
+        assert_eq!(max.values().copied().fold(0., f64::max), expected);
+
+        let file =
+            File::open("test_data/HEJFOG.lhe.gz").expect("file not found");
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let mut lhef = Reader::new(reader).unwrap();
+        let max = lhef.max_weight_per_process().unwrap();
+        assert_eq!(max, BTreeMap::from([(1, 0.00098642556)]));
+    }
+
+    #[test]
+    fn read_strict() {
+        let file =
+            File::open("test_data/HEJFOG.lhe.gz").expect("file not found");
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let mut lhef = ReaderBuilder::new().strict(true).build(reader).unwrap();
+        let mut nevents = 0;
+        while lhef.hepeup().unwrap().is_some() {
+            nevents += 1
+        }
+        assert_eq!(nevents, 10);
+
+        let text = "<LesHouchesEvents version=\"1.0\">
+<init>
+2212 2212 7000 7000 0 0 230000 230000 2 1
+1 0.1 1 1
+</init>
+<event>
+2 1 1 91.188 0.0078125 0.118
+21 -1 0 0 501 502 0 0 10 10 0 0 9
+21 -1 3 0 502 501 0 0 -10 10 0 0 9
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert!(lhef.hepeup().is_ok());
+        let mut lhef = ReaderBuilder::new()
+            .strict(true)
+            .build(text.as_bytes())
+            .unwrap();
+        assert!(matches!(lhef.hepeup(), Err(ReadError::Invalid(_))));
+    }
+
+    #[test]
+    fn duplicate_attr() {
+        let text = "<LesHouchesEvents version=\"3.0\">
+<init>
+2212 2212 6500 6500 0 0 0 0 3 1
+1 0.1 1 1
+</init>
+<event npLO=\"1\" npLO=\"2\">
+0 1 1 91.188 0.0078125 0.118
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        let event = lhef.hepeup().unwrap().unwrap();
+        assert_eq!(event.attr["npLO"], "2");
+        let mut lhef = ReaderBuilder::new()
+            .strict(true)
+            .build(text.as_bytes())
+            .unwrap();
+        assert!(matches!(lhef.hepeup(), Err(ReadError::BadXmlTag(_))));
+    }
+
+    #[test]
+    fn verify_event_count() {
+        let text = "<LesHouchesEvents version=\"3.0\">
+<init>
+2212 2212 6500 6500 0 0 0 0 3 1
+1 0.1 1 1
+<xsecinfo neve=\"2\" totxsec=\"1\"/>
+</init>
+<event>
+0 1 1 91.188 0.0078125 0.118
+</event>
+<event>
+0 1 1 91.188 0.0078125 0.118
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert!(lhef.verify_event_count().is_ok());
+
+        let text = text.replace("neve=\"2\"", "neve=\"3\"");
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert!(matches!(
+            lhef.verify_event_count(),
+            Err(ReadError::EventCountMismatch(3, 2))
+        ));
+
+        // only the remaining events are counted
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        lhef.hepeup().unwrap();
+        assert!(matches!(
+            lhef.verify_event_count(),
+            Err(ReadError::EventCountMismatch(3, 1))
+        ));
+    }
+
+    #[test]
+    fn reject_nonfinite() {
+        let text = "<LesHouchesEvents version=\"1.0\">
+<init>
+2212 2212 6500 6500 0 0 0 0 3 1
+1 0.1 1 1
+</init>
+<event>
+1 1 1 91.188 0.0078125 0.118
+21 -1 0 0 501 502 0 0 inf 100 0 0 9
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        let event = lhef.hepeup().unwrap().unwrap();
+        assert_eq!(event.PUP[0][2], f64::INFINITY);
+
+        let mut lhef = ReaderBuilder::new()
+            .reject_nonfinite(true)
+            .build(text.as_bytes())
+            .unwrap();
+        match lhef.hepeup() {
+            Err(ReadError::NonFinite(name)) => assert_eq!(name, "PUP(1, 3)"),
+            res => panic!("unexpected result {res:?}"),
+        }
+    }
+
+    #[test]
+    fn stray_lines() {
+        let text = "<LesHouchesEvents version=\"3.0\">
+<init>
+2212 2212 6500 6500 0 0 0 0 3 1
+1 0.1 1 1
+</init>
+
+# Generated by some tool
+<event>
+0 1 1 91.188 0.0078125 0.118
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert_eq!(lhef.hepeup().unwrap().unwrap().IDRUP, 1);
+        assert!(lhef.hepeup().unwrap().is_none());
+
+        let strict = ReaderBuilder::new().strict(true);
+        let mut lhef = strict.build(text.as_bytes()).unwrap();
+        match lhef.hepeup() {
+            Err(ReadError::BadEventStart(line)) => assert_eq!(line, "\n"),
+            res => panic!("unexpected result {res:?}"),
+        }
+
+        let no_blank = text.replace("\n\n", "\n");
+        let mut lhef = strict.build(no_blank.as_bytes()).unwrap();
+        match lhef.hepeup() {
+            Err(ReadError::BadEventStart(line)) => {
+                assert_eq!(line, "# Generated by some tool\n")
+            }
+            res => panic!("unexpected result {res:?}"),
+        }
+
+        // other text is rejected also outside strict mode
+        let stray_row = text.replace("# Generated by some tool", "1 2 3");
+        let mut lhef = Reader::new(stray_row.as_bytes()).unwrap();
+        match lhef.hepeup() {
+            Err(ReadError::BadEventStart(line)) => {
+                assert_eq!(line, "1 2 3\n")
+            }
+            res => panic!("unexpected result {res:?}"),
+        }
+    }
+
+    #[test]
+    fn unknown_tags() {
+        let text = "<LesHouchesEvents version=\"3.0\">
+<init>
+2212 2212 6500 6500 0 0 0 0 3 1
+1 0.1 1 1
+</init>
+<event>
+0 1 1 91.188 0.0078125 0.118
+</event>
+<foo a=\"1\">
+  <bar>text</bar>
+</foo>
+<event>
+0 2 1 91.188 0.0078125 0.118
+</event>
+<baz>text</baz>
+<empty
+/>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert!(lhef.hepeup().unwrap().is_some());
+        assert!(matches!(lhef.hepeup(), Err(ReadError::BadEventStart(_))));
+
+        for policy in [UnknownTagPolicy::Skip, UnknownTagPolicy::Collect] {
+            let mut lhef = ReaderBuilder::new()
+                .on_unknown_tag(policy)
+                .build(text.as_bytes())
+                .unwrap();
+            let mut ids = Vec::new();
+            while let Some(event) = lhef.hepeup().unwrap() {
+                ids.push(event.IDRUP);
+            }
+            assert_eq!(ids, [1, 2]);
+            if policy == UnknownTagPolicy::Collect {
+                assert_eq!(
+                    lhef.unknown_tags(),
+                    [
+                        "<foo a=\"1\">\n  <bar>text</bar>\n</foo>\n",
+                        "<baz>text</baz>\n",
+                        "<empty\n/>\n"
+                    ]
+                );
+            } else {
+                assert!(lhef.unknown_tags().is_empty());
+            }
+        }
+
+        let text = text.replace("</foo>", "");
+        let mut lhef = ReaderBuilder::new()
+            .on_unknown_tag(UnknownTagPolicy::Collect)
+            .build(text.as_bytes())
+            .unwrap();
+        assert!(lhef.hepeup().unwrap().is_some());
+        assert!(matches!(
+            lhef.hepeup(),
+            Err(ReadError::EndOfFile("unknown tag"))
+        ));
+    }
+
+    #[test]
+    fn weighted_events() {
+        let text = "<LesHouchesEvents version=\"3.0\">
+<init>
+2212 2212 6500 6500 0 0 0 0 3 1
+1 0.1 1 1
+<initrwgt>
+<weightgroup name='scale' combine='envelope'>
+<weight id='1001'> muR=2 </weight>
+<weight id='1002'> muR=0.5 </weight>
+</weightgroup>
+<weight id='2001'></weight>
+</initrwgt>
+</init>
+<event>
+0 1 1 91.188 0.0078125 0.118
+<rwgt>
+<wgt id='1001'> 1.5 </wgt>
+<wgt id='1002'> 0.5 </wgt>
+<wgt id='2001'> 2 </wgt>
+</rwgt>
+</event>
+<event>
+0 1 -1 91.188 0.0078125 0.118
+<rwgt>
+<wgt id='1002'> -0.5 </wgt>
+<wgt id='3001'> 3 </wgt>
+</rwgt>
+</event>
+</LesHouchesEvents>
+";
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert_eq!(
+            lhef.heprup().weight_definitions(),
+            [
+                ("1001".to_owned(), "muR=2".to_owned()),
+                ("1002".to_owned(), "muR=0.5".to_owned()),
+                ("2001".to_owned(), "2001".to_owned()),
+            ]
+        );
+        let events = Vec::from_iter(lhef.weighted_events().map(Result::unwrap));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].0.XWGTUP, 1.);
+        assert_eq!(
+            events[0].1,
+            HashMap::from([
+                ("muR=2".to_owned(), 1.5),
+                ("muR=0.5".to_owned(), 0.5),
+                ("2001".to_owned(), 2.),
+            ])
+        );
+        assert_eq!(events[1].0.XWGTUP, -1.);
+        assert_eq!(events[1].1, HashMap::from([("muR=0.5".to_owned(), -0.5)]));
+
+        // definitions in the header
+        let text = text.replace("<init>", "<header>\n</header>\n<init>");
+        let start = text.find("<initrwgt>").unwrap();
+        let end = text.find("</initrwgt>").unwrap() + "</initrwgt>\n".len();
+        let definitions = &text[start..end];
+        let text = text.replacen(definitions, "", 1).replacen(
+            "<header>\n",
+            &format!("<header>\n{definitions}"),
+            1,
+        );
+        let mut lhef = Reader::new(text.as_bytes()).unwrap();
+        assert!(lhef.heprup().weight_definitions().is_empty());
+        let weights =
+            Vec::from_iter(lhef.weighted_events().map(|e| e.unwrap().1));
+        assert_eq!(weights[1], HashMap::from([("muR=0.5".to_owned(), -0.5)]));
+    }
+
+    #[test]
+    fn event_from_str() {
+        let event = HEPEUP {
+            NUP: 2,
+            IDRUP: 3,
+            XWGTUP: -0.25,
+            SCALUP: 91.188,
+            AQEDUP: 0.0078125,
+            AQCDUP: 0.118,
+            IDUP: vec![21, 22],
+            ISTUP: vec![-1, 1],
+            MOTHUP: vec![[0, 0], [1, 1]],
+            ICOLUP: vec![[501, 502], [0, 0]],
+            PUP: vec![[0., 0., 10., 10., 0.], [0., 0., 10., 10., 0.]],
+            VTIMUP: vec![0., 0.],
+            SPINUP: vec![9., -1.],
+            info: "<mgrwt>\n</mgrwt>\n".to_owned(),
+            attr: XmlAttr::from([("npLO".to_owned(), "1".to_owned())]),
+            raw: None,
+        };
+        let mut writer = crate::Writer::new(vec![], "1.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.hepeup(&event).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let start = output.find(EVENT_START).unwrap();
+        let end = output.find(EVENT_END).unwrap() + EVENT_END.len();
+        let text = format!("\n  {}\n\n", &output[start..end]);
+        assert_eq!(HEPEUP::try_from(text.as_str()).unwrap(), event);
+
+        let text = &output[start..end - 1];
+        assert!(matches!(
+            HEPEUP::<f64>::try_from(text),
+            Err(ReadError::EndOfFile("event"))
+        ));
+        let err = HEPEUP::<f64>::try_from("<init>\n</init>").unwrap_err();
+        assert!(matches!(err, ReadError::BadEventStart(_)));
+    }
+
+    #[test]
+    fn skip_info() {
+        let event = HEPEUP {
+            NUP: 1,
+            IDRUP: 1,
+            XWGTUP: 0.5,
+            IDUP: vec![22],
+            ISTUP: vec![1],
+            MOTHUP: vec![[0, 0]],
+            ICOLUP: vec![[0, 0]],
+            PUP: vec![[0., 0., 10., 10., 0.]],
+            VTIMUP: vec![0.],
+            SPINUP: vec![9.],
+            info: "<mgrwt>\n<rscale> 0 0.1E+03 </rscale>\n</mgrwt>\n"
+                .to_owned(),
+            ..HEPEUP::empty()
+        };
+        let mut writer = crate::Writer::new(vec![], "3.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.hepeup(&event).unwrap();
+        writer.hepeup(&event).unwrap();
+        writer.finish().unwrap();
+        let text = writer.into_inner();
+
+        let mut lhef = ReaderBuilder::new()
+            .skip_info(true)
+            .build(text.as_slice())
+            .unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = lhef.hepeup().unwrap() {
+            events.push(event);
+        }
+        let expected = HEPEUP {
+            info: String::new(),
+            ..event
+        };
+        assert_eq!(events, [expected.clone(), expected]);
+
+        let mut writer = crate::Writer::new(vec![], "3.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.write_events(events).unwrap();
+        writer.finish().unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(!output.contains("mgrwt"));
+    }
+
+    #[test]
+    fn init_from_str() {
+        let heprup = HEPRUP {
+            IDBMUP: [2212, -2212],
+            EBMUP: [6500., 6500.],
+            PDFGUP: [0, 0],
+            PDFSUP: [303400, 303400],
+            IDWTUP: -4,
+            NPRUP: 1,
+            XSECUP: vec![12.5],
+            XERRUP: vec![0.25],
+            XMAXUP: vec![3.],
+            LPRUP: vec![7],
+            info: "<generator name='test'>\n</generator>\n".to_owned(),
+            attr: XmlAttr::from([("npLO".to_owned(), "2".to_owned())]),
+        };
+        let mut writer = crate::Writer::new(vec![], "1.0").unwrap();
+        writer.heprup(&heprup).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let start = output.find(INIT_START).unwrap();
+        let end = output.find(INIT_END).unwrap() + INIT_END.len();
+        let text = format!("\t{}\n", &output[start..end]);
+        assert_eq!(HEPRUP::try_from(text.as_str()).unwrap(), heprup);
+
+        let text = &output[start..end - 1];
+        assert!(matches!(
+            HEPRUP::<f64>::try_from(text),
+            Err(ReadError::EndOfFile("init"))
+        ));
+        let err = HEPRUP::<f64>::try_from("<event>\n</event>").unwrap_err();
+        assert!(matches!(err, ReadError::BadInitStart(_)));
+    }
 }
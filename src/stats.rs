@@ -0,0 +1,190 @@
+#[cfg(feature = "metrics")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Summary statistics for event weights
+///
+/// # Example
+///
+/// ```rust
+/// let mut stats = lhef::stats::EventStats::new();
+/// for weight in [1.5, -0.5, 2.] {
+///     stats.add(weight);
+/// }
+/// assert_eq!(stats.n_events, 3);
+/// assert_eq!(stats.sum_weights, 3.);
+/// assert_eq!(stats.n_negative_weights, 1);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventStats {
+    /// Number of events
+    pub n_events: u64,
+    /// Sum of event weights
+    pub sum_weights: f64,
+    /// Sum of squared event weights
+    pub sum_weights_sq: f64,
+    /// Smallest event weight, infinity if there are no events
+    pub min_weight: f64,
+    /// Largest event weight, negative infinity if there are no events
+    pub max_weight: f64,
+    /// Number of events with negative weight
+    pub n_negative_weights: u64,
+}
+
+impl EventStats {
+    /// Create statistics without any events
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an event with the given weight
+    pub fn add(&mut self, weight: f64) {
+        self.n_events += 1;
+        self.sum_weights += weight;
+        self.sum_weights_sq += weight * weight;
+        self.min_weight = self.min_weight.min(weight);
+        self.max_weight = self.max_weight.max(weight);
+        if weight < 0. {
+            self.n_negative_weights += 1;
+        }
+    }
+
+    /// Fraction of events with negative weight
+    ///
+    /// This is the number of events with negative weight divided by the
+    /// total number of events. Returns NaN if there are no events.
+    pub fn negative_fraction(&self) -> f64 {
+        self.n_negative_weights as f64 / self.n_events as f64
+    }
+
+    /// Effective number of events
+    ///
+    /// This is the number of unweighted events with the same relative
+    /// statistical uncertainty of the sum of weights, computed as
+    ///
+    /// N_eff = (∑ w)^2 / ∑ w^2 ,
+    ///
+    /// where the sums are over all event weights w. For unweighted
+    /// samples with positive weights, this is the number of events.
+    /// Negative weights reduce the effective number of events. Returns
+    /// NaN if there are no events.
+    pub fn effective_events(&self) -> f64 {
+        self.sum_weights * self.sum_weights / self.sum_weights_sq
+    }
+}
+
+impl Default for EventStats {
+    fn default() -> Self {
+        Self {
+            n_events: 0,
+            sum_weights: 0.,
+            sum_weights_sq: 0.,
+            min_weight: f64::INFINITY,
+            max_weight: f64::NEG_INFINITY,
+            n_negative_weights: 0,
+        }
+    }
+}
+
+/// Amount of processed data since the creation of a reader or writer
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let file = std::fs::File::open("events.lhe").unwrap();
+/// let file = std::io::BufReader::new(file);
+/// let mut reader = lhef::Reader::new(file).unwrap();
+///
+/// while let Some(event) = reader.hepeup().unwrap() {
+///     // ...
+/// }
+/// let throughput = reader.throughput();
+/// println!("Read {} events per second", throughput.events_per_sec());
+/// ```
+#[cfg(feature = "metrics")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throughput {
+    /// Number of events
+    pub events: u64,
+    /// Number of bytes
+    pub bytes: u64,
+    /// Elapsed time
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "metrics")]
+impl Throughput {
+    /// Average number of events per second
+    pub fn events_per_sec(&self) -> f64 {
+        self.events as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Average number of bytes per second
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+// Counts events and measures the time since creation
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ThroughputMeter {
+    start: Instant,
+    events: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl ThroughputMeter {
+    pub(crate) fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            events: 0,
+        }
+    }
+
+    pub(crate) fn add_event(&mut self) {
+        self.events += 1;
+    }
+
+    pub(crate) fn throughput(&self, bytes: u64) -> Throughput {
+        Throughput {
+            events: self.events,
+            bytes,
+            elapsed: self.start.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::*;
+
+    #[test]
+    fn mixed_weights() {
+        let mut stats = EventStats::new();
+        for weight in [2., -1., 2., 1.] {
+            stats.add(weight);
+        }
+        assert_eq!(stats.n_events, 4);
+        assert_eq!(stats.sum_weights, 4.);
+        assert_eq!(stats.sum_weights_sq, 10.);
+        assert_eq!(stats.min_weight, -1.);
+        assert_eq!(stats.max_weight, 2.);
+        assert_eq!(stats.negative_fraction(), 0.25);
+        assert_eq!(stats.effective_events(), 1.6);
+    }
+
+    #[test]
+    fn unweighted() {
+        let mut stats = EventStats::new();
+        for _ in 0..10 {
+            stats.add(0.5);
+        }
+        assert_eq!(stats.negative_fraction(), 0.);
+        assert_eq!(stats.effective_events(), 10.);
+    }
+}
@@ -10,3 +10,52 @@ pub const INTERMEDIATE_RESONANCE: i32 = 2;
 pub const INTERMEDIATE_DOC: i32 = 3;
 /// Incoming beam particles at time t = −∞
 pub const INCOMING_BEAM: i32 = -9;
+
+/// Convert a particle status to the HepMC3 convention
+///
+/// The status codes are mapped as follows:
+///
+/// | LHEF status                   | HepMC3 status              |
+/// |-------------------------------|----------------------------|
+/// | [INCOMING_BEAM] (-9)          | 4 (incoming beam particle) |
+/// | [INCOMING] (-1)               | 4 (incoming beam particle) |
+/// | [OUTGOING] (1)                | 1 (undecayed final state)  |
+/// | [INTERMEDIATE_RESONANCE] (2)  | 2 (decayed particle)       |
+/// | [INTERMEDIATE_SPACELIKE] (-2) | 3 (documentation line)     |
+/// | [INTERMEDIATE_DOC] (3)        | 3 (documentation line)     |
+/// | anything else                 | 0 (not defined)            |
+///
+/// # Example
+///
+/// ```rust
+/// use lhef::status::{to_hepmc, OUTGOING};
+///
+/// assert_eq!(to_hepmc(OUTGOING), 1);
+/// ```
+pub fn to_hepmc(istup: i32) -> i32 {
+    match istup {
+        INCOMING_BEAM | INCOMING => 4,
+        OUTGOING => 1,
+        INTERMEDIATE_RESONANCE => 2,
+        INTERMEDIATE_SPACELIKE | INTERMEDIATE_DOC => 3,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod status_tests {
+    use super::*;
+
+    #[test]
+    fn hepmc_status() {
+        assert_eq!(to_hepmc(INCOMING_BEAM), 4);
+        assert_eq!(to_hepmc(INCOMING), 4);
+        assert_eq!(to_hepmc(OUTGOING), 1);
+        assert_eq!(to_hepmc(INTERMEDIATE_RESONANCE), 2);
+        assert_eq!(to_hepmc(INTERMEDIATE_SPACELIKE), 3);
+        assert_eq!(to_hepmc(INTERMEDIATE_DOC), 3);
+        assert_eq!(to_hepmc(0), 0);
+        assert_eq!(to_hepmc(-3), 0);
+        assert_eq!(to_hepmc(4), 0);
+    }
+}
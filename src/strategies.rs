@@ -0,0 +1,157 @@
+use std::ops::RangeInclusive;
+
+use crate::data::*;
+
+use proptest::collection::{vec, SizeRange};
+use proptest::prelude::*;
+
+// Largest absolute value of generated momentum components and energies
+const MAX_MOMENTUM: f64 = 1e4;
+
+/// Strategy for generating run information
+///
+/// The number of subprocesses `NPRUP` is taken from `n_subprocesses` and
+/// the lengths of `XSECUP`, `XERRUP`, `XMAXUP`, and `LPRUP` agree with
+/// it. `info` and `attr` are always empty.
+///
+/// # Example
+///
+/// ```rust
+/// use proptest::prelude::*;
+/// use lhef::strategies::heprup_strategy;
+///
+/// proptest! {
+///     fn positive_cross_sections(heprup in heprup_strategy(1..=5)) {
+///         prop_assert!(heprup.XSECUP.iter().all(|&xs| xs >= 0.));
+///     }
+/// }
+/// ```
+#[allow(non_snake_case)]
+pub fn heprup_strategy(
+    n_subprocesses: impl Into<SizeRange>,
+) -> impl Strategy<Value = HEPRUP> {
+    let beam = prop::sample::select(vec![2212, -2212, 11, -11]);
+    let subprocess = (0. ..1e6, 0. ..1e3, 0. ..1e6, 1..=100i32);
+    (
+        prop::array::uniform2(beam),
+        prop::array::uniform2(0. ..MAX_MOMENTUM),
+        prop::array::uniform2(0..1000i32),
+        prop::array::uniform2(0..1_000_000i32),
+        prop::sample::select(vec![-4, -3, -2, -1, 1, 2, 3, 4]),
+        vec(subprocess, n_subprocesses),
+    )
+        .prop_map(
+            |(IDBMUP, EBMUP, PDFGUP, PDFSUP, IDWTUP, subprocesses)| {
+                let mut heprup = HEPRUP {
+                    IDBMUP,
+                    EBMUP,
+                    PDFGUP,
+                    PDFSUP,
+                    IDWTUP,
+                    NPRUP: subprocesses.len() as i32,
+                    ..HEPRUP::empty()
+                };
+                for (xs, err, max, id) in subprocesses {
+                    heprup.XSECUP.push(xs);
+                    heprup.XERRUP.push(err);
+                    heprup.XMAXUP.push(max);
+                    heprup.LPRUP.push(id);
+                }
+                heprup
+            },
+        )
+}
+
+/// Strategy for generating events
+///
+/// The number of particles `NUP` is taken from `n_particles` and the
+/// lengths of the particle properties agree with it. The absolute value
+/// of the event weight `XWGTUP` is at most `max_weight`. The mothers of
+/// each particle are valid indices into the event or zero. `info` and
+/// `attr` are always empty, and `raw` is `None`.
+///
+/// # Example
+///
+/// ```rust
+/// use proptest::prelude::*;
+/// use lhef::strategies::hepeup_strategy;
+///
+/// proptest! {
+///     fn bounded_weights(event in hepeup_strategy(2..10, 1.)) {
+///         prop_assert!(event.XWGTUP.abs() <= 1.);
+///     }
+/// }
+/// ```
+#[allow(non_snake_case)]
+pub fn hepeup_strategy(
+    n_particles: impl Into<SizeRange>,
+    max_weight: f64,
+) -> impl Strategy<Value = HEPEUP> {
+    let weights: RangeInclusive<f64> = -max_weight..=max_weight;
+    let colour = prop::sample::select(vec![0, 501, 502, 503]);
+    let particle = (
+        -25..=25i32,
+        prop::sample::select(vec![-1, 1, 2, 3]),
+        prop::array::uniform2(any::<u16>()),
+        prop::array::uniform2(colour),
+        prop::array::uniform4(-MAX_MOMENTUM..MAX_MOMENTUM),
+        0. ..100.,
+        0. ..1.,
+        prop::sample::select(vec![-1., 0., 1., 9.]),
+    );
+    (
+        1..=100i32,
+        weights,
+        0. ..MAX_MOMENTUM,
+        0. ..1.,
+        0. ..1.,
+        vec(particle, n_particles),
+    )
+        .prop_map(|(IDRUP, XWGTUP, SCALUP, AQEDUP, AQCDUP, particles)| {
+            let nup = particles.len();
+            let mut event = HEPEUP {
+                NUP: nup as i32,
+                IDRUP,
+                XWGTUP,
+                SCALUP,
+                AQEDUP,
+                AQCDUP,
+                ..HEPEUP::empty()
+            };
+            for (id, status, mothers, colour, p, m, vtim, spin) in particles {
+                event.IDUP.push(id);
+                event.ISTUP.push(status);
+                let mothers = mothers.map(|m| m as i32 % (nup as i32 + 1));
+                event.MOTHUP.push(mothers);
+                event.ICOLUP.push(colour);
+                event.PUP.push([p[0], p[1], p[2], p[3], m]);
+                event.VTIMUP.push(vtim);
+                event.SPINUP.push(spin);
+            }
+            event
+        })
+}
+
+#[cfg(test)]
+mod strategies_tests {
+    use super::*;
+    use crate::writer::Writer;
+
+    proptest! {
+        #[test]
+        fn writable(
+            heprup in heprup_strategy(0..4),
+            events in vec(hepeup_strategy(0..8, 10.), 0..4),
+        ) {
+            prop_assert_eq!(heprup.NPRUP as usize, heprup.LPRUP.len());
+            let mut writer = Writer::new(vec![], "3.0").unwrap();
+            prop_assert!(writer.heprup(&heprup).is_ok());
+            for event in &events {
+                prop_assert_eq!(event.NUP as usize, event.PUP.len());
+                prop_assert!(event.XWGTUP.abs() <= 10.);
+                prop_assert!(writer.hepeup(event).is_ok());
+            }
+            prop_assert!(writer.finish().is_ok());
+        }
+    }
+}
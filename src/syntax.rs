@@ -1,3 +1,7 @@
+pub(crate) const BOM: char = '\u{feff}';
+pub(crate) const XML_DECL_START: &str = "<?xml";
+pub(crate) const XML_DECL_END: &str = "?>";
+pub(crate) const LHEF_TAG_START: &str = "<LesHouchesEvents";
 pub(crate) const LHEF_TAG_OPEN: &str = "<LesHouchesEvents version=";
 pub(crate) const COMMENT_START: &str = "<!--";
 pub(crate) const COMMENT_END: &str = "-->";
@@ -8,3 +12,16 @@ pub(crate) const INIT_END: &str = "</init>";
 pub(crate) const EVENT_START: &str = "<event";
 pub(crate) const EVENT_END: &str = "</event>";
 pub(crate) const LHEF_LAST_LINE: &str = "</LesHouchesEvents>";
+pub(crate) const RWGT_START: &str = "<rwgt";
+pub(crate) const RWGT_END: &str = "</rwgt>";
+pub(crate) const WGT_START: &str = "<wgt";
+pub(crate) const WGT_END: &str = "</wgt>";
+pub(crate) const SCALES_START: &str = "<scales";
+pub(crate) const INITRWGT_START: &str = "<initrwgt";
+pub(crate) const INITRWGT_END: &str = "</initrwgt>";
+pub(crate) const WEIGHT_START: &str = "<weight";
+pub(crate) const WEIGHT_END: &str = "</weight>";
+pub(crate) const WEIGHTGROUP_START: &str = "<weightgroup";
+pub(crate) const WEIGHTGROUP_END: &str = "</weightgroup>";
+pub(crate) const XSECINFO_START: &str = "<xsecinfo";
+pub(crate) const XSECINFO_END: &str = "</xsecinfo>";
@@ -0,0 +1,172 @@
+use std::io::BufRead;
+
+use crate::data::*;
+use crate::reader::{ReadError, Reader};
+use crate::tools::write_headers;
+use crate::writer::{WriteError, Writer};
+
+use thiserror::Error;
+
+/// Check that a LHEF file is unchanged after writing and reading it
+///
+/// All data are read from `reader` and written to an in-memory buffer,
+/// which is then read again. The version, headers, run information,
+/// and events of the original and the re-read data are compared. On
+/// success, the number of events is returned. The first difference is
+/// reported as a [Mismatch] error.
+///
+/// Since all events are kept in memory, this is intended for test
+/// files of moderate size.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let file = std::fs::File::open("events.lhe").unwrap();
+/// let file = std::io::BufReader::new(file);
+/// let reader = lhef::Reader::new(file).unwrap();
+///
+/// let nevents = lhef::testing::assert_round_trip(reader).unwrap();
+/// println!("{nevents} events survived the round trip");
+/// ```
+pub fn assert_round_trip<R: BufRead>(
+    mut reader: Reader<R>,
+) -> Result<usize, RoundTripError> {
+    let mut writer = Writer::new(Vec::new(), reader.version())?;
+    write_headers(&reader, &mut writer)?;
+    writer.heprup(reader.heprup())?;
+    let mut events = Vec::new();
+    while let Some(event) = reader.hepeup()? {
+        writer.hepeup(&event)?;
+        events.push(event);
+    }
+    writer.finish()?;
+    let output = writer.into_inner();
+
+    let mut round_trip = Reader::new(output.as_slice())?;
+    if round_trip.version() != reader.version() {
+        return Err(Mismatch::Version {
+            original: reader.version().to_owned(),
+            round_trip: round_trip.version().to_owned(),
+        }
+        .into());
+    }
+    if round_trip.header() != reader.header() {
+        return Err(Mismatch::Header {
+            original: reader.header().to_owned(),
+            round_trip: round_trip.header().to_owned(),
+        }
+        .into());
+    }
+    if round_trip.xml_header() != reader.xml_header() {
+        return Err(Mismatch::XmlHeader {
+            original: reader.xml_header().clone().map(Box::new),
+            round_trip: round_trip.xml_header().clone().map(Box::new),
+        }
+        .into());
+    }
+    if round_trip.heprup() != reader.heprup() {
+        return Err(Mismatch::Heprup {
+            original: Box::new(reader.heprup().clone()),
+            round_trip: Box::new(round_trip.heprup().clone()),
+        }
+        .into());
+    }
+    let nevents = events.len();
+    let mut events = events.into_iter();
+    for index in 0.. {
+        let original = events.next();
+        let round_trip = round_trip.hepeup()?;
+        if original != round_trip {
+            return Err(Mismatch::Event {
+                index,
+                original: original.map(Box::new),
+                round_trip: round_trip.map(Box::new),
+            }
+            .into());
+        }
+        if original.is_none() {
+            break;
+        }
+    }
+    Ok(nevents)
+}
+
+/// Difference between the original data and the data after a round trip
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    #[error("Version changed from {original} to {round_trip}")]
+    Version {
+        original: String,
+        round_trip: String,
+    },
+    #[error("Header changed from\n{original}\nto\n{round_trip}")]
+    Header {
+        original: String,
+        round_trip: String,
+    },
+    #[error("XML header changed from\n{original:?}\nto\n{round_trip:?}")]
+    XmlHeader {
+        original: Option<Box<XmlHeader>>,
+        round_trip: Option<Box<XmlHeader>>,
+    },
+    #[error("Run information changed from\n{original:?}\nto\n{round_trip:?}")]
+    Heprup {
+        original: Box<HEPRUP>,
+        round_trip: Box<HEPRUP>,
+    },
+    /// A missing event is represented by `None`
+    #[error("Event {index} changed from\n{original:?}\nto\n{round_trip:?}")]
+    Event {
+        index: usize,
+        original: Option<Box<HEPEUP>>,
+        round_trip: Option<Box<HEPEUP>>,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum RoundTripError {
+    #[error("Failed to read: {0}")]
+    ReadErr(#[from] ReadError),
+    #[error("Failed to write: {0}")]
+    WriteErr(#[from] WriteError),
+    #[error("Round trip changed the data: {0}")]
+    Mismatch(#[from] Mismatch),
+}
+
+#[cfg(test)]
+mod testing_tests {
+    extern crate flate2;
+    use super::*;
+
+    use std::fs::File;
+    use std::io::BufReader;
+    use testing_tests::flate2::bufread::GzDecoder;
+
+    #[test]
+    fn round_trip_2j() {
+        let file = File::open("test_data/2j.lhe.gz").expect("file not found");
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let reader = Reader::new(reader).unwrap();
+        assert_eq!(assert_round_trip(reader).unwrap(), 1628);
+    }
+
+    #[test]
+    fn mismatch() {
+        // NaN weights are never equal
+        let input = "<LesHouchesEvents version=\"1.0\">
+<init>
+ 1 1 1 1 0 0 0 0 1 0
+</init>
+<event>
+0 1 NaN 1 1 1
+</event>
+</LesHouchesEvents>
+";
+        let reader = Reader::new(input.as_bytes()).unwrap();
+        let err = assert_round_trip(reader).unwrap_err();
+        assert!(matches!(
+            err,
+            RoundTripError::Mismatch(Mismatch::Event { index: 0, .. })
+        ));
+    }
+}
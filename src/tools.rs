@@ -0,0 +1,1931 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+#[cfg(feature = "external-sort")]
+use std::fs::File;
+#[cfg(any(feature = "bincode", feature = "external-sort"))]
+use std::io::BufReader;
+#[cfg(feature = "bincode")]
+use std::io::Read;
+use std::io::{self, BufRead, BufWriter, Seek, Write};
+use std::sync::mpsc::{channel, Sender};
+#[cfg(feature = "arrow")]
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::data::*;
+use crate::info::find_weight_group;
+use crate::kinematics::FourVector;
+use crate::reader::{ReadError, Reader};
+use crate::stats::EventStats;
+use crate::status::{INCOMING, OUTGOING};
+use crate::writer::{WriteError, Writer};
+
+#[cfg(feature = "bincode")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Concatenate the events from several inputs into a single output
+///
+/// The headers are taken from the first input. The run information is
+/// obtained by merging the run information of all inputs with
+/// [HEPRUP::merge].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let inputs = ["run1.lhe", "run2.lhe"].into_iter().map(|name| {
+///     let file = BufReader::new(File::open(name).unwrap());
+///     lhef::Reader::new(file).unwrap()
+/// }).collect();
+/// let out = File::create("combined.lhe").unwrap();
+/// lhef::tools::concat(out, inputs, "1.0").unwrap();
+/// ```
+pub fn concat<W: Write, R: BufRead>(
+    out: W,
+    inputs: Vec<Reader<R>>,
+    version: &str,
+) -> Result<(), ToolError> {
+    let Some((first, rest)) = inputs.split_first() else {
+        return Err(ToolError::NoInput);
+    };
+    let mut heprup = first.heprup().clone();
+    for input in rest {
+        heprup = heprup.merge(input.heprup())?;
+    }
+    let mut writer = Writer::new(out, version)?;
+    write_headers(first, &mut writer)?;
+    writer.heprup(&heprup)?;
+    for mut input in inputs {
+        copy_events(&mut input, &mut writer)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Split the events from a reader into chunks
+///
+/// For each chunk, a new writer is obtained by calling `make_writer`
+/// with the index of the chunk, starting from zero. The headers and run
+/// information of `reader` are written to each chunk, followed by (at
+/// most) `events_per_chunk` events. Returns the number of chunks.
+///
+/// # Panics
+///
+/// Panics if `events_per_chunk` is zero.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let version = reader.version().to_owned();
+/// let nchunks = lhef::tools::split(
+///     &mut reader,
+///     |n| {
+///         let out = File::create(format!("events_{n}.lhe")).unwrap();
+///         lhef::Writer::new(out, &version).unwrap()
+///     },
+///     10000,
+/// ).unwrap();
+/// ```
+pub fn split<R, W, F>(
+    reader: &mut Reader<R>,
+    mut make_writer: F,
+    events_per_chunk: usize,
+) -> Result<usize, ToolError>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(usize) -> Writer<W>,
+{
+    assert!(events_per_chunk > 0);
+    let mut nchunks = 0;
+    let mut next = reader.hepeup()?;
+    while let Some(event) = next {
+        let mut writer = make_writer(nchunks);
+        write_headers(reader, &mut writer)?;
+        writer.heprup(reader.heprup())?;
+        writer.hepeup(&event)?;
+        let mut nevents = 1;
+        next = reader.hepeup()?;
+        while let Some(event) = &next {
+            if nevents == events_per_chunk {
+                break;
+            }
+            writer.hepeup(event)?;
+            nevents += 1;
+            next = reader.hepeup()?;
+        }
+        writer.finish()?;
+        nchunks += 1;
+    }
+    Ok(nchunks)
+}
+
+/// Select a weighted subsample of events
+///
+/// The headers and run information of `reader` are copied to `writer`,
+/// followed by at most `target_n` events selected with priority
+/// sampling (Duffield, Lund, Thorup, [J. ACM 54 (2007)
+/// 32](https://doi.org/10.1145/1314690.1314696)). The weights of the
+/// selected events are adjusted such that the sum of weights is
+/// preserved in expectation. Finally, the output is finished.
+///
+/// `rng` has to return uniformly distributed random numbers in the
+/// interval [0, 1).
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let out = File::create("subsample.lhe").unwrap();
+/// let mut writer = lhef::Writer::new(out, reader.version()).unwrap();
+///
+/// // use a proper random number generator in practice
+/// let mut seed = 42u32;
+/// let rng = || {
+///     seed ^= seed << 13;
+///     seed ^= seed >> 17;
+///     seed ^= seed << 5;
+///     seed as f64 / (u32::MAX as f64 + 1.)
+/// };
+/// lhef::tools::subsample(&mut reader, &mut writer, 1000, rng).unwrap();
+/// ```
+pub fn subsample<R, W, F>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+    target_n: usize,
+    mut rng: F,
+) -> Result<(), ToolError>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut() -> f64,
+{
+    write_headers(reader, writer)?;
+    writer.heprup(reader.heprup())?;
+    let mut candidates = BinaryHeap::with_capacity(target_n + 1);
+    let mut index = 0;
+    while let Some(event) = reader.hepeup()? {
+        let weight = event.XWGTUP.abs();
+        if weight > 0. {
+            let priority = weight / (1. - rng());
+            candidates.push(Reverse(Candidate {
+                priority,
+                index,
+                event,
+            }));
+            if candidates.len() > target_n + 1 {
+                candidates.pop();
+            }
+        }
+        index += 1;
+    }
+    let mut threshold = 0.;
+    if candidates.len() > target_n {
+        threshold = candidates.pop().unwrap().0.priority;
+    }
+    let mut selected = Vec::from_iter(candidates.into_iter().map(|c| c.0));
+    selected.sort_unstable_by_key(|c| c.index);
+    for Candidate { mut event, .. } in selected {
+        let weight = event.XWGTUP.abs().max(threshold);
+        event.XWGTUP = weight.copysign(event.XWGTUP);
+        event.raw = None;
+        writer.hepeup(&event)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Unweight events with hit-or-miss sampling
+///
+/// The headers and run information of `reader` are copied to `writer`,
+/// where the weighting strategy `IDWTUP` is changed to ±3. Each event
+/// is then accepted with a probability given by the ratio of its
+/// absolute weight and `max_weight`. Accepted events are written with
+/// a weight of ±1, depending on the sign of the original weight.
+/// Finally, the output is finished.
+///
+/// If no `max_weight` is given, all events are first read into memory
+/// to determine the maximum absolute weight. For large samples, this
+/// can require more memory than is available; use [unweight_seekable]
+/// to scan the input twice instead. If `max_weight` is smaller than
+/// the largest absolute weight, the output will be biased.
+///
+/// `rng` has to return uniformly distributed random numbers in the
+/// interval [0, 1).
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let out = File::create("unweighted.lhe").unwrap();
+/// let mut writer = lhef::Writer::new(out, reader.version()).unwrap();
+///
+/// // use a proper random number generator in practice
+/// let mut seed = 42u32;
+/// let rng = || {
+///     seed ^= seed << 13;
+///     seed ^= seed >> 17;
+///     seed ^= seed << 5;
+///     seed as f64 / (u32::MAX as f64 + 1.)
+/// };
+/// lhef::tools::unweight(&mut reader, &mut writer, None, rng).unwrap();
+/// ```
+pub fn unweight<R, W, F>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+    max_weight: Option<f64>,
+    mut rng: F,
+) -> Result<(), ToolError>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut() -> f64,
+{
+    write_headers(reader, writer)?;
+    let mut heprup = reader.heprup().clone();
+    heprup.IDWTUP = if heprup.IDWTUP < 0 { -3 } else { 3 };
+    writer.heprup(&heprup)?;
+    if let Some(max_weight) = max_weight {
+        while let Some(event) = reader.hepeup()? {
+            unweight_event(writer, event, max_weight, &mut rng)?;
+        }
+    } else {
+        let mut events = Vec::new();
+        while let Some(event) = reader.hepeup()? {
+            events.push(event);
+        }
+        let max_weight =
+            events.iter().map(|e| e.XWGTUP.abs()).fold(0., f64::max);
+        for event in events {
+            unweight_event(writer, event, max_weight, &mut rng)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Unweight events with hit-or-miss sampling in two passes
+///
+/// Like [unweight] without a given maximum weight, but without keeping
+/// the events in memory. All events of `reader` are first scanned for
+/// the largest absolute weight. Then `reader` goes back to the first
+/// event and the events are unweighted one by one. This includes events
+/// that have been read before calling this function.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let out = File::create("unweighted.lhe").unwrap();
+/// let mut writer = lhef::Writer::new(out, reader.version()).unwrap();
+///
+/// // use a proper random number generator in practice
+/// let mut seed = 42u32;
+/// let rng = || {
+///     seed ^= seed << 13;
+///     seed ^= seed >> 17;
+///     seed ^= seed << 5;
+///     seed as f64 / (u32::MAX as f64 + 1.)
+/// };
+/// lhef::tools::unweight_seekable(&mut reader, &mut writer, rng).unwrap();
+/// ```
+pub fn unweight_seekable<R, W, F>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+    rng: F,
+) -> Result<(), ToolError>
+where
+    R: BufRead + Seek,
+    W: Write,
+    F: FnMut() -> f64,
+{
+    reader.rewind_events()?;
+    let max_weight = reader.max_weight()?;
+    reader.rewind_events()?;
+    unweight(reader, writer, Some(max_weight), rng)
+}
+
+/// Stream events through a function
+///
+/// The headers and run information of `reader` are copied to `writer`.
+/// Then `f` is called for each event. If it returns `Some` event, that
+/// event is written, otherwise the event is dropped. Finally, the
+/// output is finished.
+///
+/// If `reader` preserves the original text of events, the original
+/// text is only written for events whose content is not changed by `f`,
+/// as determined by [HEPEUP::content_hash].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let reader = lhef::Reader::new(file).unwrap();
+/// let out = File::create("positive.lhe").unwrap();
+/// let writer = lhef::Writer::new(out, reader.version()).unwrap();
+///
+/// // only keep events with positive weight
+/// lhef::tools::transform(reader, writer, |event| {
+///     (event.XWGTUP > 0.).then_some(event)
+/// }).unwrap();
+/// ```
+pub fn transform<R, W, F>(
+    mut reader: Reader<R>,
+    mut writer: Writer<W>,
+    mut f: F,
+) -> Result<(), ToolError>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(HEPEUP) -> Option<HEPEUP>,
+{
+    write_headers(&reader, &mut writer)?;
+    writer.heprup(reader.heprup())?;
+    while let Some(event) = reader.hepeup()? {
+        let hash = event.raw.is_some().then(|| event.content_hash());
+        if let Some(mut event) = f(event) {
+            if hash.is_some() && hash != Some(event.content_hash()) {
+                event.raw = None;
+            }
+            writer.hepeup(&event)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Write events sent from several threads
+///
+/// Spawns a thread that writes all events sent through the returned
+/// channel to `writer`, in the order in which they are received. The
+/// headers and run information have to be written before. When all
+/// senders have been dropped, the output is finished and the thread
+/// terminates. Joining the returned handle gives the result of
+/// writing. If writing fails, the thread stops and sending further
+/// events fails.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+///
+/// let out = File::create("events.lhe").unwrap();
+/// let mut writer = lhef::Writer::new(out, "3.0").unwrap();
+/// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+/// let (sender, handle) = lhef::tools::channel_writer(writer);
+///
+/// let producers: Vec<_> = (0..4).map(|_| {
+///     let sender = sender.clone();
+///     std::thread::spawn(move || {
+///         // generate events ...
+///         sender.send(lhef::HEPEUP::empty()).unwrap();
+///     })
+/// }).collect();
+/// drop(sender);
+/// for producer in producers {
+///     producer.join().unwrap();
+/// }
+/// handle.join().unwrap().unwrap();
+/// ```
+pub fn channel_writer<W>(
+    mut writer: Writer<W>,
+) -> (Sender<HEPEUP>, JoinHandle<Result<(), ToolError>>)
+where
+    W: Write + Send + 'static,
+{
+    let (sender, receiver) = channel();
+    let handle = thread::spawn(move || {
+        for event in receiver {
+            writer.hepeup(&event)?;
+        }
+        writer.finish()?;
+        Ok(())
+    });
+    (sender, handle)
+}
+
+/// Write events ordered by a key
+///
+/// The events are sorted in ascending order of `key` and written to
+/// `writer`. The sort is stable, i.e. events with the same key are
+/// written in their original order. All events are kept in memory; for
+/// inputs that do not fit into memory, use `write_sorted_external`
+/// from the `external-sort` feature.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::cmp::Reverse;
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let out = File::create("sorted.lhe").unwrap();
+/// let mut writer = lhef::Writer::new(out, reader.version()).unwrap();
+/// writer.heprup(reader.heprup()).unwrap();
+///
+/// let mut events = Vec::new();
+/// while let Some(event) = reader.hepeup().unwrap() {
+///     events.push(event);
+/// }
+/// // sort by decreasing number of particles
+/// lhef::tools::write_sorted(&mut writer, events, |e| Reverse(e.NUP))
+///     .unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub fn write_sorted<W, K, F>(
+    writer: &mut Writer<W>,
+    mut events: Vec<HEPEUP>,
+    key: F,
+) -> Result<(), ToolError>
+where
+    W: Write,
+    K: Ord,
+    F: Fn(&HEPEUP) -> K,
+{
+    events.sort_by_key(key);
+    writer.write_events(events)?;
+    Ok(())
+}
+
+/// Write events ordered by a key, using temporary files
+///
+/// This sorts all remaining events of `reader` in ascending order of
+/// `key` and writes them to `writer`, like [write_sorted]. The events
+/// are read in chunks of `chunk_size` events. Each chunk is sorted in
+/// memory and written to a temporary file, and the temporary files are
+/// merged in the end.
+///
+/// At most `chunk_size` events are kept in memory while reading, and
+/// one event per chunk while merging. In exchange, all events are
+/// written to disk once more, and one file per chunk is open at the
+/// same time. If all events fit into a single chunk, no temporary
+/// files are created.
+///
+/// This function requires the `external-sort` feature.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::cmp::Reverse;
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let out = File::create("sorted.lhe").unwrap();
+/// let mut writer = lhef::Writer::new(out, reader.version()).unwrap();
+/// writer.heprup(reader.heprup()).unwrap();
+///
+/// // sort by decreasing number of particles
+/// lhef::tools::write_sorted_external(
+///     &mut reader,
+///     &mut writer,
+///     100_000,
+///     |e| Reverse(e.NUP),
+/// ).unwrap();
+/// writer.finish().unwrap();
+/// ```
+#[cfg(feature = "external-sort")]
+pub fn write_sorted_external<R, W, K, F>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+    chunk_size: usize,
+    key: F,
+) -> Result<(), ToolError>
+where
+    R: BufRead,
+    W: Write,
+    K: Ord,
+    F: Fn(&HEPEUP) -> K,
+{
+    let chunk_size = chunk_size.max(1);
+    let mut chunks = Vec::new();
+    let mut events = Vec::new();
+    while let Some(event) = reader.hepeup()? {
+        events.push(event);
+        if events.len() >= chunk_size {
+            chunks.push(write_sorted_chunk(reader, &mut events, &key)?);
+        }
+    }
+    if chunks.is_empty() {
+        return write_sorted(writer, events, key);
+    }
+    if !events.is_empty() {
+        chunks.push(write_sorted_chunk(reader, &mut events, &key)?);
+    }
+
+    let mut chunks = Result::<Vec<_>, _>::from_iter(
+        chunks
+            .into_iter()
+            .map(|chunk| Reader::new(BufReader::new(chunk))),
+    )?;
+    let mut next = BinaryHeap::with_capacity(chunks.len());
+    for (chunk, reader) in chunks.iter_mut().enumerate() {
+        if let Some(event) = reader.hepeup()? {
+            next.push(Reverse(SortEntry::new(event, chunk, &key)));
+        }
+    }
+    while let Some(Reverse(SortEntry { event, chunk, .. })) = next.pop() {
+        writer.hepeup(&event)?;
+        if let Some(event) = chunks[chunk].hepeup()? {
+            next.push(Reverse(SortEntry::new(event, chunk, &key)));
+        }
+    }
+    Ok(())
+}
+
+// Sort events and move them to a temporary file
+#[cfg(feature = "external-sort")]
+fn write_sorted_chunk<R, K, F>(
+    reader: &Reader<R>,
+    events: &mut Vec<HEPEUP>,
+    key: F,
+) -> Result<File, ToolError>
+where
+    R: BufRead,
+    K: Ord,
+    F: Fn(&HEPEUP) -> K,
+{
+    events.sort_by_key(key);
+    let mut file = BufWriter::new(tempfile::tempfile()?);
+    let mut writer = Writer::new(&mut file, reader.version())?;
+    writer.heprup(reader.heprup())?;
+    writer.write_events(events.drain(..))?;
+    writer.finish()?;
+    drop(writer);
+    let mut file = file.into_inner().map_err(|err| err.into_error())?;
+    file.rewind()?;
+    Ok(file)
+}
+
+/// Export events in the binary bincode format
+///
+/// All remaining events of `reader` are written to `out` in the
+/// [bincode](https://docs.rs/bincode) format. This format is much
+/// faster to read than LHEF text and more compact, but it is specific
+/// to this crate and only contains the events. Use [from_bincode] to
+/// read the events back.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::{BufReader, BufWriter};
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let out = BufWriter::new(File::create("events.bin").unwrap());
+/// lhef::tools::to_bincode(&mut reader, out).unwrap();
+/// ```
+#[cfg(feature = "bincode")]
+pub fn to_bincode<R: BufRead, W: Write>(
+    reader: &mut Reader<R>,
+    mut out: W,
+) -> Result<(), ToolError> {
+    let config = bincode::config::standard();
+    while let Some(event) = reader.hepeup()? {
+        let event = BincodeEvent::from(event);
+        bincode::serde::encode_into_std_write(&event, &mut out, config)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Import events in the binary bincode format
+///
+/// Returns an iterator over the events written with [to_bincode]. The
+/// iterator stops after the first error.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+///
+/// let input = File::open("events.bin").unwrap();
+/// for event in lhef::tools::from_bincode(input) {
+///     println!("Event weight: {}", event.unwrap().XWGTUP);
+/// }
+/// ```
+#[cfg(feature = "bincode")]
+pub fn from_bincode<R: Read>(
+    input: R,
+) -> impl Iterator<Item = Result<HEPEUP, ToolError>> {
+    let config = bincode::config::standard();
+    let mut input = BufReader::new(input);
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let event = match input.fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => bincode::serde::decode_from_std_read(&mut input, config)
+                .map(|event: BincodeEvent| event.into())
+                .map_err(ToolError::from),
+            Err(err) => Err(err.into()),
+        };
+        failed = event.is_err();
+        Some(event)
+    })
+}
+
+/// Identifier at the start of the output of [export_arrays]
+pub const ARRAYS_MAGIC: &[u8; 8] = b"LHEFARR1";
+
+/// Export particle ids, momenta, and event weights as flat arrays
+///
+/// All remaining events of `reader` are written to `out` as contiguous
+/// arrays, which can be loaded directly into array libraries like
+/// NumPy. All numbers are in little-endian byte order. With `n_events`
+/// events containing `n_particles` particles in total, the layout is
+///
+/// | Content                    | Type  | Length                |
+/// |----------------------------|-------|-----------------------|
+/// | [ARRAYS_MAGIC]             | bytes | 8                     |
+/// | `n_events`                 | u64   | 1                     |
+/// | `n_particles`              | u64   | 1                     |
+/// | offsets                    | u64   | `n_events + 1`        |
+/// | `XWGTUP`                   | f64   | `n_events`            |
+/// | `IDUP`                     | i32   | `n_particles`         |
+/// | `PUP`                      | f64   | `5 * n_particles`     |
+///
+/// The particles of event `i` have the indices from `offsets[i]`
+/// (inclusive) to `offsets[i + 1]` (exclusive). The momentum components
+/// `PUP` are stored particle by particle. The arrays are collected in
+/// memory before writing.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let out = File::create("events.arrays").unwrap();
+/// lhef::tools::export_arrays(&mut reader, out).unwrap();
+/// ```
+///
+/// The output can then be read in Python with
+///
+/// ```python
+/// import numpy as np
+///
+/// data = open("events.arrays", "rb").read()
+/// n_events, n_particles = np.frombuffer(data, "<u8", 2, 8)
+/// pos = 24
+/// offsets = np.frombuffer(data, "<u8", n_events + 1, pos)
+/// pos += 8 * (n_events + 1)
+/// weights = np.frombuffer(data, "<f8", n_events, pos)
+/// pos += 8 * n_events
+/// ids = np.frombuffer(data, "<i4", n_particles, pos)
+/// pos += 4 * n_particles
+/// momenta = np.frombuffer(data, "<f8", 5 * n_particles, pos).reshape(-1, 5)
+/// ```
+pub fn export_arrays<R: BufRead, W: Write>(
+    reader: &mut Reader<R>,
+    out: W,
+) -> Result<(), ToolError> {
+    let mut offsets = vec![0u64];
+    let mut weights = Vec::new();
+    let mut ids = Vec::new();
+    let mut momenta = Vec::new();
+    while let Some(event) = reader.hepeup()? {
+        weights.push(event.XWGTUP);
+        ids.extend(event.IDUP);
+        momenta.extend(event.PUP.into_iter().flatten());
+        offsets.push(ids.len() as u64);
+    }
+    let mut out = BufWriter::new(out);
+    out.write_all(ARRAYS_MAGIC)?;
+    out.write_all(&(weights.len() as u64).to_le_bytes())?;
+    out.write_all(&(ids.len() as u64).to_le_bytes())?;
+    for offset in offsets {
+        out.write_all(&offset.to_le_bytes())?;
+    }
+    for weight in weights {
+        out.write_all(&weight.to_le_bytes())?;
+    }
+    for id in ids {
+        out.write_all(&id.to_le_bytes())?;
+    }
+    for p in momenta {
+        out.write_all(&p.to_le_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Convert events into an Apache Arrow record batch
+///
+/// The batch has one row for each particle in the remaining events of
+/// `reader`. The columns are
+///
+/// - `event`: index of the event, starting from zero,
+/// - `particle`: index of the particle in the event, starting from zero,
+/// - `pdg_id`: particle id `IDUP`,
+/// - `status`: particle status `ISTUP`,
+/// - `px`, `py`, `pz`, `e`, `m`: momentum components, energy, and mass
+///   from `PUP`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let batch = lhef::tools::to_arrow(&mut reader).unwrap();
+/// println!("Read {} particles", batch.num_rows());
+/// ```
+#[cfg(feature = "arrow")]
+pub fn to_arrow<R: BufRead>(
+    reader: &mut Reader<R>,
+) -> Result<arrow::record_batch::RecordBatch, ToolError> {
+    use arrow::array::{
+        ArrayRef, Float64Array, Int32Array, UInt32Array, UInt64Array,
+    };
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+
+    let mut event_idx = Vec::new();
+    let mut particle_idx = Vec::new();
+    let mut ids = Vec::new();
+    let mut status = Vec::new();
+    let mut momenta: [Vec<f64>; 5] = Default::default();
+    let mut nevent = 0;
+    while let Some(event) = reader.hepeup()? {
+        let nparticles = event.IDUP.len();
+        event_idx.extend(std::iter::repeat_n(nevent, nparticles));
+        particle_idx.extend(0..nparticles as u32);
+        ids.extend(event.IDUP);
+        status.extend(event.ISTUP);
+        for p in event.PUP {
+            for (column, p) in momenta.iter_mut().zip(p) {
+                column.push(p);
+            }
+        }
+        nevent += 1;
+    }
+    let [px, py, pz, e, m] = momenta;
+    let momentum_field = |name| Field::new(name, DataType::Float64, false);
+    let schema = Schema::new(vec![
+        Field::new("event", DataType::UInt64, false),
+        Field::new("particle", DataType::UInt32, false),
+        Field::new("pdg_id", DataType::Int32, false),
+        Field::new("status", DataType::Int32, false),
+        momentum_field("px"),
+        momentum_field("py"),
+        momentum_field("pz"),
+        momentum_field("e"),
+        momentum_field("m"),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from(event_idx)),
+        Arc::new(UInt32Array::from(particle_idx)),
+        Arc::new(Int32Array::from(ids)),
+        Arc::new(Int32Array::from(status)),
+        Arc::new(Float64Array::from(px)),
+        Arc::new(Float64Array::from(py)),
+        Arc::new(Float64Array::from(pz)),
+        Arc::new(Float64Array::from(e)),
+        Arc::new(Float64Array::from(m)),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Method for combining weight variations into an uncertainty band
+///
+/// Let X₀ be the central value and X₁, …, Xₙ the variations, i.e. the
+/// sums of the individual weights in a weight group.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum UncertaintyMethod {
+    /// Envelope of all variations
+    ///
+    /// The band extends from the smallest to the largest value among
+    /// X₀, …, Xₙ. This is the usual method for scale variations.
+    Envelope,
+    /// Hessian eigenvector variations
+    ///
+    /// The variations are taken as pairs (X₁, X₂), (X₃, X₄), … of
+    /// eigenvector directions. The upper uncertainty is
+    /// √(∑ max(X₂ᵢ₋₁ - X₀, X₂ᵢ - X₀, 0)²) and the lower uncertainty
+    /// √(∑ max(X₀ - X₂ᵢ₋₁, X₀ - X₂ᵢ, 0)²). If the number of variations
+    /// is odd, the first one is taken to be the central PDF member and
+    /// is skipped.
+    Hessian,
+    /// Monte Carlo replicas
+    ///
+    /// The band is given by the central value plus or minus the
+    /// standard deviation of the replica values. If the number of
+    /// variations is odd, the first one is taken to be the central PDF
+    /// member and is skipped.
+    Replicas,
+}
+
+/// Central value and uncertainty band of a cross section
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VariationBand {
+    /// Central value
+    pub central: f64,
+    /// Lower end of the band
+    pub lower: f64,
+    /// Upper end of the band
+    pub upper: f64,
+}
+
+/// Compute the uncertainty band from the weights in a weight group
+///
+/// The weight group with the given name or type is looked up in the
+/// weight definitions of the run information, see
+/// [HEPRUP::weight_definitions], or, if there are none, in the XML
+/// header. For each weight in the group, the corresponding named
+/// weights of all remaining events are summed, see
+/// [HEPEUP::named_weights]. Events without a weight do not contribute
+/// to the sum. The central value is the sum of the event weights
+/// `XWGTUP`. The weights are not normalised. For the common
+/// convention where the weights of all events add up to the total cross
+/// section, the result is the cross section with its uncertainty band.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use lhef::tools::{compute_variation_band, UncertaintyMethod};
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// let method = UncertaintyMethod::Envelope;
+/// let band = compute_variation_band(&mut reader, "scale", method).unwrap();
+/// let up = band.upper - band.central;
+/// let down = band.central - band.lower;
+/// println!("Cross section: {} +{up} -{down}", band.central);
+/// ```
+pub fn compute_variation_band<R: BufRead>(
+    reader: &mut Reader<R>,
+    group: &str,
+    method: UncertaintyMethod,
+) -> Result<VariationBand, ToolError> {
+    let definitions = find_weight_group(&reader.heprup().info, group)
+        .or_else(|| find_weight_group(reader.xml_header_text()?, group))
+        .ok_or_else(|| ToolError::UnknownWeightGroup(group.to_owned()))?;
+    let index: HashMap<_, _> = definitions
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (id, _))| (id, idx))
+        .collect();
+    let mut central = 0.;
+    let mut variations = vec![0.; index.len()];
+    while let Some(event) = reader.hepeup()? {
+        central += event.XWGTUP;
+        for (id, weight) in event.named_weights() {
+            if let Some(&idx) = index.get(&id) {
+                variations[idx] += weight;
+            }
+        }
+    }
+    Ok(variation_band(central, &variations, method))
+}
+
+fn variation_band(
+    central: f64,
+    variations: &[f64],
+    method: UncertaintyMethod,
+) -> VariationBand {
+    use UncertaintyMethod::*;
+    // skip the central member of PDF sets
+    let members = if method != Envelope && variations.len() % 2 == 1 {
+        &variations[1..]
+    } else {
+        variations
+    };
+    let (lower, upper) = match method {
+        Envelope => {
+            members.iter().fold((central, central), |(min, max), &x| {
+                (f64::min(min, x), f64::max(max, x))
+            })
+        }
+        Hessian => {
+            let mut up = 0.;
+            let mut down = 0.;
+            for pair in members.chunks_exact(2) {
+                let (a, b) = (pair[0] - central, pair[1] - central);
+                up += f64::max(f64::max(a, b), 0.).powi(2);
+                down += f64::max(f64::max(-a, -b), 0.).powi(2);
+            }
+            (central - f64::sqrt(down), central + f64::sqrt(up))
+        }
+        Replicas => {
+            let n = members.len() as f64;
+            let sigma = if members.len() < 2 {
+                0.
+            } else {
+                let mean = members.iter().sum::<f64>() / n;
+                let var =
+                    members.iter().map(|x| (x - mean).powi(2)).sum::<f64>();
+                f64::sqrt(var / (n - 1.))
+            };
+            (central - sigma, central + sigma)
+        }
+    };
+    VariationBand {
+        central,
+        lower,
+        upper,
+    }
+}
+
+// Event with all fields, since bincode does not support the fields
+// skipped by the serde implementation of `HEPEUP`
+#[cfg(feature = "bincode")]
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize)]
+struct BincodeEvent {
+    NUP: i32,
+    IDRUP: i32,
+    XWGTUP: f64,
+    SCALUP: f64,
+    AQEDUP: f64,
+    AQCDUP: f64,
+    IDUP: Vec<i32>,
+    ISTUP: Vec<i32>,
+    MOTHUP: Vec<[i32; 2]>,
+    ICOLUP: Vec<[i32; 2]>,
+    PUP: Vec<[f64; 5]>,
+    VTIMUP: Vec<f64>,
+    SPINUP: Vec<f64>,
+    info: String,
+    attr: XmlAttr,
+    raw: Option<String>,
+}
+
+#[cfg(feature = "bincode")]
+#[allow(non_snake_case)]
+impl From<HEPEUP> for BincodeEvent {
+    fn from(event: HEPEUP) -> Self {
+        let HEPEUP {
+            NUP,
+            IDRUP,
+            XWGTUP,
+            SCALUP,
+            AQEDUP,
+            AQCDUP,
+            IDUP,
+            ISTUP,
+            MOTHUP,
+            ICOLUP,
+            PUP,
+            VTIMUP,
+            SPINUP,
+            info,
+            attr,
+            raw,
+        } = event;
+        Self {
+            NUP,
+            IDRUP,
+            XWGTUP,
+            SCALUP,
+            AQEDUP,
+            AQCDUP,
+            IDUP,
+            ISTUP,
+            MOTHUP,
+            ICOLUP,
+            PUP,
+            VTIMUP,
+            SPINUP,
+            info,
+            attr,
+            raw,
+        }
+    }
+}
+
+#[cfg(feature = "bincode")]
+#[allow(non_snake_case)]
+impl From<BincodeEvent> for HEPEUP {
+    fn from(event: BincodeEvent) -> Self {
+        let BincodeEvent {
+            NUP,
+            IDRUP,
+            XWGTUP,
+            SCALUP,
+            AQEDUP,
+            AQCDUP,
+            IDUP,
+            ISTUP,
+            MOTHUP,
+            ICOLUP,
+            PUP,
+            VTIMUP,
+            SPINUP,
+            info,
+            attr,
+            raw,
+        } = event;
+        Self {
+            NUP,
+            IDRUP,
+            XWGTUP,
+            SCALUP,
+            AQEDUP,
+            AQCDUP,
+            IDUP,
+            ISTUP,
+            MOTHUP,
+            ICOLUP,
+            PUP,
+            VTIMUP,
+            SPINUP,
+            info,
+            attr,
+            raw,
+        }
+    }
+}
+
+fn unweight_event<W: Write>(
+    writer: &mut Writer<W>,
+    mut event: HEPEUP,
+    max_weight: f64,
+    rng: &mut impl FnMut() -> f64,
+) -> Result<(), WriteError> {
+    if event.XWGTUP.abs() > rng() * max_weight {
+        event.XWGTUP = 1f64.copysign(event.XWGTUP);
+        event.raw = None;
+        writer.hepeup(&event)?;
+    }
+    Ok(())
+}
+
+struct Candidate {
+    priority: f64,
+    index: usize,
+    event: HEPEUP,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .total_cmp(&other.priority)
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+// Next event from a sorted chunk, ordered by key and chunk index
+#[cfg(feature = "external-sort")]
+struct SortEntry<K> {
+    key: K,
+    chunk: usize,
+    event: HEPEUP,
+}
+
+#[cfg(feature = "external-sort")]
+impl<K> SortEntry<K> {
+    fn new<F: Fn(&HEPEUP) -> K>(event: HEPEUP, chunk: usize, key: F) -> Self {
+        Self {
+            key: key(&event),
+            chunk,
+            event,
+        }
+    }
+}
+
+#[cfg(feature = "external-sort")]
+impl<K: Ord> PartialEq for SortEntry<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+#[cfg(feature = "external-sort")]
+impl<K: Ord> Eq for SortEntry<K> {}
+
+#[cfg(feature = "external-sort")]
+impl<K: Ord> PartialOrd for SortEntry<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "external-sort")]
+impl<K: Ord> Ord for SortEntry<K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key).then(self.chunk.cmp(&other.chunk))
+    }
+}
+
+pub(crate) fn write_headers<R: BufRead, W: Write>(
+    reader: &Reader<R>,
+    writer: &mut Writer<W>,
+) -> Result<(), WriteError> {
+    if !reader.header().is_empty() {
+        writer.header(reader.header())?;
+    }
+    if let Some(header) = reader.xml_header_text() {
+        writer.xml_header_text(header)?;
+    } else if let Some(header) = reader.xml_header() {
+        writer.xml_header(header)?;
+    }
+    Ok(())
+}
+
+fn copy_events<R: BufRead, W: Write>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+) -> Result<(), ToolError> {
+    while let Some(event) = reader.hepeup()? {
+        writer.hepeup(&event)?;
+    }
+    Ok(())
+}
+
+/// Check all remaining events without writing any output
+///
+/// Each event is parsed and checked for valid mothers, see
+/// [HEPEUP::validate_mothers]. If `momentum_tol` is given, it is also
+/// checked that the total four-momentum of the final-state particles
+/// agrees with the one of the incoming particles. The check fails if
+/// any component of the difference is larger than `momentum_tol`
+/// times the total incoming energy.
+///
+/// Reading continues after malformed events, see
+/// [Reader::events_lossy]. If all events pass, summary statistics for
+/// the event weights are returned. Otherwise, the error contains the
+/// (0-based) index of each failing event together with the reason.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = BufReader::new(File::open("events.lhe").unwrap());
+/// let mut reader = lhef::Reader::new(file).unwrap();
+/// match lhef::tools::validate_file(&mut reader, Some(1e-6)) {
+///     Ok(stats) => println!("All {} events are valid", stats.n_events),
+///     Err(errors) => {
+///         for (idx, err) in errors {
+///             println!("Event {idx} is invalid: {err}");
+///         }
+///     }
+/// }
+/// ```
+pub fn validate_file<R: BufRead>(
+    reader: &mut Reader<R>,
+    momentum_tol: Option<f64>,
+) -> Result<EventStats, Vec<(usize, ToolError)>> {
+    let mut stats = EventStats::new();
+    let mut errors = Vec::new();
+    let mut idx = 0;
+    for event in reader.events_lossy() {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                errors.push((idx, err.into()));
+                idx += 1;
+                continue;
+            }
+        };
+        if let Err(err) = event.validate_mothers() {
+            errors.push((idx, err.into()));
+        } else if let Some(tol) = momentum_tol {
+            if let Err(imbalance) = check_momentum_conservation(&event, tol) {
+                errors.push((idx, ToolError::MomentumNotConserved(imbalance)));
+            }
+        }
+        stats.add(event.XWGTUP);
+        idx += 1;
+    }
+    if errors.is_empty() {
+        Ok(stats)
+    } else {
+        Err(errors)
+    }
+}
+
+// Check that the final-state momentum agrees with the incoming one up
+// to `tol` times the incoming energy, otherwise return the largest
+// deviation of a momentum component
+fn check_momentum_conservation(event: &HEPEUP, tol: f64) -> Result<(), f64> {
+    let total = |status| -> FourVector {
+        event
+            .ISTUP
+            .iter()
+            .zip(&event.PUP)
+            .filter(|(&st, _)| st == status)
+            .map(|(_, &p)| FourVector::from(p))
+            .sum()
+    };
+    let p_in = total(INCOMING);
+    let diff: [f64; 4] = (total(OUTGOING) - p_in).into();
+    let imbalance = diff.iter().fold(0., |max: f64, p| max.max(p.abs()));
+    if imbalance <= tol * p_in.e {
+        Ok(())
+    } else {
+        Err(imbalance)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ToolError {
+    #[error("No input given")]
+    NoInput,
+    #[error("Failed to read input: {0}")]
+    ReadErr(#[from] ReadError),
+    #[error("Failed to write output: {0}")]
+    WriteErr(#[from] WriteError),
+    #[error("Incompatible run information: {0}")]
+    MergeErr(#[from] MergeError),
+    #[error("I/O error: {0}")]
+    IoErr(#[from] io::Error),
+    #[error("Unknown weight group '{0}'")]
+    UnknownWeightGroup(String),
+    #[error("Invalid event: {0}")]
+    InvalidEvent(#[from] ValidationError),
+    #[error("Momentum is not conserved, largest deviation is {0}")]
+    MomentumNotConserved(f64),
+    #[cfg(feature = "bincode")]
+    #[error("Failed to encode event: {0}")]
+    EncodeErr(#[from] bincode::error::EncodeError),
+    #[cfg(feature = "bincode")]
+    #[error("Failed to decode event: {0}")]
+    DecodeErr(#[from] bincode::error::DecodeError),
+    #[cfg(feature = "arrow")]
+    #[error("Failed to convert to arrow format: {0}")]
+    ArrowErr(#[from] arrow::error::ArrowError),
+}
+
+#[cfg(test)]
+mod tools_tests {
+    extern crate flate2;
+    use super::*;
+    use crate::reader::ReaderData;
+
+    use std::cell::RefCell;
+    use std::fs::File;
+    use std::io::{BufReader, Cursor};
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+    use tools_tests::flate2::bufread::GzDecoder;
+
+    fn hejfog() -> Reader<impl BufRead> {
+        let file =
+            File::open("test_data/HEJFOG.lhe.gz").expect("file not found");
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        Reader::new(reader).unwrap()
+    }
+
+    fn count_events<R: BufRead>(mut reader: Reader<R>) -> usize {
+        let mut nevents = 0;
+        while reader.hepeup().unwrap().is_some() {
+            nevents += 1
+        }
+        nevents
+    }
+
+    #[test]
+    fn concat_two() {
+        let mut output = Vec::new();
+        concat(&mut output, vec![hejfog(), hejfog()], "3.0").unwrap();
+        let reader = Reader::new(Cursor::new(&output)).unwrap();
+        assert_eq!(reader.heprup().NPRUP, 1);
+        assert_eq!(reader.heprup().XSECUP, [2. * 0.0054619959]);
+        assert_eq!(count_events(reader), 20);
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn split_chunks() {
+        let mut reader = hejfog();
+        let mut chunks = Vec::new();
+        let nchunks = split(
+            &mut reader,
+            |_| {
+                let buf = SharedBuf::default();
+                chunks.push(buf.clone());
+                Writer::new(buf, "3.0").unwrap()
+            },
+            4,
+        )
+        .unwrap();
+        assert_eq!(nchunks, 3);
+        let nevents: Vec<_> = chunks
+            .iter()
+            .map(|chunk| {
+                let chunk = chunk.0.borrow();
+                let reader = Reader::new(chunk.as_slice()).unwrap();
+                assert_eq!(reader.heprup(), hejfog().heprup());
+                count_events(reader)
+            })
+            .collect();
+        assert_eq!(nevents, [4, 4, 2]);
+    }
+
+    fn xorshift(mut seed: u64) -> impl FnMut() -> f64 {
+        move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+
+    fn weighted_sample(nevents: usize) -> Vec<u8> {
+        let heprup = HEPRUP {
+            IDBMUP: [2212, 2212],
+            EBMUP: [7000.0, 7000.0],
+            PDFGUP: [0, 0],
+            PDFSUP: [230000, 230000],
+            IDWTUP: -4,
+            NPRUP: 1,
+            XSECUP: vec![1.],
+            XERRUP: vec![0.1],
+            XMAXUP: vec![20.],
+            LPRUP: vec![1],
+            info: String::new(),
+            attr: XmlAttr::new(),
+        };
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output, "1.0").unwrap();
+        writer.heprup(&heprup).unwrap();
+        for i in 0..nevents {
+            let sign = if i % 5 == 0 { -1. } else { 1. };
+            let event = HEPEUP {
+                IDRUP: 1,
+                XWGTUP: sign * (i % 17 + 1) as f64,
+                SCALUP: 91.188,
+                AQEDUP: 0.007546771,
+                AQCDUP: 0.1190024,
+                ..HEPEUP::empty()
+            };
+            writer.hepeup(&event).unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+        output
+    }
+
+    fn sum_weights<R: BufRead>(mut reader: Reader<R>) -> (usize, f64) {
+        let mut nevents = 0;
+        let mut sum = 0.;
+        while let Some(event) = reader.hepeup().unwrap() {
+            nevents += 1;
+            sum += event.XWGTUP;
+        }
+        (nevents, sum)
+    }
+
+    #[test]
+    fn subsample_weights() {
+        const NEVENTS: usize = 200;
+        const TARGET: usize = 40;
+        const NRUNS: usize = 200;
+        let input = weighted_sample(NEVENTS);
+        let (_, expected) = sum_weights(Reader::new(input.as_slice()).unwrap());
+        let mut total = 0.;
+        for seed in 1..=NRUNS {
+            let mut reader = Reader::new(input.as_slice()).unwrap();
+            let mut output = Vec::new();
+            let mut writer = Writer::new(&mut output, "1.0").unwrap();
+            let rng = xorshift(seed as u64);
+            subsample(&mut reader, &mut writer, TARGET, rng).unwrap();
+            drop(writer);
+            let reader = Reader::new(output.as_slice()).unwrap();
+            let (nevents, sum) = sum_weights(reader);
+            assert_eq!(nevents, TARGET);
+            total += sum;
+        }
+        let mean = total / NRUNS as f64;
+        assert!((mean - expected).abs() < 0.05 * expected.abs());
+    }
+
+    #[test]
+    fn unweight_events() {
+        let input = weighted_sample(200);
+        for max_weight in [None, Some(20.)] {
+            let mut reader = Reader::new(input.as_slice()).unwrap();
+            let mut output = Vec::new();
+            let mut writer = Writer::new(&mut output, "1.0").unwrap();
+            unweight(&mut reader, &mut writer, max_weight, xorshift(1))
+                .unwrap();
+            drop(writer);
+            let mut reader = Reader::new(output.as_slice()).unwrap();
+            assert_eq!(reader.heprup().IDWTUP, -3);
+            let mut nevents = 0;
+            while let Some(event) = reader.hepeup().unwrap() {
+                assert_eq!(event.XWGTUP.abs(), 1.);
+                nevents += 1;
+            }
+            assert!(nevents > 0);
+            assert!(nevents < 200);
+        }
+    }
+
+    #[test]
+    fn unweight_two_pass() {
+        let input = weighted_sample(200);
+        let run = |seekable: bool| {
+            let mut reader = Reader::new(Cursor::new(&input)).unwrap();
+            let mut output = Vec::new();
+            let mut writer = Writer::new(&mut output, "1.0").unwrap();
+            if seekable {
+                reader.hepeup().unwrap();
+                unweight_seekable(&mut reader, &mut writer, xorshift(1))
+            } else {
+                unweight(&mut reader, &mut writer, None, xorshift(1))
+            }
+            .unwrap();
+            drop(writer);
+            output
+        };
+        assert_eq!(run(true), run(false));
+    }
+
+    #[test]
+    fn modify_raw_events() {
+        let input = weighted_sample(50);
+        let run = |preserve_raw: bool, sub: bool| {
+            let mut reader = crate::reader::ReaderBuilder::new()
+                .preserve_raw(preserve_raw)
+                .build(input.as_slice())
+                .unwrap();
+            let mut output = Vec::new();
+            let mut writer = Writer::new(&mut output, "1.0").unwrap();
+            if sub {
+                subsample(&mut reader, &mut writer, 10, xorshift(1)).unwrap();
+            } else {
+                unweight(&mut reader, &mut writer, Some(20.), xorshift(1))
+                    .unwrap();
+            }
+            drop(writer);
+            output
+        };
+        for sub in [false, true] {
+            assert_eq!(run(true, sub), run(false, sub));
+        }
+    }
+
+    #[test]
+    fn concat_incompatible() {
+        let mut output = Vec::new();
+        let mut data = ReaderData::from(hejfog());
+        data.heprup.IDWTUP = 3;
+        let second = Reader::from(data);
+        let res = concat(&mut output, vec![hejfog(), second], "3.0");
+        assert!(matches!(res, Err(ToolError::MergeErr(_))));
+    }
+
+    #[derive(Clone, Default)]
+    struct SyncBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SyncBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn channel_two_producers() {
+        const NEVENTS: i32 = 100;
+        let output = SyncBuf::default();
+        let mut writer = Writer::new(output.clone(), "3.0").unwrap();
+        writer.heprup(hejfog().heprup()).unwrap();
+        let (sender, handle) = channel_writer(writer);
+        let producers: Vec<_> = [1, 2]
+            .into_iter()
+            .map(|idrup| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    for _ in 0..NEVENTS {
+                        let event = HEPEUP {
+                            IDRUP: idrup,
+                            ..HEPEUP::empty()
+                        };
+                        sender.send(event).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        handle.join().unwrap().unwrap();
+
+        let output = output.0.lock().unwrap();
+        let mut reader = Reader::new(output.as_slice()).unwrap();
+        let mut nevents = [0; 2];
+        while let Some(event) = reader.hepeup().unwrap() {
+            nevents[event.IDRUP as usize - 1] += 1;
+        }
+        assert_eq!(nevents, [NEVENTS; 2]);
+    }
+
+    // Sort key for decreasing weights
+    #[derive(PartialEq)]
+    struct DecreasingWeight(f64);
+
+    impl Eq for DecreasingWeight {}
+
+    impl PartialOrd for DecreasingWeight {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for DecreasingWeight {
+        fn cmp(&self, other: &Self) -> Ordering {
+            other.0.total_cmp(&self.0)
+        }
+    }
+
+    fn decreasing_weight(event: &HEPEUP) -> DecreasingWeight {
+        DecreasingWeight(event.XWGTUP)
+    }
+
+    fn sorted_weights(output: Vec<u8>) -> Vec<f64> {
+        let mut reader = Reader::new(output.as_slice()).unwrap();
+        let mut weights = Vec::new();
+        while let Some(event) = reader.hepeup().unwrap() {
+            weights.push(event.XWGTUP);
+        }
+        weights
+    }
+
+    #[test]
+    fn sort_by_weight() {
+        let mut reader = hejfog();
+        let mut writer = Writer::new(vec![], reader.version()).unwrap();
+        writer.heprup(reader.heprup()).unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = reader.hepeup().unwrap() {
+            events.push(event);
+        }
+        write_sorted(&mut writer, events, decreasing_weight).unwrap();
+        writer.finish().unwrap();
+        let weights = sorted_weights(writer.into_inner());
+        assert_eq!(weights.len(), 10);
+        assert!(weights.windows(2).all(|w| w[0] >= w[1]));
+        assert!(weights[0] > weights[9]);
+    }
+
+    #[cfg(feature = "external-sort")]
+    #[test]
+    fn sort_by_weight_external() {
+        let mut expected = Vec::new();
+        for chunk_size in [0, 3, 10, 100] {
+            let mut reader = hejfog();
+            let mut writer = Writer::new(vec![], reader.version()).unwrap();
+            writer.heprup(reader.heprup()).unwrap();
+            write_sorted_external(
+                &mut reader,
+                &mut writer,
+                chunk_size,
+                decreasing_weight,
+            )
+            .unwrap();
+            writer.finish().unwrap();
+            let weights = sorted_weights(writer.into_inner());
+            assert_eq!(weights.len(), 10);
+            assert!(weights.windows(2).all(|w| w[0] >= w[1]));
+            if expected.is_empty() {
+                expected = weights;
+            } else {
+                assert_eq!(weights, expected);
+            }
+        }
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trip() {
+        let mut reader = hejfog();
+        let mut output = Vec::new();
+        to_bincode(&mut reader, &mut output).unwrap();
+
+        let mut events = Vec::new();
+        let mut reader = hejfog();
+        while let Some(event) = reader.hepeup().unwrap() {
+            events.push(event);
+        }
+        let cmp_events: Vec<_> = from_bincode(output.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(cmp_events.len(), 10);
+        for (event, cmp_event) in events.iter().zip(&cmp_events) {
+            assert_eq!(event.XWGTUP.to_bits(), cmp_event.XWGTUP.to_bits());
+        }
+        assert_eq!(cmp_events, events);
+
+        // truncated input
+        let truncated = &output[..output.len() - 1];
+        let mut cmp_events = from_bincode(truncated);
+        for _ in 0..9 {
+            assert!(cmp_events.next().unwrap().is_ok());
+        }
+        assert!(cmp_events.next().unwrap().is_err());
+        assert!(cmp_events.next().is_none());
+    }
+
+    #[test]
+    fn arrays() {
+        let mut output = Vec::new();
+        export_arrays(&mut hejfog(), &mut output).unwrap();
+        let (magic, rest) = output.split_at(8);
+        assert_eq!(magic, ARRAYS_MAGIC);
+        let mut numbers = rest
+            .chunks_exact(8)
+            .map(|x| u64::from_le_bytes(x.try_into().unwrap()));
+        let n_events = numbers.next().unwrap() as usize;
+        let n_particles = numbers.next().unwrap() as usize;
+        assert_eq!(n_events, 10);
+        let offsets = Vec::from_iter(numbers.take(n_events + 1));
+        assert_eq!(offsets[0], 0);
+        assert_eq!(offsets[n_events], n_particles as u64);
+
+        let mut reader = hejfog();
+        let first = reader.hepeup().unwrap().unwrap();
+        assert_eq!(offsets[1], first.NUP as u64);
+        let mut nparticles = first.NUP as usize;
+        while let Some(event) = reader.hepeup().unwrap() {
+            nparticles += event.NUP as usize;
+        }
+        assert_eq!(n_particles, nparticles);
+        let len = 8 + 16 + 8 * (n_events + 1) + 8 * n_events;
+        assert_eq!(output.len(), len + 4 * n_particles + 40 * n_particles);
+
+        let weight = &output[len - 8 * n_events..][..8];
+        assert_eq!(
+            f64::from_le_bytes(weight.try_into().unwrap()),
+            first.XWGTUP
+        );
+        let id = &output[len..][..4];
+        assert_eq!(i32::from_le_bytes(id.try_into().unwrap()), first.IDUP[0]);
+        let p = &output[len + 4 * n_particles..][..8];
+        assert_eq!(f64::from_le_bytes(p.try_into().unwrap()), first.PUP[0][0]);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn arrow() {
+        use arrow::array::{Float64Array, UInt64Array};
+
+        let batch = to_arrow(&mut hejfog()).unwrap();
+        let mut reader = hejfog();
+        let first = reader.hepeup().unwrap().unwrap();
+        let mut nparticles = first.NUP as usize;
+        let mut nevents = 1;
+        while let Some(event) = reader.hepeup().unwrap() {
+            nparticles += event.NUP as usize;
+            nevents += 1;
+        }
+        assert_eq!(batch.num_rows(), nparticles);
+        assert_eq!(batch.num_columns(), 9);
+
+        let events = batch.column_by_name("event").unwrap();
+        let events = events.as_any().downcast_ref::<UInt64Array>().unwrap();
+        assert_eq!(events.value(0), 0);
+        assert_eq!(events.value(nparticles - 1), nevents - 1);
+        let e = batch.column_by_name("e").unwrap();
+        let e = e.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(e.value(0), first.PUP[0][3]);
+    }
+
+    #[test]
+    fn variation_band_scale() {
+        let text = "<LesHouchesEvents version=\"3.0\">
+<header>
+<initrwgt>
+<weightgroup name='scale' combine='envelope'>
+<weight id='1'> muR=2 </weight>
+<weight id='2'> muR=0.5 </weight>
+</weightgroup>
+<weightgroup name='pdf'>
+<weight id='3'> member 1 </weight>
+</weightgroup>
+</initrwgt>
+</header>
+<init>
+2212 2212 6500 6500 0 0 0 0 3 1
+1 0.1 1 1
+</init>
+<event>
+0 1 1 91.188 0.0078125 0.118
+<rwgt>
+<wgt id='1'> 0.75 </wgt>
+<wgt id='2'> 1.5 </wgt>
+<wgt id='3'> 1 </wgt>
+</rwgt>
+</event>
+<event>
+0 1 2 91.188 0.0078125 0.118
+<rwgt>
+<wgt id='1'> 1.5 </wgt>
+<wgt id='2'> 2 </wgt>
+</rwgt>
+</event>
+</LesHouchesEvents>
+";
+        let mut reader = Reader::new(text.as_bytes()).unwrap();
+        let band = compute_variation_band(
+            &mut reader,
+            "scale",
+            UncertaintyMethod::Envelope,
+        )
+        .unwrap();
+        assert_eq!(
+            band,
+            VariationBand {
+                central: 3.,
+                lower: 2.25,
+                upper: 3.5
+            }
+        );
+        let mut reader = Reader::new(text.as_bytes()).unwrap();
+        assert!(matches!(
+            compute_variation_band(
+                &mut reader,
+                "ren",
+                UncertaintyMethod::Envelope
+            ),
+            Err(ToolError::UnknownWeightGroup(_))
+        ));
+
+        // weights moved to the run information
+        let start = text.find("<initrwgt>").unwrap();
+        let end = text.find("</header>").unwrap();
+        let text = text.replacen(&text[start..end], "", 1).replacen(
+            "</init>",
+            &format!("{}</init>", &text[start..end]),
+            1,
+        );
+        let mut reader = Reader::new(text.as_bytes()).unwrap();
+        let band = compute_variation_band(
+            &mut reader,
+            "pdf",
+            UncertaintyMethod::Hessian,
+        )
+        .unwrap();
+        assert_eq!(band.central, 3.);
+        assert_eq!(band.lower, 3.);
+        assert_eq!(band.upper, 3.);
+    }
+
+    #[test]
+    fn uncertainty_methods() {
+        use UncertaintyMethod::*;
+        let band = variation_band(10., &[10., 12., 9., 7., 11.], Hessian);
+        assert_eq!(band.lower, 10. - f64::sqrt(1. + 9.));
+        assert_eq!(band.upper, 10. + f64::sqrt(4. + 1.));
+        let band = variation_band(10., &[12., 9., 7., 11.], Hessian);
+        assert_eq!(band.lower, 10. - f64::sqrt(1. + 9.));
+        let band = variation_band(10., &[12., 9., 7., 11.], Envelope);
+        assert_eq!((band.lower, band.upper), (7., 12.));
+        let band = variation_band(10., &[0., 9., 11.], Replicas);
+        assert_eq!(
+            (band.lower, band.upper),
+            (10. - f64::sqrt(2.), 10. + f64::sqrt(2.))
+        );
+        let band = variation_band(10., &[], Replicas);
+        assert_eq!((band.lower, band.upper), (10., 10.));
+    }
+
+    #[test]
+    fn validate() {
+        use std::io::Read;
+
+        let stats = validate_file(&mut hejfog(), Some(1e-6)).unwrap();
+        assert_eq!(stats.n_events, 10);
+
+        let file =
+            File::open("test_data/HEJFOG.lhe.gz").expect("file not found");
+        let mut text = String::new();
+        GzDecoder::new(BufReader::new(file))
+            .read_to_string(&mut text)
+            .unwrap();
+        let mut events: Vec<_> =
+            text.split("<event").map(String::from).collect();
+        // malformed number of particles in event 1
+        events[2] = events[2].replacen("    7", "  abc", 1);
+        // mother out of range in event 3
+        events[4] = events[4].replacen(" 1    2   -1", " 9    2   -1", 1);
+        // momentum violation in event 4
+        events[5] = events[5].replacen("786.70144 ", "800.00000 ", 1);
+        let text = events.join("<event");
+
+        let reader = || Reader::new(text.as_bytes()).unwrap();
+        let errors = validate_file(&mut reader(), None).unwrap_err();
+        let idx = Vec::from_iter(errors.iter().map(|(idx, _)| *idx));
+        assert_eq!(idx, [1, 3]);
+        assert!(matches!(errors[0].1, ToolError::ReadErr(_)));
+        assert!(matches!(errors[1].1, ToolError::InvalidEvent(_)));
+
+        let errors = validate_file(&mut reader(), Some(1e-6)).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[2].0, 4);
+        assert!(matches!(errors[2].1, ToolError::MomentumNotConserved(_)));
+
+        // truncated file
+        let end = text.rfind("<event").unwrap();
+        let mut reader = Reader::new(&text.as_bytes()[..end]).unwrap();
+        let errors = validate_file(&mut reader, None).unwrap_err();
+        assert!(matches!(
+            errors.last().unwrap(),
+            (9, ToolError::ReadErr(ReadError::EndOfFile(_)))
+        ));
+    }
+
+    #[test]
+    fn transform_weights() {
+        for preserve_raw in [false, true] {
+            let file = File::open("test_data/HEJFOG.lhe.gz").unwrap();
+            let file = BufReader::new(GzDecoder::new(BufReader::new(file)));
+            let input = crate::reader::ReaderBuilder::new()
+                .preserve_raw(preserve_raw)
+                .build(file)
+                .unwrap();
+            let mut output = Vec::new();
+            let writer = Writer::new(&mut output, "3.0").unwrap();
+            transform(input, writer, |mut event| {
+                event.XWGTUP *= 2.;
+                Some(event)
+            })
+            .unwrap();
+            let mut reader = Reader::new(output.as_slice()).unwrap();
+            let mut orig = hejfog();
+            assert_eq!(reader.header(), orig.header());
+            assert_eq!(reader.heprup(), orig.heprup());
+            while let Some(event) = orig.hepeup().unwrap() {
+                let doubled = reader.hepeup().unwrap().unwrap();
+                assert_eq!(doubled.XWGTUP, 2. * event.XWGTUP);
+                assert_eq!(doubled.PUP, event.PUP);
+            }
+            assert!(reader.hepeup().unwrap().is_none());
+        }
+
+        let mut output = Vec::new();
+        let writer = Writer::new(&mut output, "3.0").unwrap();
+        transform(hejfog(), writer, |_| None).unwrap();
+        let reader = Reader::new(output.as_slice()).unwrap();
+        assert_eq!(count_events(reader), 0);
+    }
+}
@@ -1,10 +1,17 @@
-use std::fmt::Write as FmtWrite;
-use std::io::Write;
+use std::fmt::{self, Debug, Write as FmtWrite};
+#[cfg(feature = "zstd")]
+use std::fs::File;
+use std::io::{self, Write};
 use std::mem::take;
 use std::ops::Drop;
 use std::str;
+use std::sync::{Arc, Mutex};
 
 use crate::data::*;
+use crate::info::XsecInfo;
+use crate::stats::EventStats;
+#[cfg(feature = "metrics")]
+use crate::stats::{Throughput, ThroughputMeter};
 use crate::syntax::*;
 
 use itertools::izip;
@@ -13,6 +20,9 @@ use thiserror::Error;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+// Size above which partial event output is written to the stream
+const WRITE_CHUNK_SIZE: usize = 64 * 1024;
+
 /// Writer for the LHEF format
 ///
 /// The general usage to write a file is
@@ -33,13 +43,82 @@ use serde::{Deserialize, Serialize};
 /// writer.finish()?;
 /// ```
 /// It is important to keep the proper order of method calls and to call
-/// finish() at the end.
+/// finish() at the end. By default, a writer that is dropped after
+/// writing the run information calls finish() automatically. Errors
+/// in this case can only be retrieved through [Writer::drop_error].
+/// See also [Writer::finish_on_drop].
 #[derive(Debug, PartialEq, Eq)]
 pub struct Writer<T: Write> {
     stream: T,
     state: WriterState,
+    finish_on_drop: bool,
+    drop_error: Option<DropError>,
+    fortran_columns: bool,
+    reject_nonfinite: bool,
+    block_separator: BlockSeparator,
+    // whether the last block written was an event
+    after_event: bool,
+    finish_stream: Option<StreamFinisher<T>>,
+    #[cfg(feature = "metrics")]
+    meter: ThroughputMeter,
+    #[cfg(feature = "metrics")]
+    bytes_written: u64,
+}
+
+// Completes the output stream after the last line, e.g. by ending a
+// compressed frame
+struct StreamFinisher<T>(fn(&mut T) -> io::Result<()>);
+
+impl<T> Debug for StreamFinisher<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StreamFinisher")
+    }
+}
+
+// Functions cannot be compared reliably, so they are ignored when
+// comparing writers
+impl<T> PartialEq for StreamFinisher<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for StreamFinisher<T> {}
+
+/// Error from finishing a writer when it is dropped
+///
+/// See [Writer::drop_error].
+#[derive(Debug, Clone, Default)]
+pub struct DropError(Arc<Mutex<Option<WriteError>>>);
+
+impl DropError {
+    /// Take the error, if any
+    ///
+    /// Returns `None` if the writer has not been dropped yet, finishing
+    /// on drop succeeded, or the error has already been taken.
+    pub fn take(&self) -> Option<WriteError> {
+        self.lock().take()
+    }
+
+    fn set(&self, error: WriteError) {
+        *self.lock() = Some(error);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<WriteError>> {
+        // the slot stays usable even if another thread panicked
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
 }
 
+// Handles are equal if they refer to the same error
+impl PartialEq for DropError {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for DropError {}
+
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Copy)]
 /// State of LHEF writer
@@ -55,6 +134,33 @@ pub enum WriterState {
     Failed,
 }
 
+/// Separator between consecutive events
+///
+/// See [Writer::set_block_separator].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub enum BlockSeparator {
+    /// Start each event directly on the line after the previous one
+    #[default]
+    None,
+    /// Write an empty line between events
+    BlankLine,
+    /// Write the given text between events
+    ///
+    /// The text is written as it is and should end with a line break.
+    Custom(String),
+}
+
+impl BlockSeparator {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::None => "",
+            Self::BlankLine => "\n",
+            Self::Custom(text) => text,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum WriteError {
     #[error(
@@ -74,6 +180,10 @@ pub enum WriteError {
          Output was written, but the file may be broken anyway."
     )]
     WriteToFailed,
+    #[error("Invalid LHEF version '{0}', expected a version like '1.0'")]
+    InvalidVersion(String),
+    #[error("Entry '{0}' is not a finite number")]
+    NonFinite(String),
     #[error("Format error: {0}")]
     FmtErr(#[from] std::fmt::Error),
     #[error("Write error: {0}")]
@@ -100,12 +210,51 @@ impl<T: Write> Writer<T> {
     ///    std::io::Cursor::new(&mut output), "1.0"
     /// ).unwrap();
     /// ```
-    pub fn new(mut stream: T, version: &str) -> Result<Writer<T>, WriteError> {
+    ///
+    /// The version has to be of the form "X.Y" with non-negative
+    /// integers X and Y, otherwise an error is returned. To write a
+    /// nonstandard version, use [Writer::new_unchecked].
+    ///
+    /// The writer performs many small writes. Writing directly to an
+    /// unbuffered stream like a [std::fs::File] is therefore
+    /// slow. Use [Writer::buffered] to add buffering.
+    pub fn new(stream: T, version: &str) -> Result<Writer<T>, WriteError> {
+        if !is_valid_version(version) {
+            return Err(WriteError::InvalidVersion(version.to_owned()));
+        }
+        Self::new_unchecked(stream, version)
+    }
+
+    /// Create a new LHEF writer without checking the version
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut output = vec![];
+    /// let writer = lhef::Writer::new_unchecked(
+    ///    std::io::Cursor::new(&mut output), "3.0.beta"
+    /// ).unwrap();
+    /// ```
+    pub fn new_unchecked(
+        mut stream: T,
+        version: &str,
+    ) -> Result<Writer<T>, WriteError> {
         let output = String::from(LHEF_TAG_OPEN) + "\"" + version + "\">\n";
         stream.write_all(output.as_bytes())?;
         Ok(Writer {
             stream,
             state: WriterState::ExpectingHeaderOrInit,
+            finish_on_drop: true,
+            drop_error: None,
+            fortran_columns: false,
+            reject_nonfinite: false,
+            block_separator: BlockSeparator::None,
+            after_event: false,
+            finish_stream: None,
+            #[cfg(feature = "metrics")]
+            meter: ThroughputMeter::new(),
+            #[cfg(feature = "metrics")]
+            bytes_written: output.len() as u64,
         })
     }
 
@@ -130,6 +279,11 @@ impl<T: Write> Writer<T> {
     }
 
     /// Write a LHEF comment header
+    ///
+    /// The header is enclosed in the comment markers `<!--` and `-->`,
+    /// unless it already starts and ends with them, as for example the
+    /// header returned by [Reader::header](crate::Reader::header).
+    ///
     /// # Example
     ///
     /// ```rust
@@ -141,13 +295,24 @@ impl<T: Write> Writer<T> {
     /// ```
     pub fn header(&mut self, header: &str) -> Result<(), WriteError> {
         self.assert_state(WriterState::ExpectingHeaderOrInit, "header")?;
-        let output = String::from(COMMENT_START)
-            + "\n"
-            + header
-            + "\n"
-            + COMMENT_END
-            + "\n";
-        match self.stream.write_all(output.as_bytes()) {
+        let trimmed = header.trim();
+        let output = if trimmed.starts_with(COMMENT_START)
+            && trimmed.ends_with(COMMENT_END)
+        {
+            let mut output = header.to_owned();
+            if !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output
+        } else {
+            String::from(COMMENT_START)
+                + "\n"
+                + header
+                + "\n"
+                + COMMENT_END
+                + "\n"
+        };
+        match self.write_stream(&output) {
             Ok(_) => self.ok_unless_failed(),
             Err(error) => {
                 self.state = WriterState::Failed;
@@ -156,6 +321,7 @@ impl<T: Write> Writer<T> {
         }
     }
 
+    #[cfg(feature = "xml")]
     /// Write a LHEF xml header
     ///
     /// If the outermost xml tag in the argument is not "header" an
@@ -194,9 +360,7 @@ impl<T: Write> Writer<T> {
             xml_to_string(header, &mut output);
             output += "\n";
         } else {
-            for (key, value) in &header.attributes {
-                write!(&mut output, " {}=\"{}\"", key, value)?;
-            }
+            write_xml_attributes(&header.attributes, &mut output);
             output += ">";
             if !header.children.is_empty() {
                 output += "\n";
@@ -210,7 +374,7 @@ impl<T: Write> Writer<T> {
                     if header.children.is_empty() && !text.starts_with('\n') {
                         output += "\n"
                     }
-                    output += text;
+                    write_text(text, &mut output);
                     if !text.ends_with('\n') {
                         output += "\n";
                     }
@@ -219,7 +383,7 @@ impl<T: Write> Writer<T> {
         }
         output += HEADER_END;
         output += "\n";
-        match self.stream.write_all(output.as_bytes()) {
+        match self.write_stream(&output) {
             Ok(_) => self.ok_unless_failed(),
             Err(error) => {
                 self.state = WriterState::Failed;
@@ -228,6 +392,51 @@ impl<T: Write> Writer<T> {
         }
     }
 
+    /// Write a LHEF xml header
+    ///
+    /// Without the `xml` feature, the header is given as text including
+    /// the enclosing `<header>` tags and is written unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut output = vec![];
+    /// let mut writer = lhef::Writer::new(
+    ///    std::io::Cursor::new(&mut output), "1.0"
+    /// ).unwrap();
+    /// writer.xml_header("<header>\nsome xml header\n</header>").unwrap();
+    /// ```
+    #[cfg(not(feature = "xml"))]
+    pub fn xml_header(&mut self, header: &str) -> Result<(), WriteError> {
+        self.xml_header_text(header)
+    }
+
+    /// Write the text of a LHEF xml header
+    ///
+    /// The header is given as text including the enclosing `<header>`
+    /// tags and is written unchanged, apart from adding a final line
+    /// break if necessary. This preserves comments, for example in a
+    /// header obtained from [Reader::xml_header_text](crate::Reader::xml_header_text).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut output = vec![];
+    /// let mut writer = lhef::Writer::new(
+    ///    std::io::Cursor::new(&mut output), "1.0"
+    /// ).unwrap();
+    /// writer.xml_header_text("<header>\n<!-- comment -->\n</header>")
+    ///     .unwrap();
+    /// ```
+    pub fn xml_header_text(&mut self, header: &str) -> Result<(), WriteError> {
+        self.assert_state(WriterState::ExpectingHeaderOrInit, "xml header")?;
+        let mut output = header.to_owned();
+        if !output.ends_with('\n') {
+            output += "\n";
+        }
+        self.write_output(&output)
+    }
+
     /// Write the run information in HEPRUP format
     ///
     /// # Example
@@ -253,7 +462,10 @@ impl<T: Write> Writer<T> {
     /// };
     /// writer.heprup(&heprup).unwrap();
     /// ```
-    pub fn heprup(&mut self, runinfo: &HEPRUP) -> Result<(), WriteError> {
+    pub fn heprup<F: Float>(
+        &mut self,
+        runinfo: &HEPRUP<F>,
+    ) -> Result<(), WriteError> {
         self.assert_state(WriterState::ExpectingHeaderOrInit, "init")?;
         let num_sub = runinfo.NPRUP as usize;
         if num_sub != runinfo.XSECUP.len()
@@ -263,9 +475,14 @@ impl<T: Write> Writer<T> {
         {
             return Err(WriteError::MismatchedSubprocesses);
         }
+        if self.reject_nonfinite {
+            if let Some(name) = runinfo.find_nonfinite() {
+                return Err(WriteError::NonFinite(name));
+            }
+        }
         let mut output = String::from(INIT_START);
         for (attr, value) in &runinfo.attr {
-            write!(&mut output, "{}=\"{}\"", attr, value)?;
+            write!(&mut output, " {}=\"{}\"", attr, value)?;
         }
         output += ">\n";
         for entry in runinfo.IDBMUP.iter() {
@@ -299,7 +516,7 @@ impl<T: Write> Writer<T> {
         }
         output += INIT_END;
         output += "\n";
-        if let Err(error) = self.stream.write_all(output.as_bytes()) {
+        if let Err(error) = self.write_stream(&output) {
             self.state = WriterState::Failed;
             return Err(error.into());
         }
@@ -309,8 +526,57 @@ impl<T: Write> Writer<T> {
         self.ok_unless_failed()
     }
 
+    /// Write run information with an `<xsecinfo>` tag computed from
+    /// event statistics
+    ///
+    /// The run information is written with [heprup](Self::heprup), with
+    /// the `<xsecinfo>` tag in `info` replaced by one derived from
+    /// `stats`, see [XsecInfo]. The number of events is `n_events`, the
+    /// total cross section `totxsec` is the sum of weights, and the
+    /// largest weight `maxweight` is the largest absolute weight.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use std::io::BufReader;
+    /// use std::fs::File;
+    ///
+    /// let file = BufReader::new(File::open("events.lhe").unwrap());
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    /// let stats = reader.statistics().unwrap();
+    ///
+    /// let file = BufReader::new(File::open("events.lhe").unwrap());
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    /// let out = File::create("out.lhe").unwrap();
+    /// let mut writer = lhef::Writer::new(out, "3.0").unwrap();
+    /// writer
+    ///     .heprup_with_computed_xsecinfo(reader.heprup(), &stats)
+    ///     .unwrap();
+    /// while let Some(event) = reader.hepeup().unwrap() {
+    ///     writer.hepeup(&event).unwrap();
+    /// }
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn heprup_with_computed_xsecinfo<F: Float>(
+        &mut self,
+        runinfo: &HEPRUP<F>,
+        stats: &EventStats,
+    ) -> Result<(), WriteError> {
+        let mut runinfo = runinfo.clone();
+        runinfo.set_xsecinfo(&XsecInfo::from(stats));
+        self.heprup(&runinfo)
+    }
+
     /// Write event in HEPEUP format
     ///
+    /// If the event contains the original text in its `raw` field, that
+    /// text is written instead.
+    ///
+    /// Events with many particles are written in several chunks, so
+    /// that the whole event text never has to be kept in memory. Each
+    /// chunk is only written after it has been formatted completely. If
+    /// writing fails, the writer enters the `Failed` state.
+    ///
     /// # Example
     ///
     /// ```rust,no_run
@@ -340,12 +606,44 @@ impl<T: Write> Writer<T> {
     ///     SPINUP: vec!(1.0, -1.0, -1.0, 1.0),
     ///     info: String::new(),
     ///     attr: lhef::XmlAttr::new(),
+    ///     raw: None,
     /// };
     /// writer.hepeup(&hepeup).unwrap();
     /// ```
-    pub fn hepeup(&mut self, event: &HEPEUP) -> Result<(), WriteError> {
+    pub fn hepeup<F: Float>(
+        &mut self,
+        event: &HEPEUP<F>,
+    ) -> Result<(), WriteError> {
+        self.write_event(event)?;
+        self.after_event = true;
+        #[cfg(feature = "metrics")]
+        self.meter.add_event();
+        Ok(())
+    }
+
+    fn write_event<F: Float>(
+        &mut self,
+        event: &HEPEUP<F>,
+    ) -> Result<(), WriteError> {
         let mut buffer = ryu::Buffer::new();
         self.assert_state(WriterState::ExpectingEventOrFinish, "event")?;
+        let separator = if self.after_event {
+            self.block_separator.as_str()
+        } else {
+            ""
+        };
+        if self.reject_nonfinite {
+            if let Some(name) = event.find_nonfinite() {
+                return Err(WriteError::NonFinite(name));
+            }
+        }
+        if let Some(raw) = &event.raw {
+            let mut output = separator.to_owned() + raw;
+            if !output.ends_with('\n') {
+                output += "\n";
+            }
+            return self.write_output(&output);
+        }
         let num_particles = event.NUP as usize;
         if num_particles != event.IDUP.len()
             || num_particles != event.ISTUP.len()
@@ -357,7 +655,7 @@ impl<T: Write> Writer<T> {
         {
             return Err(WriteError::MismatchedParticles);
         }
-        let mut output = String::from(EVENT_START);
+        let mut output = separator.to_owned() + EVENT_START;
         for (attr, value) in &event.attr {
             write!(&mut output, " {}=\"{}\"", attr, value)?;
         }
@@ -383,18 +681,37 @@ impl<T: Write> Writer<T> {
         );
 
         for (id, status, mothers, colour, p, lifetime, spin) in particles {
-            write!(&mut output, "{} {} ", id, status)?;
-            for m in mothers {
-                write!(&mut output, "{} ", m)?;
-            }
-            for c in colour {
-                write!(&mut output, "{} ", c)?;
+            // write large events in chunks to limit memory usage
+            if output.len() >= WRITE_CHUNK_SIZE {
+                if let Err(error) = self.write_stream(&output) {
+                    self.state = WriterState::Failed;
+                    return Err(error.into());
+                }
+                output.clear();
             }
-            for p in p {
-                write!(&mut output, "{} ", buffer.format(*p))?;
+            if self.fortran_columns {
+                write!(&mut output, " {:>8} {:>4}", id, status)?;
+                for i in mothers.iter().chain(colour) {
+                    write!(&mut output, " {:>4}", i)?;
+                }
+                for &x in p.iter().chain([lifetime, spin]) {
+                    write_fortran_float(&mut output, x.into())?;
+                }
+                output += "\n";
+            } else {
+                write!(&mut output, "{} {} ", id, status)?;
+                for m in mothers {
+                    write!(&mut output, "{} ", m)?;
+                }
+                for c in colour {
+                    write!(&mut output, "{} ", c)?;
+                }
+                for p in p {
+                    write!(&mut output, "{} ", buffer.format(*p))?;
+                }
+                write!(&mut output, "{} ", buffer.format(*lifetime))?;
+                writeln!(&mut output, "{}", buffer.format(*spin))?;
             }
-            write!(&mut output, "{} ", buffer.format(*lifetime))?;
-            writeln!(&mut output, "{}", buffer.format(*spin))?;
         }
         if !event.info.is_empty() {
             output += &event.info;
@@ -404,7 +721,111 @@ impl<T: Write> Writer<T> {
         }
         output += EVENT_END;
         output += "\n";
-        match self.stream.write_all(output.as_bytes()) {
+        self.write_output(&output)
+    }
+
+    /// Write several events
+    ///
+    /// The events are written in order. Writing stops at the first
+    /// error, which is returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::open("events.lhe").unwrap();
+    /// let file = std::io::BufReader::new(file);
+    /// let mut reader = lhef::Reader::new(file).unwrap();
+    ///
+    /// let mut output = vec![];
+    /// let mut writer = lhef::Writer::new(
+    ///    std::io::Cursor::new(&mut output), "1.0"
+    /// ).unwrap();
+    /// writer.heprup(reader.heprup()).unwrap();
+    /// let events = reader
+    ///     .filter_events(|event| event.XWGTUP > 0.)
+    ///     .map_while(Result::ok);
+    /// writer.write_events(events).unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn write_events<F, I>(&mut self, events: I) -> Result<(), WriteError>
+    where
+        F: Float,
+        I: IntoIterator<Item = HEPEUP<F>>,
+    {
+        for event in events {
+            self.hepeup(&event)?;
+        }
+        Ok(())
+    }
+
+    /// Write several borrowed events
+    ///
+    /// Like [write_events](Self::write_events), but without taking
+    /// ownership of the events.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let events = vec![lhef::HEPEUP::empty(); 3];
+    ///
+    /// let mut output = vec![];
+    /// let mut writer = lhef::Writer::new(
+    ///    std::io::Cursor::new(&mut output), "1.0"
+    /// ).unwrap();
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// writer.write_events_ref(&events).unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn write_events_ref<'a, F, I>(
+        &mut self,
+        events: I,
+    ) -> Result<(), WriteError>
+    where
+        F: Float + 'a,
+        I: IntoIterator<Item = &'a HEPEUP<F>>,
+    {
+        for event in events {
+            self.hepeup(event)?;
+        }
+        Ok(())
+    }
+
+    /// Write a comment block after the events
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut output = vec![];
+    /// let mut writer = lhef::Writer::new(
+    ///    std::io::Cursor::new(&mut output), "1.0"
+    /// ).unwrap();
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// // ... write events ...
+    /// writer.footer("some footer text").unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn footer(&mut self, footer: &str) -> Result<(), WriteError> {
+        self.assert_state(WriterState::ExpectingEventOrFinish, "footer")?;
+        let output = String::from(COMMENT_START)
+            + "\n"
+            + footer
+            + "\n"
+            + COMMENT_END
+            + "\n";
+        self.write_output(&output)
+    }
+
+    fn write_stream(&mut self, output: &str) -> std::io::Result<()> {
+        self.stream.write_all(output.as_bytes())?;
+        #[cfg(feature = "metrics")]
+        {
+            self.bytes_written += output.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn write_output(&mut self, output: &str) -> Result<(), WriteError> {
+        match self.write_stream(output) {
             Ok(_) => self.ok_unless_failed(),
             Err(error) => {
                 self.state = WriterState::Failed;
@@ -428,10 +849,16 @@ impl<T: Write> Writer<T> {
     pub fn finish(&mut self) -> Result<(), WriteError> {
         self.assert_state(WriterState::ExpectingEventOrFinish, "finish")?;
         let output = String::from(LHEF_LAST_LINE) + "\n";
-        if let Err(error) = self.stream.write_all(output.as_bytes()) {
+        if let Err(error) = self.write_stream(&output) {
             self.state = WriterState::Failed;
             return Err(error.into());
         }
+        if let Some(StreamFinisher(finish_stream)) = &self.finish_stream {
+            if let Err(error) = finish_stream(&mut self.stream) {
+                self.state = WriterState::Failed;
+                return Err(error.into());
+            }
+        }
         if self.state != WriterState::Failed {
             self.state = WriterState::Finished
         }
@@ -453,13 +880,339 @@ impl<T: Write> Writer<T> {
     pub fn state(&self) -> WriterState {
         self.state
     }
+
+    /// Check whether the LHEF output has been completed with
+    /// [finish](Self::finish)
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut writer = lhef::Writer::new(vec![], "1.0").unwrap();
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// assert!(!writer.is_finished());
+    /// writer.finish().unwrap();
+    /// assert!(writer.is_finished());
+    /// ```
+    pub fn is_finished(&self) -> bool {
+        self.state == WriterState::Finished
+    }
+
+    /// Check whether a previous write failed
+    ///
+    /// In this case, the LHEF output may be broken.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let writer = lhef::Writer::new(vec![], "1.0").unwrap();
+    /// assert!(!writer.has_failed());
+    /// ```
+    pub fn has_failed(&self) -> bool {
+        self.state == WriterState::Failed
+    }
+
+    /// Get the number of events and bytes written since the writer was
+    /// created
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut writer = lhef::Writer::new(vec![], "1.0").unwrap();
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// writer.hepeup(&lhef::HEPEUP::empty()).unwrap();
+    /// assert_eq!(writer.throughput().events, 1);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn throughput(&self) -> Throughput {
+        self.meter.throughput(self.bytes_written)
+    }
+
+    /// Write particles in fixed-width columns
+    ///
+    /// If enabled, the particle lines of each event are written in the
+    /// classic Fortran style. Each entry is right-aligned and preceded by
+    /// a space. `IDUP` takes up 8 columns and the other integers 4
+    /// columns. Floating-point numbers are written in 15 columns with
+    /// eight decimal places and an exponent with at least two digits,
+    /// corresponding to the format `%16.8E` including the separating
+    /// space. Larger numbers exceed their columns, but are always
+    /// separated by at least one space. The default is `false`, i.e.
+    /// compact output with the shortest representation of each number.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut writer = lhef::Writer::new(vec![], "1.0").unwrap();
+    /// writer.set_fortran_columns(true);
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// let event = lhef::HEPEUP {
+    ///     NUP: 1,
+    ///     IDUP: vec![22],
+    ///     ISTUP: vec![1],
+    ///     MOTHUP: vec![[0, 0]],
+    ///     ICOLUP: vec![[0, 0]],
+    ///     PUP: vec![[0., 0., -10., 10., 0.]],
+    ///     VTIMUP: vec![0.],
+    ///     SPINUP: vec![9.],
+    ///     ..lhef::HEPEUP::empty()
+    /// };
+    /// writer.hepeup(&event).unwrap();
+    /// writer.finish().unwrap();
+    /// let output = String::from_utf8(writer.into_inner()).unwrap();
+    /// assert!(output.contains(
+    ///     "       22    1    0    0    0    0  0.00000000E+00  \
+    ///     0.00000000E+00 -1.00000000E+01  1.00000000E+01  \
+    ///     0.00000000E+00  0.00000000E+00  9.00000000E+00\n"
+    /// ));
+    /// ```
+    pub fn set_fortran_columns(&mut self, enable: bool) {
+        self.fortran_columns = enable;
+    }
+
+    /// Reject floating-point numbers that are not finite
+    ///
+    /// If enabled, writing run information or an event with an entry
+    /// that is infinite or NaN results in an error, and nothing is
+    /// written. By default, such entries are written as `inf` or `NaN`,
+    /// which can be read again unless
+    /// [ReaderBuilder::reject_nonfinite](crate::reader::ReaderBuilder::reject_nonfinite)
+    /// is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use lhef::writer::WriteError;
+    ///
+    /// let mut writer = lhef::Writer::new(vec![], "1.0").unwrap();
+    /// writer.set_reject_nonfinite(true);
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// let event = lhef::HEPEUP {
+    ///     XWGTUP: f64::NAN,
+    ///     ..lhef::HEPEUP::empty()
+    /// };
+    /// assert!(matches!(
+    ///     writer.hepeup(&event),
+    ///     Err(WriteError::NonFinite(_))
+    /// ));
+    /// ```
+    pub fn set_reject_nonfinite(&mut self, reject: bool) {
+        self.reject_nonfinite = reject;
+    }
+
+    /// Set the separator between consecutive events
+    ///
+    /// The separator is written between the end of an event and the
+    /// start of the next one, but not before the first or after the
+    /// last event. The default is [BlockSeparator::None]. Readers in
+    /// [strict mode](crate::reader::ReaderBuilder::strict) reject blank
+    /// lines between events.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use lhef::writer::BlockSeparator;
+    ///
+    /// let mut writer = lhef::Writer::new(vec![], "1.0").unwrap();
+    /// writer.set_block_separator(BlockSeparator::BlankLine);
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// writer.hepeup(&lhef::HEPEUP::empty()).unwrap();
+    /// writer.hepeup(&lhef::HEPEUP::empty()).unwrap();
+    /// writer.finish().unwrap();
+    /// let output = String::from_utf8(writer.into_inner()).unwrap();
+    /// assert!(output.contains("</event>\n\n<event>"));
+    /// ```
+    pub fn set_block_separator(&mut self, separator: BlockSeparator) {
+        self.block_separator = separator;
+    }
+
+    /// Set whether the output should be finished when the writer is dropped
+    ///
+    /// This is enabled by default. When enabled, dropping a writer that
+    /// has written the run information but has not been finished calls
+    /// [finish](Self::finish). Since errors cannot be returned from a
+    /// destructor, they are only available through
+    /// [drop_error](Self::drop_error). It is therefore still
+    /// recommended to call [finish](Self::finish) explicitly and check
+    /// the result. Failures before that can be detected with
+    /// [has_failed](Self::has_failed).
+    ///
+    /// When disabled, dropping an unfinished writer leaves the output
+    /// incomplete.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut output = vec![];
+    /// {
+    ///     let mut writer = lhef::Writer::new(&mut output, "1.0").unwrap();
+    ///     writer.finish_on_drop(false);
+    ///     writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// }
+    /// let output = String::from_utf8(output).unwrap();
+    /// assert!(!output.contains("</LesHouchesEvents>"));
+    /// ```
+    pub fn finish_on_drop(&mut self, finish: bool) {
+        self.finish_on_drop = finish;
+    }
+
+    /// Get a handle to the error from finishing the output on drop
+    ///
+    /// If finishing the output fails when the writer is dropped, see
+    /// [finish_on_drop](Self::finish_on_drop), the error is stored in
+    /// the returned handle, where it can be retrieved after the writer
+    /// is gone. Without a handle, such errors are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let mut writer = lhef::Writer::new(vec![], "1.0").unwrap();
+    /// let drop_error = writer.drop_error();
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// drop(writer);
+    /// assert!(drop_error.take().is_none());
+    /// ```
+    pub fn drop_error(&mut self) -> DropError {
+        self.drop_error
+            .get_or_insert_with(DropError::default)
+            .clone()
+    }
 }
 
 impl<T: Write> Drop for Writer<T> {
     fn drop(&mut self) {
-        if self.state == WriterState::ExpectingEventOrFinish {
-            let _ = self.finish();
+        if self.finish_on_drop
+            && self.state == WriterState::ExpectingEventOrFinish
+        {
+            if let Err(err) = self.finish() {
+                if let Some(drop_error) = &self.drop_error {
+                    drop_error.set(err);
+                }
+            }
+        }
+    }
+}
+
+impl<W: Write> Writer<io::BufWriter<W>> {
+    /// Create a new LHEF writer with a buffered output stream
+    ///
+    /// The stream is wrapped in a [BufWriter](io::BufWriter), which is
+    /// flushed when the writer is finished, see [Writer::finish].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let file = std::fs::File::create("events.lhe").unwrap();
+    /// let mut writer = lhef::Writer::buffered(file, "3.0").unwrap();
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// // ... write events ...
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn buffered(stream: W, version: &str) -> Result<Self, WriteError> {
+        let mut writer = Self::new(io::BufWriter::new(stream), version)?;
+        writer.finish_stream = Some(StreamFinisher(io::BufWriter::flush));
+        Ok(writer)
+    }
+}
+
+impl Writer<io::BufWriter<io::StdoutLock<'static>>> {
+    /// Create a new LHEF writer for the standard output
+    ///
+    /// The standard output is locked for the lifetime of the writer and
+    /// buffered like for [Writer::buffered].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// // cat events.lhe | mytool > positive.lhe
+    /// let mut reader = lhef::Reader::from_stdin().unwrap();
+    /// let writer = lhef::Writer::to_stdout(reader.version()).unwrap();
+    /// lhef::tools::transform(reader, writer, |event| {
+    ///     (event.XWGTUP > 0.).then_some(event)
+    /// }).unwrap();
+    /// ```
+    pub fn to_stdout(version: &str) -> Result<Self, WriteError> {
+        Self::buffered(io::stdout().lock(), version)
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Writer<ZstdFile> {
+    /// Create a writer for a zstd-compressed file
+    ///
+    /// `level` is the compression level, where 0 chooses the default
+    /// level. The compressed data are completed when the writer is
+    /// finished, see [Writer::finish]. Files written in this way can be
+    /// read with [Reader::from_path](crate::Reader::from_path).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let mut writer =
+    ///     lhef::Writer::create_zstd("events.lhe.zst", "3.0", 3).unwrap();
+    /// writer.heprup(&lhef::HEPRUP::empty()).unwrap();
+    /// // ... write events ...
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn create_zstd<P: AsRef<std::path::Path>>(
+        path: P,
+        version: &str,
+        level: i32,
+    ) -> Result<Self, WriteError> {
+        let file = io::BufWriter::new(File::create(path)?);
+        let encoder = zstd::Encoder::new(file, level)?;
+        let stream = ZstdFile {
+            encoder: Some(encoder),
+        };
+        let mut writer = Self::new(stream, version)?;
+        writer.finish_stream = Some(StreamFinisher(ZstdFile::finish));
+        Ok(writer)
+    }
+}
+
+/// Zstd-compressed output file
+///
+/// See [Writer::create_zstd]. If the file is dropped before the writer
+/// is finished, the compressed data are completed, but any error is
+/// lost.
+#[cfg(feature = "zstd")]
+pub struct ZstdFile {
+    encoder: Option<zstd::Encoder<'static, io::BufWriter<File>>>,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdFile {
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish()?.flush()?;
         }
+        Ok(())
+    }
+
+    fn encoder(
+        &mut self,
+    ) -> io::Result<&mut zstd::Encoder<'static, io::BufWriter<File>>> {
+        self.encoder
+            .as_mut()
+            .ok_or_else(|| io::Error::other("compressed file was finished"))
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Write for ZstdFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder()?.flush()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Drop for ZstdFile {
+    fn drop(&mut self) {
+        // errors are documented to be lost here
+        let _ = self.finish();
     }
 }
 
@@ -474,9 +1227,158 @@ impl<T: Write + Default> Writer<T> {
     }
 }
 
+/// Write a complete LHEF document with the given run information and events
+///
+/// This creates a [Writer] for `out`, writes `heprup` and all `events`,
+/// and finishes the output. An error is returned if the version is not
+/// valid or if the run information or any of the events is
+/// inconsistent, see [Writer::heprup] and [Writer::hepeup]. In this
+/// case, the output is incomplete.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let events = vec![lhef::HEPEUP::empty(); 3];
+/// let out = std::fs::File::create("events.lhe").unwrap();
+/// lhef::writer::write_minimal(out, "3.0", &lhef::HEPRUP::empty(), events)
+///     .unwrap();
+/// ```
+pub fn write_minimal<W, F, I>(
+    out: W,
+    version: &str,
+    heprup: &HEPRUP<F>,
+    events: I,
+) -> Result<(), WriteError>
+where
+    W: Write,
+    F: Float,
+    I: IntoIterator<Item = HEPEUP<F>>,
+{
+    let mut writer = Writer::new(out, version)?;
+    writer.finish_on_drop(false);
+    writer.heprup(heprup)?;
+    writer.write_events(events)?;
+    writer.finish()
+}
+
+fn is_valid_version(version: &str) -> bool {
+    let is_number =
+        |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+    match version.split_once('.') {
+        Some((major, minor)) => is_number(major) && is_number(minor),
+        None => false,
+    }
+}
+
+#[cfg(feature = "xml")]
+/// Convert an XML tree to a string
+///
+/// Elements without children and text are written as self-closing tags.
+/// Attributes are written in alphabetical order. Special characters in
+/// attribute values and text are escaped. Text spanning several lines
+/// that contains special characters is written as a CDATA section
+/// instead, so that it appears unchanged in the output.
+///
+/// # Example
+///
+/// ```rust
+/// let tree = lhef::XmlTree::parse("<a x='1'><b>text</b></a>".as_bytes())
+///     .unwrap();
+/// let text = lhef::writer::xml_tree_to_string(&tree);
+/// assert_eq!(text, "<a x=\"1\"><b>text</b></a>");
+/// ```
+pub fn xml_tree_to_string(xml: &XmlTree) -> String {
+    let mut output = String::new();
+    xml_to_string(xml, &mut output);
+    output
+}
+
+#[cfg(feature = "xml")]
+fn xml_to_string(xml: &XmlTree, output: &mut String) {
+    *output += "<";
+    *output += &xml.name;
+    write_xml_attributes(&xml.attributes, output);
+    let has_text = xml.text.as_ref().is_some_and(|text| !text.is_empty());
+    if xml.children.is_empty() && !has_text {
+        *output += "/>";
+        return;
+    }
+    *output += ">";
+    if let Some(ref text) = xml.text {
+        write_text(text, output);
+    }
+    for child in &xml.children {
+        xml_to_string(child, output)
+    }
+    *output += &format!("</{}>", xml.name);
+}
+
+// Write a space followed by a number in the Fortran format E15.8, with
+// an exponent of at least two digits
+fn write_fortran_float(output: &mut String, x: f64) -> fmt::Result {
+    let mut formatted = format!("{:.8E}", x);
+    // infinity and NaN have no exponent
+    if let Some((mantissa, exponent)) = formatted.split_once('E') {
+        let exponent: i32 = exponent.parse().unwrap();
+        let sign = if exponent < 0 { '-' } else { '+' };
+        formatted = format!("{}E{}{:02}", mantissa, sign, exponent.abs());
+    }
+    write!(output, " {:>15}", formatted)
+}
+
+#[cfg(feature = "xml")]
+fn write_xml_attributes(attributes: &XmlAttr, output: &mut String) {
+    let mut attributes = Vec::from_iter(attributes);
+    attributes.sort_unstable();
+    for (key, value) in attributes {
+        *output += " ";
+        *output += key;
+        *output += "=\"";
+        write_escaped(value, output);
+        *output += "\"";
+    }
+}
+
+#[cfg(feature = "xml")]
+fn write_text(text: &str, output: &mut String) {
+    const CDATA_START: &str = "<![CDATA[";
+    const CDATA_END: &str = "]]>";
+    if !text.contains(['&', '<', '>']) {
+        *output += text;
+    } else if text.contains('\n') && !text.contains(CDATA_END) {
+        *output += CDATA_START;
+        *output += text;
+        *output += CDATA_END;
+    } else {
+        for c in text.chars() {
+            match c {
+                '&' => *output += "&amp;",
+                '<' => *output += "&lt;",
+                '>' => *output += "&gt;",
+                c => output.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "xml")]
+fn write_escaped(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => *output += "&amp;",
+            '<' => *output += "&lt;",
+            '>' => *output += "&gt;",
+            '"' => *output += "&quot;",
+            '\'' => *output += "&apos;",
+            c => output.push(c),
+        }
+    }
+}
+
 #[cfg(test)]
 mod writer_tests {
     use super::*;
+    #[cfg(feature = "xml")]
     use std::collections::HashMap;
     use std::io;
 
@@ -532,12 +1434,14 @@ mod writer_tests {
 ",
             ),
             attr: XmlAttr::new(),
+            raw: None,
         };
         let mut buf = vec![];
         {
             let mut writer =
                 Writer::new(io::Cursor::new(&mut buf), "1.0").unwrap();
             writer.header("some header").unwrap();
+            #[cfg(feature = "xml")]
             let header = {
                 let mut attr = HashMap::new();
                 attr.insert("attr0".to_string(), "val0".to_string());
@@ -552,6 +1456,10 @@ mod writer_tests {
                     text: Some(String::from("some xml header")),
                 }
             };
+            #[cfg(not(feature = "xml"))]
+            let header = String::from(
+                "<header attr0=\"val0\">\nsome xml header\n</header>",
+            );
             writer.xml_header(&header).unwrap();
             writer.heprup(&heprup).unwrap();
             writer.hepeup(&hepeup).unwrap();
@@ -559,20 +1467,588 @@ mod writer_tests {
         }
         // println!("{}", str::from_utf8(&buf).unwrap());
     }
-}
 
-fn xml_to_string(xml: &XmlTree, output: &mut String) {
-    *output += "<";
-    *output += &xml.name;
-    for (key, value) in &xml.attributes {
-        *output += &format!(" {}=\"{}\"", key, value);
+    #[test]
+    fn write_version() {
+        for version in ["1.0", "2.0", "3.0", "3.1", "10.12"] {
+            let writer = Writer::new(vec![], version);
+            assert!(writer.is_ok(), "rejected version '{version}'");
+        }
+        for version in ["garbage", "1", "1.", ".0", "1.0.beta", "1.0 "] {
+            let writer = Writer::new(vec![], version);
+            assert!(
+                matches!(writer, Err(WriteError::InvalidVersion(_))),
+                "accepted version '{version}'"
+            );
+        }
+        let writer = Writer::new_unchecked(vec![], "1.0.beta").unwrap();
+        assert!(String::from_utf8(writer.into_inner())
+            .unwrap()
+            .starts_with("<LesHouchesEvents version=\"1.0.beta\">"));
     }
-    *output += ">";
-    if let Some(ref text) = xml.text {
-        *output += text;
+
+    #[test]
+    fn write_events() {
+        let heprup = HEPRUP {
+            NPRUP: 1,
+            XSECUP: vec![1.],
+            XERRUP: vec![0.1],
+            XMAXUP: vec![1.],
+            LPRUP: vec![1],
+            ..HEPRUP::empty()
+        };
+        let events: Vec<_> = (1..=3)
+            .map(|n| HEPEUP {
+                IDRUP: 1,
+                XWGTUP: n as f64,
+                ..HEPEUP::empty()
+            })
+            .collect();
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        writer.heprup(&heprup).unwrap();
+        writer.write_events_ref(&events).unwrap();
+        writer.write_events(events.clone()).unwrap();
+        writer.finish().unwrap();
+        let output = writer.into_inner();
+
+        let mut reader = crate::Reader::new(output.as_slice()).unwrap();
+        for expected in events.iter().chain(&events) {
+            let event = reader.hepeup().unwrap().unwrap();
+            assert_eq!(event.XWGTUP, expected.XWGTUP);
+        }
+        assert!(reader.hepeup().unwrap().is_none());
+
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        assert!(matches!(
+            writer.write_events_ref(&events),
+            Err(WriteError::BadState(..))
+        ));
+
+        // writing stops at the first broken event
+        let mut broken = events.clone();
+        broken[1].NUP = 1;
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        writer.heprup(&heprup).unwrap();
+        assert!(matches!(
+            writer.write_events(broken),
+            Err(WriteError::MismatchedParticles)
+        ));
+        writer.finish().unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(output.matches(EVENT_START).count(), 1);
     }
-    for child in &xml.children {
-        xml_to_string(child, output)
+
+    // Writer that fails once more than `capacity` bytes are written
+    struct FailingWriter {
+        capacity: usize,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.capacity {
+                return Err(io::Error::other("full"));
+            }
+            self.capacity -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn state_transitions() {
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        assert!(!writer.is_finished());
+        assert!(!writer.has_failed());
+        writer.header("header").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.hepeup(&HEPEUP::empty()).unwrap();
+        assert!(!writer.is_finished());
+        assert!(!writer.has_failed());
+        writer.finish().unwrap();
+        assert!(writer.is_finished());
+        assert!(!writer.has_failed());
+        assert!(writer.finish().is_err());
+        assert!(writer.is_finished());
+
+        let out = FailingWriter { capacity: 40 };
+        let mut writer = Writer::new(out, "1.0").unwrap();
+        assert!(!writer.has_failed());
+        assert!(writer.heprup(&HEPRUP::empty()).is_err());
+        assert!(writer.has_failed());
+        assert!(!writer.is_finished());
+    }
+
+    #[test]
+    fn footer() {
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        assert!(matches!(
+            writer.footer("too early"),
+            Err(WriteError::BadState(..))
+        ));
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.hepeup(&HEPEUP::empty()).unwrap();
+        writer.footer("first footer").unwrap();
+        writer.footer("second\nfooter").unwrap();
+        writer.finish().unwrap();
+        let output = writer.into_inner();
+        assert!(String::from_utf8_lossy(&output).contains("first footer"));
+
+        let mut reader = crate::Reader::new(output.as_slice()).unwrap();
+        assert_eq!(reader.hepeup().unwrap(), Some(HEPEUP::empty()));
+        assert!(reader.hepeup().unwrap().is_none());
+        let mut reader = crate::Reader::new(output.as_slice()).unwrap();
+        assert!(reader.next_raw_event().unwrap().is_some());
+        assert!(reader.next_raw_event().unwrap().is_none());
+        let mut reader = crate::Reader::new(output.as_slice()).unwrap();
+        assert_eq!(reader.statistics().unwrap().n_events, 1);
+
+        let single_line = "<LesHouchesEvents version=\"1.0\">
+<init>
+0 0 0 0 0 0 0 0 0 0
+</init>
+<!-- footer -->
+</LesHouchesEvents>
+";
+        let mut reader = crate::Reader::new(single_line.as_bytes()).unwrap();
+        assert!(reader.hepeup().unwrap().is_none());
+
+        let unterminated = single_line.replace(" -->", "");
+        let mut reader = crate::Reader::new(unterminated.as_bytes()).unwrap();
+        assert!(reader.hepeup().is_err());
+    }
+
+    #[test]
+    fn write_large_event() {
+        let nparticles = 10_000;
+        let mut event = HEPEUP {
+            NUP: nparticles,
+            ..HEPEUP::empty()
+        };
+        for i in 0..nparticles {
+            event.IDUP.push(i);
+            event.ISTUP.push(1);
+            event.MOTHUP.push([1, 2]);
+            event.ICOLUP.push([0, 0]);
+            event.PUP.push([1.5, -2.5, 3.5, 4.5, 0.]);
+            event.VTIMUP.push(0.);
+            event.SPINUP.push(9.);
+        }
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.hepeup(&event).unwrap();
+        writer.finish().unwrap();
+        let output = writer.into_inner();
+        assert!(output.len() > 2 * WRITE_CHUNK_SIZE);
+        let mut reader = crate::Reader::new(output.as_slice()).unwrap();
+        assert_eq!(reader.hepeup().unwrap(), Some(event.clone()));
+
+        let out = FailingWriter {
+            capacity: WRITE_CHUNK_SIZE,
+        };
+        let mut writer = Writer::new(out, "1.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        assert!(writer.hepeup(&event).is_err());
+        assert!(writer.has_failed());
+
+        // an already failed writer still writes the complete event
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.state = WriterState::Failed;
+        let res = writer.hepeup(&event);
+        assert!(matches!(res, Err(WriteError::WriteToFailed)));
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(output.len() > 2 * WRITE_CHUNK_SIZE);
+        assert!(output.ends_with("</event>\n"));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn write_throughput() {
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        let start = writer.throughput();
+        assert_eq!(start.events, 0);
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer
+            .write_events([HEPEUP::empty(), HEPEUP::empty()])
+            .unwrap();
+        let throughput = writer.throughput();
+        assert_eq!(throughput.events, 2);
+        assert!(throughput.bytes > start.bytes);
+        assert!(throughput.elapsed >= start.elapsed);
+        writer.finish().unwrap();
+        let bytes = writer.throughput().bytes;
+        assert_eq!(bytes, writer.into_inner().len() as u64);
+    }
+
+    #[test]
+    fn finish_on_drop() {
+        for finish in [true, false] {
+            let mut output = vec![];
+            {
+                let mut writer = Writer::new(&mut output, "1.0").unwrap();
+                writer.finish_on_drop(finish);
+                writer.heprup(&HEPRUP::empty()).unwrap();
+                writer.hepeup(&HEPEUP::empty()).unwrap();
+            }
+            let output = String::from_utf8(output).unwrap();
+            let last_line = String::from(LHEF_LAST_LINE) + "\n";
+            assert_eq!(output.ends_with(&last_line), finish);
+        }
+
+        // enough space for the run information, but not for the last line
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        let capacity = writer.into_inner().len();
+
+        let out = FailingWriter { capacity };
+        let mut writer = Writer::new(out, "1.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        assert!(!writer.has_failed());
+        assert!(writer.finish().is_err());
+        assert!(writer.has_failed());
+
+        // failing to finish on drop must not panic
+        let out = FailingWriter { capacity };
+        let mut writer = Writer::new(out, "1.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        drop(writer);
+
+        let out = FailingWriter { capacity };
+        let mut writer = Writer::new(out, "1.0").unwrap();
+        let drop_error = writer.drop_error();
+        assert_eq!(drop_error, writer.drop_error());
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        assert!(drop_error.take().is_none());
+        drop(writer);
+        assert!(matches!(drop_error.take(), Some(WriteError::WriteErr(_))));
+        assert!(drop_error.take().is_none());
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn xml_round_trip() {
+        let text = "<outer b=\"2\" a=\"&quot;1&amp;\">\
+                    <inner c=\"&lt;3&gt;\">x &lt; y</inner>\
+                    <inner/>\
+                    </outer>";
+        let tree = XmlTree::parse(text.as_bytes()).unwrap();
+        let output = xml_tree_to_string(&tree);
+        assert_eq!(
+            output,
+            "<outer a=\"&quot;1&amp;\" b=\"2\">\
+             <inner c=\"&lt;3&gt;\">x &lt; y</inner>\
+             <inner/>\
+             </outer>"
+        );
+        assert_eq!(XmlTree::parse(output.as_bytes()).unwrap(), tree);
+    }
+
+    #[test]
+    fn buffered() {
+        // Write run information and events, then finish
+        fn write_all<T: Write>(writer: &mut Writer<T>) {
+            let event = HEPEUP {
+                NUP: 1,
+                IDUP: vec![22],
+                ISTUP: vec![1],
+                MOTHUP: vec![[0, 0]],
+                ICOLUP: vec![[0, 0]],
+                PUP: vec![[0., 0., 10., 10., 0.]],
+                VTIMUP: vec![0.],
+                SPINUP: vec![9.],
+                ..HEPEUP::empty()
+            };
+            writer.heprup(&HEPRUP::empty()).unwrap();
+            for _ in 0..100 {
+                writer.hepeup(&event).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+
+        let mut writer = Writer::new(vec![], "3.0").unwrap();
+        write_all(&mut writer);
+        let mut buffered = Writer::buffered(vec![], "3.0").unwrap();
+        write_all(&mut buffered);
+        assert_eq!(buffered.stream.get_ref(), &writer.stream);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_round_trip() {
+        extern crate flate2;
+        use crate::Reader;
+        use flate2::bufread::GzDecoder;
+        use std::io::BufReader;
+
+        let file = File::open("test_data/HEJFOG.lhe.gz").unwrap();
+        let input = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        let mut reader = Reader::new(input).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.lhe.zst");
+        let mut writer =
+            Writer::create_zstd(&path, reader.version(), 0).unwrap();
+        writer.heprup(reader.heprup()).unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = reader.hepeup().unwrap() {
+            writer.hepeup(&event).unwrap();
+            events.push(event);
+        }
+        writer.finish().unwrap();
+        assert!(writer.write_stream("\n").is_err());
+        drop(writer);
+
+        let mut reader = Reader::from_path(&path).unwrap();
+        let mut cmp_events = Vec::new();
+        while let Some(event) = reader.hepeup().unwrap() {
+            cmp_events.push(event);
+        }
+        assert_eq!(cmp_events, events);
+
+        // dropping an unfinished writer completes the file
+        let mut writer = Writer::create_zstd(&path, "3.0", 19).unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        drop(writer);
+        let mut reader = Reader::from_path(&path).unwrap();
+        assert!(reader.hepeup().unwrap().is_none());
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn xml_self_closing() {
+        let text = "<initrwgt>\
+                    <weightgroup name='scale' combine=\"envelope\"></weightgroup>\
+                    <weightgroup name=\"pdf\"><weight id='1'/></weightgroup>\
+                    </initrwgt>";
+        let tree = XmlTree::parse(text.as_bytes()).unwrap();
+        let output = xml_tree_to_string(&tree);
+        assert_eq!(
+            output,
+            "<initrwgt>\
+             <weightgroup combine=\"envelope\" name=\"scale\"/>\
+             <weightgroup name=\"pdf\"><weight id=\"1\"/></weightgroup>\
+             </initrwgt>"
+        );
+        assert_eq!(XmlTree::parse(output.as_bytes()).unwrap(), tree);
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn xml_cdata() {
+        let namelist = "
+&RUN_PARAMS
+  nevents = 10000, ebeam = 6500.d0,
+  cuts = 'ptj > 20 & etaj < 5' /
+";
+        let text = format!(
+            "<header>\n<MGRunCard><![CDATA[{namelist}]]></MGRunCard>\n</header>"
+        );
+        let header = XmlTree::parse(text.as_bytes()).unwrap();
+        assert_eq!(header.children[0].text.as_deref(), Some(namelist));
+
+        let mut writer = Writer::new(vec![], "3.0").unwrap();
+        writer.xml_header(&header).unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.finish().unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(output.contains(&format!("<![CDATA[{namelist}]]>")));
+
+        let reader = crate::Reader::new(output.as_bytes()).unwrap();
+        assert_eq!(reader.xml_header().as_ref(), Some(&header));
+    }
+
+    #[test]
+    fn fortran_columns() {
+        let event = HEPEUP {
+            NUP: 2,
+            IDRUP: 1,
+            XWGTUP: 0.5,
+            IDUP: vec![-1000021, 22],
+            ISTUP: vec![-1, 1],
+            MOTHUP: vec![[0, 0], [1, 1]],
+            ICOLUP: vec![[501, 502], [0, 0]],
+            PUP: vec![[0., 0., 1e-5, 1234.5, 0.], [-0.25, 1e100, 2., 3., 0.]],
+            VTIMUP: vec![0., 0.],
+            SPINUP: vec![9., -1.],
+            ..HEPEUP::empty()
+        };
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        writer.set_fortran_columns(true);
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.hepeup(&event).unwrap();
+        writer.finish().unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let lines =
+            Vec::from_iter(output.lines().skip_while(|l| *l != "<event>"));
+        assert_eq!(
+            lines[2],
+            " -1000021   -1    0    0  501  502  0.00000000E+00  \
+             0.00000000E+00  1.00000000E-05  1.23450000E+03  \
+             0.00000000E+00  0.00000000E+00  9.00000000E+00"
+        );
+        assert_eq!(lines[2].len(), 9 + 5 * 5 + 7 * 16);
+        assert_eq!(
+            lines[3],
+            "       22    1    1    1    0    0 -2.50000000E-01 \
+             1.00000000E+100  2.00000000E+00  3.00000000E+00  \
+             0.00000000E+00  0.00000000E+00 -1.00000000E+00"
+        );
+        let mut reader = crate::Reader::new(output.as_bytes()).unwrap();
+        assert_eq!(reader.hepeup().unwrap().unwrap(), event);
+    }
+
+    #[test]
+    fn block_separator() {
+        let separators = [
+            (BlockSeparator::None, "</event>\n<event>"),
+            (BlockSeparator::BlankLine, "</event>\n\n<event>"),
+            (
+                BlockSeparator::Custom("<!-- next -->\n".to_owned()),
+                "</event>\n<!-- next -->\n<event>",
+            ),
+        ];
+        for (separator, between) in separators {
+            let mut writer = Writer::new(vec![], "1.0").unwrap();
+            writer.set_block_separator(separator);
+            writer.heprup(&HEPRUP::empty()).unwrap();
+            for _ in 0..3 {
+                writer.hepeup(&HEPEUP::empty()).unwrap();
+            }
+            writer.finish().unwrap();
+            let output = String::from_utf8(writer.into_inner()).unwrap();
+            assert_eq!(output.matches(between).count(), 2);
+            assert!(output.contains("</init>\n<event>"));
+            assert!(output.ends_with("</event>\n</LesHouchesEvents>\n"));
+
+            let mut reader = crate::Reader::new(output.as_bytes()).unwrap();
+            let mut nevents = 0;
+            while reader.hepeup().unwrap().is_some() {
+                nevents += 1;
+            }
+            assert_eq!(nevents, 3);
+        }
+    }
+
+    #[test]
+    fn computed_xsecinfo() {
+        let heprup = HEPRUP {
+            info: "<generator>test</generator>\n\
+                   <xsecinfo neve='1' totxsec='1'/>\n"
+                .to_owned(),
+            ..HEPRUP::empty()
+        };
+        let events: Vec<_> = [0.5, -0.25, 2.]
+            .into_iter()
+            .map(|weight| HEPEUP {
+                XWGTUP: weight,
+                ..HEPEUP::empty()
+            })
+            .collect();
+        let mut stats = EventStats::new();
+        for event in &events {
+            stats.add(event.XWGTUP);
+        }
+        let mut writer = Writer::new(vec![], "3.0").unwrap();
+        writer
+            .heprup_with_computed_xsecinfo(&heprup, &stats)
+            .unwrap();
+        writer.write_events(events).unwrap();
+        writer.finish().unwrap();
+        let output = writer.into_inner();
+
+        let mut reader = crate::Reader::new(output.as_slice()).unwrap();
+        let expected = XsecInfo {
+            neve: 3,
+            totxsec: 2.25,
+            maxweight: 2.,
+        };
+        assert_eq!(reader.heprup().xsecinfo(), Some(expected));
+        assert!(reader
+            .heprup()
+            .info
+            .starts_with("<generator>test</generator>"));
+        assert_eq!(reader.heprup().info.matches("<xsecinfo").count(), 1);
+        assert!(reader.verify_event_count().is_ok());
+    }
+
+    #[test]
+    fn reject_nonfinite() {
+        let event = HEPEUP {
+            NUP: 1,
+            IDUP: vec![21],
+            ISTUP: vec![-1],
+            MOTHUP: vec![[0, 0]],
+            ICOLUP: vec![[501, 502]],
+            PUP: vec![[0., 0., f64::INFINITY, 100., 0.]],
+            VTIMUP: vec![0.],
+            SPINUP: vec![9.],
+            ..HEPEUP::empty()
+        };
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        writer.hepeup(&event).unwrap();
+        writer.finish().unwrap();
+        let output = writer.into_inner();
+        let mut reader = crate::Reader::new(output.as_slice()).unwrap();
+        assert_eq!(reader.hepeup().unwrap().unwrap(), event);
+
+        let mut writer = Writer::new(vec![], "1.0").unwrap();
+        writer.set_reject_nonfinite(true);
+        let heprup = HEPRUP {
+            EBMUP: [f64::NAN, 6500.],
+            ..HEPRUP::empty()
+        };
+        assert!(matches!(
+            writer.heprup(&heprup),
+            Err(WriteError::NonFinite(name)) if name == "EBMUP(1)"
+        ));
+        writer.heprup(&HEPRUP::empty()).unwrap();
+        let len = writer.stream.len();
+        assert!(matches!(
+            writer.hepeup(&event),
+            Err(WriteError::NonFinite(name)) if name == "PUP(1, 3)"
+        ));
+        assert_eq!(writer.stream.len(), len);
+    }
+
+    #[test]
+    fn write_minimal() {
+        let heprup = HEPRUP {
+            NPRUP: 1,
+            XSECUP: vec![1.],
+            XERRUP: vec![0.1],
+            XMAXUP: vec![1.],
+            LPRUP: vec![1],
+            ..HEPRUP::empty()
+        };
+        let events: Vec<_> = (1..=3)
+            .map(|n| HEPEUP {
+                IDRUP: 1,
+                XWGTUP: n as f64,
+                ..HEPEUP::empty()
+            })
+            .collect();
+        let mut output = vec![];
+        super::write_minimal(&mut output, "3.0", &heprup, events.clone())
+            .unwrap();
+
+        let mut reader = crate::Reader::new(output.as_slice()).unwrap();
+        assert_eq!(reader.version(), "3.0");
+        assert_eq!(reader.heprup(), &heprup);
+        for expected in &events {
+            assert_eq!(&reader.hepeup().unwrap().unwrap(), expected);
+        }
+        assert!(reader.hepeup().unwrap().is_none());
+
+        let broken = HEPRUP { NPRUP: 2, ..heprup };
+        let mut output = vec![];
+        assert!(matches!(
+            super::write_minimal(&mut output, "3.0", &broken, events),
+            Err(WriteError::MismatchedSubprocesses)
+        ));
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains(LHEF_LAST_LINE));
     }
-    *output += &format!("</{}>", xml.name);
 }